@@ -0,0 +1,77 @@
+//! The same clients for code that isn't async, like a script run from cron. Each [BlockingModem]
+//! runs the async client on its own single-threaded runtime, so it can't be used from inside
+//! another tokio runtime.
+
+use crate::payloads::{LogEntry, Metrics};
+use crate::snapshot::ModemSnapshot;
+use crate::{ClientOptions, ClientStats, Error, Model, Modem};
+use std::collections::HashMap;
+use tokio::runtime::{Builder, Runtime};
+
+/// Blocking version of [crate::detect]
+pub fn detect(endpoint: &str, options: &ClientOptions) -> Result<Option<Model>, Error> {
+    runtime()?.block_on(crate::detect(endpoint, options))
+}
+
+fn runtime() -> Result<Runtime, Error> {
+    Ok(Builder::new_current_thread().enable_all().build()?)
+}
+
+/// A [Modem] whose methods block until they're done
+pub struct BlockingModem {
+    runtime: Runtime,
+    modem: Box<dyn Modem>,
+}
+
+impl BlockingModem {
+    /// Wraps any client, e.g. a [crate::SOAPClient] set up by hand
+    pub fn new(modem: Box<dyn Modem>) -> Result<BlockingModem, Error> {
+        Ok(BlockingModem {
+            runtime: runtime()?,
+            modem,
+        })
+    }
+
+    /// Blocking version of [crate::connect]
+    pub fn connect(
+        model: Model,
+        endpoint: String,
+        options: &ClientOptions,
+    ) -> Result<BlockingModem, Error> {
+        let runtime = runtime()?;
+        // some clients need a runtime around to be built
+        let modem = {
+            let _guard = runtime.enter();
+            crate::connect(model, endpoint, options)?
+        };
+        Ok(BlockingModem { runtime, modem })
+    }
+
+    pub fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        self.runtime.block_on(self.modem.login(username, password))
+    }
+
+    pub fn metrics(&mut self) -> Result<Metrics, Error> {
+        self.runtime.block_on(self.modem.metrics())
+    }
+
+    pub fn logs(&mut self) -> Result<Vec<LogEntry>, Error> {
+        self.runtime.block_on(self.modem.logs())
+    }
+
+    pub fn device_info(&mut self) -> Result<HashMap<String, String>, Error> {
+        self.runtime.block_on(self.modem.device_info())
+    }
+
+    pub fn reboot(&mut self) -> Result<(), Error> {
+        self.runtime.block_on(self.modem.reboot())
+    }
+
+    pub fn scrape(&mut self) -> Result<ModemSnapshot, Error> {
+        self.runtime.block_on(self.modem.scrape())
+    }
+
+    pub fn stats(&self) -> ClientStats {
+        self.modem.stats()
+    }
+}
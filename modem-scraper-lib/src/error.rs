@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use thiserror::Error;
+
+/// Everything that can go wrong talking to a modem
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to reach modem: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("modem returned {0}")]
+    Status(StatusCode),
+    /// the modem eventually forgets our session, logging in again fixes it
+    #[error("modem rejected our session")]
+    Unauthorized,
+    #[error("unable to log in: {0}")]
+    Login(&'static str),
+    /// after failed logins, so the modem doesn't lock the account
+    #[error("not logging in again until {0}, after too many failed logins")]
+    LoginHeldOff(DateTime<Utc>),
+    #[error("modem's certificate doesn't match the pinned fingerprint")]
+    PinMismatch,
+    #[error("unable to deserialize reply: {0}")]
+    Deserialize(#[from] serde_json::Error),
+    #[error("unable to parse XML reply: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("unable to parse reply: {0}")]
+    Parse(String),
+    /// HNAP replies with 200 OK and an `ERROR` result instead of a useful status code
+    #[error("modem said there was an error with {0}")]
+    Modem(String),
+    #[error("unable to tell which model the modem is, set `model` in the config")]
+    UnknownModel,
+    #[error("{0} isn't supported for this model")]
+    Unsupported(&'static str),
+    /// asked for something this build left out
+    #[error("built without the `{0}` feature")]
+    MissingFeature(&'static str),
+    #[cfg(feature = "snmp")]
+    #[error("SNMP request failed: {0}")]
+    Snmp(String),
+    /// for [crate::blocking]
+    #[cfg(feature = "blocking")]
+    #[error("unable to start a runtime: {0}")]
+    Runtime(#[from] std::io::Error),
+}
+
+impl Error {
+    /// The modem answered, but with something we couldn't make sense of
+    pub fn is_parse(&self) -> bool {
+        matches!(
+            self,
+            Error::Deserialize(_) | Error::Xml(_) | Error::Parse(_)
+        )
+    }
+}
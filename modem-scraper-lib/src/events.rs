@@ -0,0 +1,226 @@
+//! DOCSIS event IDs, from the event table in the OSSI spec, and the CM-STATUS event types from
+//! MULPI. Only the events modems commonly log are here. Most modems don't put the ID in the log
+//! line, but the message is the spec's wording, so that's matched on too.
+
+/// One entry of the catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventCode {
+    pub id: u32,
+    /// the DOCSIS event level, `critical`, `error`, `warning` or `notice`
+    pub severity: &'static str,
+    /// as the modem logs it, minus the `;CM-MAC=...` on the end
+    pub message: &'static str,
+    /// what it means, for people
+    pub description: &'static str,
+}
+
+macro_rules! event_codes {
+    ($(($id:literal, $severity:literal, $message:literal, $description:literal)),+ $(,)?) => {
+        &[$(EventCode {
+            id: $id,
+            severity: $severity,
+            message: $message,
+            description: $description,
+        }),+]
+    };
+}
+
+pub const EVENT_CODES: &[EventCode] = event_codes![
+    (
+        68000100,
+        "critical",
+        "DHCP FAILED - Discover sent, no offer received",
+        "The modem couldn't get an address from the ISP's DHCP server"
+    ),
+    (
+        68000200,
+        "critical",
+        "DHCP FAILED - Request sent, No response",
+        "The ISP's DHCP server offered an address but never confirmed it"
+    ),
+    (
+        68000300,
+        "warning",
+        "DHCP WARNING - Non-critical field invalid in response",
+        "The ISP's DHCP reply had a field the modem didn't like, but it carried on"
+    ),
+    (
+        68000400,
+        "critical",
+        "DHCP FAILED - Critical field invalid in response",
+        "The ISP's DHCP reply was unusable"
+    ),
+    (
+        68010100,
+        "warning",
+        "ToD request sent - No Response received",
+        "The ISP's time server didn't answer, so the modem's clock may be wrong"
+    ),
+    (
+        68010200,
+        "warning",
+        "ToD Response received - Invalid data format",
+        "The ISP's time server answered with something the modem couldn't read"
+    ),
+    (
+        69010100,
+        "notice",
+        "SW Download INIT - Via NMS",
+        "The ISP started a firmware download"
+    ),
+    (
+        69010200,
+        "notice",
+        "SW Download INIT - Via Config file",
+        "The modem's config file told it to download new firmware"
+    ),
+    (
+        69011200,
+        "notice",
+        "SW download Successful - Via NMS",
+        "Firmware the ISP pushed downloaded successfully"
+    ),
+    (
+        69011300,
+        "notice",
+        "SW download Successful - Via Config file",
+        "Firmware from the config file downloaded successfully"
+    ),
+    (
+        67061600,
+        "warning",
+        "Dynamic Range Window violation",
+        "The upstream channels' transmit powers are too far apart, usually from too much \
+         attenuation on the line"
+    ),
+    (
+        82000200,
+        "critical",
+        "No Ranging Response received - T3 time-out",
+        "The CMTS didn't answer a ranging request (T3). A few are normal, lots point to upstream \
+         noise or a bad upstream power level."
+    ),
+    (
+        82000300,
+        "critical",
+        "Ranging Request Retries exhausted",
+        "The CMTS never answered ranging, so the modem gave up on the upstream channel"
+    ),
+    (
+        82000400,
+        "critical",
+        "Received Response to Broadcast Maintenance Request, But no Unicast Maintenance \
+         opportunities received - T4 time out",
+        "The CMTS stopped giving the modem chances to range (T4), usually upstream noise. The \
+         modem reinitializes."
+    ),
+    (
+        82000500,
+        "critical",
+        "Started Unicast Maintenance Ranging - No Response received - T3 time-out",
+        "The CMTS didn't answer a periodic ranging request (T3)"
+    ),
+    (
+        82000600,
+        "critical",
+        "Unicast Maintenance Ranging attempted - No response - Retries exhausted",
+        "The CMTS stopped answering periodic ranging, so the modem gave up on the upstream channel"
+    ),
+    (
+        82000700,
+        "critical",
+        "Unicast Ranging Received Abort Response - Re-initializing MAC",
+        "The CMTS told the modem to stop ranging and start over"
+    ),
+    (
+        84000100,
+        "critical",
+        "SYNC Timing Synchronization failure - Failed to acquire QAM/QPSK symbol timing",
+        "The modem couldn't lock onto a downstream channel, usually a signal level problem"
+    ),
+    (
+        84000200,
+        "critical",
+        "SYNC Timing Synchronization failure - Failed to acquire FEC framing",
+        "The modem found a downstream channel but couldn't decode it"
+    ),
+    (
+        84000300,
+        "critical",
+        "SYNC Timing Synchronization failure - Acquired FEC framing - Failed to acquire MPEG2 \
+         Sync",
+        "The modem decoded a downstream channel but couldn't make sense of what was on it"
+    ),
+    (
+        84000400,
+        "critical",
+        "SYNC Timing Synchronization failure - Failed to receive MAC SYNC frame within time-out \
+         period",
+        "The CMTS's timing messages stopped arriving on the downstream"
+    ),
+    (
+        84000500,
+        "critical",
+        "SYNC Timing Synchronization failure - Loss of Sync",
+        "The modem lost its lock on the downstream"
+    ),
+    (
+        84020200,
+        "critical",
+        "Lost MDD Timeout",
+        "The CMTS's channel descriptors stopped arriving on a downstream channel"
+    ),
+    (
+        90000000,
+        "notice",
+        "MIMO Event MIMO",
+        "The modem's IP provisioning mode was set by its config file. Harmless."
+    ),
+];
+
+/// CM-STATUS event types, by their `Event Type Code`
+const CM_STATUS_EVENTS: &[(u8, &str)] = &[
+    (1, "Secondary channel MDD timeout"),
+    (2, "QAM/FEC lock failure"),
+    (3, "Sequence out of range"),
+    (4, "Secondary channel MDD recovery"),
+    (5, "QAM/FEC lock recovery"),
+    (6, "T4 timeout"),
+    (7, "T3 retries exceeded"),
+    (8, "Successful ranging after T3 retries exceeded"),
+    (9, "Running on battery backup"),
+    (10, "Back on AC power"),
+    (11, "MAC removal"),
+    (16, "OFDM downstream profile failure"),
+    (17, "Primary downstream changed"),
+    (18, "DPD mismatch"),
+    (20, "NCP profile failure"),
+    (21, "PLC failure"),
+    (22, "NCP profile recovery"),
+    (23, "PLC recovery"),
+    (24, "OFDM downstream profile recovery"),
+    (25, "OFDMA upstream profile failure"),
+    (26, "MAP storage overflow"),
+    (27, "MAP storage almost full"),
+];
+
+pub fn by_id(id: u32) -> Option<&'static EventCode> {
+    EVENT_CODES.iter().find(|event_code| event_code.id == id)
+}
+
+/// Going by the wording, ignoring case and anything after the first `;`. Firmware sometimes adds
+/// to the end of the spec's message (`MIMO Event MIMO: Stored MIMO=-1 post cfg file MIMO=-1`), so
+/// the message only has to start with it.
+pub fn by_message(message: &str) -> Option<&'static EventCode> {
+    let message = message.split(';').next()?.trim().to_lowercase();
+    EVENT_CODES
+        .iter()
+        .find(|event_code| message.starts_with(&event_code.message.to_lowercase()))
+}
+
+pub fn cm_status_event(code: u8) -> Option<&'static str> {
+    CM_STATUS_EVENTS
+        .iter()
+        .find(|(event_code, _)| *event_code == code)
+        .map(|(_, description)| *description)
+}
@@ -0,0 +1,102 @@
+use crate::payloads::{Channel, CodewordDelta, Metrics};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+// Rough DOCSIS recommended ranges: inside the first range is perfect, outside the second is as bad
+// as it gets, and the score falls off linearly in between.
+
+/// dBmV
+const DOWNSTREAM_POWER: (RangeInclusive<f64>, RangeInclusive<f64>) = (-7.0..=7.0, -15.0..=15.0);
+/// dB, for 256-QAM
+const DOWNSTREAM_SNR: (RangeInclusive<f64>, RangeInclusive<f64>) =
+    (33.0..=f64::MAX, 30.0..=f64::MAX);
+/// dBmV
+const UPSTREAM_POWER: (RangeInclusive<f64>, RangeInclusive<f64>) = (38.0..=48.0, 35.0..=51.0);
+/// codewords per second
+const UNCORRECTABLES_RATE: (RangeInclusive<f64>, RangeInclusive<f64>) = (0.0..=0.0, 0.0..=1.0);
+
+/// 1 inside `good`, 0 outside `limits`, linear in between
+fn score(value: f64, (good, limits): &(RangeInclusive<f64>, RangeInclusive<f64>)) -> f64 {
+    if good.contains(&value) {
+        1.0
+    } else if !limits.contains(&value) {
+        0.0
+    } else if value < *good.start() {
+        (value - limits.start()) / (good.start() - limits.start())
+    } else {
+        (limits.end() - value) / (limits.end() - good.end())
+    }
+}
+
+impl Channel {
+    /// Whether each of the channel's readings is inside its recommended range, as `(reading, in
+    /// range)`. Nothing an unlocked channel reads is.
+    pub fn in_range(&self) -> Vec<(&'static str, bool)> {
+        let in_range = |locked: bool, value: f64, (good, _): &(RangeInclusive<f64>, _)| {
+            locked && good.contains(&value)
+        };
+        match self {
+            Channel::Downstream(c) => vec![
+                ("power", in_range(c.lock_status, c.power, &DOWNSTREAM_POWER)),
+                ("snr", in_range(c.lock_status, c.snr, &DOWNSTREAM_SNR)),
+            ],
+            Channel::Upstream(c) => {
+                vec![("power", in_range(c.lock_status, c.power, &UPSTREAM_POWER))]
+            }
+            Channel::OfdmaUpstream(c) => {
+                vec![("power", in_range(c.lock_status, c.power, &UPSTREAM_POWER))]
+            }
+        }
+    }
+
+    /// The channel's entry in `codeword_deltas`, which only downstream channels have
+    pub fn codeword_delta<'a>(
+        &self,
+        codeword_deltas: &'a HashMap<u8, CodewordDelta>,
+    ) -> Option<&'a CodewordDelta> {
+        match self {
+            Channel::Downstream(c) => codeword_deltas.get(&c.channel_id),
+            _ => None,
+        }
+    }
+
+    /// 0 to 100, from whichever of the channel's power, SNR and uncorrectable rate is worst. An
+    /// unlocked channel is 0. Uncorrectables only count with a `codeword_delta` to go by.
+    pub fn health(&self, codeword_delta: Option<&CodewordDelta>) -> f64 {
+        let scores = match self {
+            Channel::Downstream(c) if c.lock_status => {
+                let mut scores = vec![
+                    score(c.power, &DOWNSTREAM_POWER),
+                    score(c.snr, &DOWNSTREAM_SNR),
+                ];
+                if let Some(delta) = codeword_delta {
+                    if delta.seconds > 0.0 {
+                        let rate = delta.uncorrectables as f64 / delta.seconds;
+                        scores.push(score(rate, &UNCORRECTABLES_RATE));
+                    }
+                }
+                scores
+            }
+            Channel::Upstream(c) if c.lock_status => vec![score(c.power, &UPSTREAM_POWER)],
+            Channel::OfdmaUpstream(c) if c.lock_status => vec![score(c.power, &UPSTREAM_POWER)],
+            _ => vec![0.0],
+        };
+        scores.into_iter().fold(1.0, f64::min) * 100.0
+    }
+}
+
+impl Metrics {
+    /// Average [Channel::health] across every channel, or nothing without channels.
+    /// `codeword_deltas` are by downstream channel id.
+    pub fn health(&self, codeword_deltas: &HashMap<u8, CodewordDelta>) -> Option<f64> {
+        if self.channels.is_empty() {
+            return None;
+        }
+        let total: f64 = self
+            .channels
+            .iter()
+            .map(|channel| channel.health(channel.codeword_delta(codeword_deltas)))
+            .sum();
+        Some(total / self.channels.len() as f64)
+    }
+}
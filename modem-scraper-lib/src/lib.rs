@@ -1,72 +1,361 @@
+use crate::logging::{debug, error, info, warn};
+use async_trait::async_trait;
 use hmac::{Hmac, Mac};
 use md5::Md5;
-use tracing::{debug, error, info, instrument};
+#[cfg(feature = "tracing")]
+use tracing::{field, instrument};
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod error;
+pub mod events;
+pub mod health;
+mod logging;
+mod login_guard;
+pub mod mb86xx;
+#[cfg(feature = "mock-server")]
+pub mod mock;
+pub mod modem;
+pub mod netgear;
 pub mod payloads;
+pub mod replay;
+pub mod sb8200;
+pub mod snapshot;
+#[cfg(feature = "snmp")]
+pub mod snmp;
+mod xml;
+pub use error::Error;
+use login_guard::LoginGuard;
+pub use login_guard::LoginLimits;
+pub use modem::*;
 use payloads::*;
 use reqwest::{self, StatusCode};
 use serde::de::DeserializeOwned;
-use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tracing_unwrap::{OptionExt, ResultExt};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 // HMAC MD5
 type HmacMd5 = Hmac<Md5>;
-const UNDEFINED_PRIVATE_KEY: &str = "withoutloginkey";
-const SOAP_DOMAIN: &str = "http://purenetworks.com/HNAP1/";
+pub(crate) const UNDEFINED_PRIVATE_KEY: &str = "withoutloginkey";
+pub(crate) const SOAP_DOMAIN: &str = "http://purenetworks.com/HNAP1/";
+const MAX_RELOGIN_ATTEMPTS: u32 = 3;
+const RELOGIN_BACKOFF: Duration = Duration::from_secs(1);
 
 /// Uppercase the hash resulting from running HMAC-MD5 with key on data
 pub fn hex_hmac_md5(key: &[u8], data: &[u8]) -> String {
-    let mut mac = HmacMd5::new_from_slice(key).unwrap();
+    let mut mac = HmacMd5::new_from_slice(key).expect("HMAC takes keys of any length");
     mac.update(data);
     let result = mac.finalize().into_bytes();
     hex::encode_upper(result)
 }
 
-#[derive(Default, Debug)]
+/// HNAP dialects differ slightly between vendors
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HnapVariant {
+    #[default]
+    Arris,
+    Motorola,
+}
+
+/// How the timestamp in `HNAP_AUTH` is written. Newer S33 firmware wants seconds, and answers
+/// `ERROR` to every `GetMultipleHNAPs` sub-action signed with milliseconds instead of saying why.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HnapTimestamp {
+    /// milliseconds, switching to seconds if the modem answers `ERROR` before anything's worked
+    #[default]
+    Auto,
+    Millis,
+    /// mod 2,000,000,000, same as the firmware's own page
+    Seconds,
+}
+
+impl HnapTimestamp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HnapTimestamp::Auto => "auto",
+            HnapTimestamp::Millis => "millis",
+            HnapTimestamp::Seconds => "seconds",
+        }
+    }
+
+    /// The scheme to try when this one doesn't work
+    fn alternate(&self) -> HnapTimestamp {
+        match self {
+            HnapTimestamp::Seconds => HnapTimestamp::Millis,
+            HnapTimestamp::Auto | HnapTimestamp::Millis => HnapTimestamp::Seconds,
+        }
+    }
+
+    fn now(&self) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        match self {
+            HnapTimestamp::Seconds => (now.as_secs() % 2_000_000_000).to_string(),
+            HnapTimestamp::Auto | HnapTimestamp::Millis => now.as_millis().to_string(),
+        }
+    }
+}
+
+/// The `HNAP_AUTH` header, which is HMAC(private key, timestamp + SOAP action URI) + timestamp
+pub(crate) fn hnap_auth(private_key: &str, action: &str, timestamp: HnapTimestamp) -> String {
+    let current_time = timestamp.now();
+    let soap_action_uri = format!(r#"{}{}"#, SOAP_DOMAIN, action);
+    let message = current_time.to_owned() + &soap_action_uri;
+
+    hex_hmac_md5(private_key.as_bytes(), message.as_bytes()) + " " + &current_time
+}
+
+/// How HNAP requests are encoded. Most firmware takes JSON, but some revisions only accept the XML
+/// SOAP envelopes.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HnapTransport {
+    #[default]
+    Json,
+    Xml,
+}
+
+/// How many times `metrics()` and `logs()` try before giving up, and how long to wait in between.
+/// The wait doubles after every failure, up to `max_backoff_ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// wait somewhere between half and all of the backoff, so modems don't retry in lockstep
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 10_000,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait after the `attempt`th failure
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff_ms = self
+            .initial_backoff_ms
+            .saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff_ms);
+        let backoff_ms = match self.jitter {
+            // not worth pulling in rand for this
+            true => {
+                let nanos = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .subsec_nanos();
+                backoff_ms / 2 + u64::from(nanos) % (backoff_ms / 2 + 1)
+            }
+            false => backoff_ms,
+        };
+        Duration::from_millis(backoff_ms)
+    }
+}
+
+/// Unwraps `<action>Response` out of a reply and deserializes it
+pub(crate) fn parse_reply<T>(action: &str, serialized_json: serde_json::Value) -> Result<T, Error>
+where
+    T: DeserializeOwned + std::fmt::Debug + HasResult,
+{
+    // check before deserializing, an expired session leaves out everything but the result
+    let result = &serialized_json[action.to_owned() + "Response"][action.to_owned() + "Result"];
+    if result == "UN-AUTH" {
+        return Err(Error::Unauthorized);
+    }
+    // and so can an error, e.g. `GetMultipleHNAPs` signed with the wrong kind of timestamp
+    if result == "ERROR" {
+        error!("{:?}", serialized_json);
+        return Err(Error::Modem(action.to_owned()));
+    }
+    // rebind here to the concrete type so that we can return the right type
+    let mut serialized_json = serde_json::value::from_value::<HashMap<String, T>>(serialized_json)?;
+
+    // serialized_json[action + "Response"][action + "Result"] will tell us if bad JSON returned
+    // unclear why they couldn't just 400/500 that, but whatever.
+    let unwrapped_json = serialized_json
+        .remove(&(action.to_owned() + "Response"))
+        .ok_or_else(|| Error::Parse(format!("no {}Response in reply", action)))?;
+    match unwrapped_json.get_result().as_str() {
+        "ERROR" => {
+            error!("{:?}", unwrapped_json);
+            Err(Error::Modem(action.to_owned()))
+        }
+        _ => Ok(unwrapped_json),
+    }
+}
+
+/// `Debug` is written out by hand so the password and session never end up in a log or a span
+#[derive(Default)]
 pub struct SOAPClient {
     client: reqwest::Client,
     endpoint: String,
     private_key: String,
     cookie: String,
+    variant: HnapVariant,
+    transport: HnapTransport,
+    /// kept around so we can log in again when the session expires
+    credentials: Option<(String, String)>,
+    retry_policy: RetryPolicy,
+    /// log lines that didn't parse and have already been warned about. Only the ones still in the
+    /// modem's log are kept.
+    quarantined_log_lines: HashSet<String>,
+    dump_raw: Option<PathBuf>,
+    stats: ClientStats,
+    login_guard: LoginGuard,
+    /// never [HnapTimestamp::Auto], that's what `detect_timestamp` is for
+    timestamp: HnapTimestamp,
+    /// until a request works, when the timestamp scheme is `auto`
+    detect_timestamp: bool,
+}
+
+impl std::fmt::Debug for SOAPClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SOAPClient")
+            .field("endpoint", &self.endpoint)
+            .field("variant", &self.variant)
+            .field("transport", &self.transport)
+            .field(
+                "username",
+                &self.credentials.as_ref().map(|(username, _)| username),
+            )
+            .field("timestamp", &self.timestamp)
+            .finish_non_exhaustive()
+    }
 }
 
 impl SOAPClient {
-    pub fn new(endpoint: String, accept_invalid_certs: bool) -> SOAPClient {
-        SOAPClient {
-            client: reqwest::Client::builder()
-                .danger_accept_invalid_certs(accept_invalid_certs)
-                .build()
-                .unwrap(),
+    /// Fails if the HTTP client can't be built, e.g. the TLS backend won't load, or a pinned
+    /// certificate is asked for over plain HTTP
+    pub fn new(endpoint: String, options: &ClientOptions) -> Result<SOAPClient, Error> {
+        options.check_endpoint(&endpoint)?;
+        Ok(SOAPClient {
+            client: options.client_builder().build()?,
             endpoint,
             private_key: UNDEFINED_PRIVATE_KEY.to_string(),
             cookie: "".to_string(),
+            variant: HnapVariant::default(),
+            transport: options.hnap_transport,
+            credentials: None,
+            retry_policy: options.retry_policy,
+            quarantined_log_lines: HashSet::new(),
+            dump_raw: options.dump_raw.clone(),
+            stats: ClientStats::default(),
+            login_guard: LoginGuard::new(options.login_limits.clone()),
+            timestamp: match options.hnap_timestamp {
+                HnapTimestamp::Auto => HnapTimestamp::Millis,
+                timestamp => timestamp,
+            },
+            detect_timestamp: options.hnap_timestamp == HnapTimestamp::Auto,
+        })
+    }
+
+    pub fn with_variant(mut self, variant: HnapVariant) -> SOAPClient {
+        self.variant = variant;
+        self
+    }
+
+    /// [SOAPClient::send_soap_action], tried again on failure according to the retry policy
+    pub(crate) async fn send_soap_action_with_retry<T>(
+        &mut self,
+        action: &str,
+        additional_params: &HashMap<&str, &str>,
+    ) -> Result<T, Error>
+    where
+        T: DeserializeOwned + std::fmt::Debug + HasResult,
+    {
+        let mut attempt = 1;
+        loop {
+            match self.send_soap_action(action, additional_params).await {
+                Err(e) if attempt < self.retry_policy.max_attempts => {
+                    let backoff = self.retry_policy.backoff(attempt);
+                    warn!("{} failed ({}), retrying in {:?}", action, e, backoff);
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
         }
     }
 
-    async fn send_soap_action<T>(
+    /// Sends the action, logging in again (with backoff) if the session has expired, and trying the
+    /// other `HNAP_AUTH` timestamp scheme if it's still being detected
+    pub(crate) async fn send_soap_action<T>(
         &mut self,
         action: &str,
         additional_params: &HashMap<&str, &str>,
-    ) -> Result<T, &str>
+    ) -> Result<T, Error>
     where
         T: DeserializeOwned + std::fmt::Debug + HasResult,
     {
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis()
-            .to_string();
-        let soap_action_uri = format!(r#"{}{}"#, SOAP_DOMAIN, action);
-        let message = current_time.to_owned() + &soap_action_uri;
-
-        let auth =
-            hex_hmac_md5(self.private_key.as_bytes(), message.as_bytes()) + " " + &current_time;
-        // debug!("{}", auth);
+        let mut attempt = 1;
+        let mut backoff = RELOGIN_BACKOFF;
+        let mut switched_timestamp = false;
+        loop {
+            match self.request(action, additional_params).await {
+                Ok(reply) => {
+                    if self.detect_timestamp {
+                        info!("HNAP_AUTH timestamps are in {}", self.timestamp.as_str());
+                        self.detect_timestamp = false;
+                    }
+                    return Ok(reply);
+                }
+                Err(Error::Modem(_)) if self.detect_timestamp && !switched_timestamp => {
+                    switched_timestamp = true;
+                    self.timestamp = self.timestamp.alternate();
+                    warn!(
+                        "{} failed, trying HNAP_AUTH timestamps in {}",
+                        action,
+                        self.timestamp.as_str()
+                    );
+                }
+                Err(Error::Unauthorized) if attempt < MAX_RELOGIN_ATTEMPTS => {
+                    let (username, password) = match self.credentials.clone() {
+                        Some(credentials) => credentials,
+                        None => return Err(Error::Unauthorized),
+                    };
+                    info!("Session expired, logging in again in {:?}", backoff);
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                    self.login(&username, &password).await?;
+                }
+                Err(e) => {
+                    // it wasn't the timestamp after all
+                    if switched_timestamp {
+                        self.timestamp = self.timestamp.alternate();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
 
-        // additional_params gets nested under the action for no reason
-        let mut nested_additional_params = HashMap::new();
-        nested_additional_params.insert(action, additional_params);
-        debug!("Sending payload: {:?}", nested_additional_params);
+    /// Sends the action once. Login goes through here directly so it can't recurse.
+    #[cfg_attr(feature = "tracing", instrument(
+        skip(self, additional_params),
+        fields(latency_ms = field::Empty, response_bytes = field::Empty, result = field::Empty)
+    ))]
+    async fn request<T>(
+        &mut self,
+        action: &str,
+        additional_params: &HashMap<&str, &str>,
+    ) -> Result<T, Error>
+    where
+        T: DeserializeOwned + std::fmt::Debug + HasResult,
+    {
+        let auth = hnap_auth(&self.private_key, action, self.timestamp);
+        // debug!("{}", auth);
 
         // create the request
         let req = self
@@ -74,47 +363,143 @@ impl SOAPClient {
             .post(&self.endpoint)
             .header("SOAPAction", SOAP_DOMAIN.to_owned() + action)
             .header("HNAP_AUTH", auth)
-            .header(
-                "Cookie",
-                format!(
-                    "Secure; uid={}; PrivateKey={}",
-                    self.cookie, self.private_key
-                ),
-            )
-            .json(&nested_additional_params);
+            .header("Cookie", self.cookie_header());
+        let req = match self.transport {
+            HnapTransport::Json => {
+                // additional_params gets nested under the action for no reason
+                let mut nested_additional_params = HashMap::new();
+                nested_additional_params.insert(action, additional_params);
+                debug!("Sending payload: {:?}", nested_additional_params);
+                req.json(&nested_additional_params)
+            }
+            HnapTransport::Xml => {
+                let envelope = xml::envelope(action, additional_params);
+                debug!("Sending payload: {}", envelope);
+                req.header("Content-Type", "text/xml; charset=utf-8")
+                    .body(envelope)
+            }
+        };
         debug!("Sending request: {:?}", req);
 
         // fire off the request
-        let res = req.send().await.unwrap_or_log();
+        let started = Instant::now();
+        let res = req.send().await?;
+
+        let status = res.status();
+        if status != StatusCode::OK {
+            let body = res.bytes().await.map(|body| body.len()).unwrap_or_default();
+            self.record_request(action, started, body, status.as_str());
+            if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+                debug!("{} answered {}", action, status);
+                return Err(Error::Unauthorized);
+            }
+            error!("{} answered {}", action, status);
+            return Err(Error::Status(status));
+        }
 
         // serialize to Value so we can print out the whole payload first
-        let serialized_json: serde_json::Value = match res.status() {
-            StatusCode::OK => res.json().await.unwrap_or_log(),
-            _ => {
-                error!("{:?}", res);
-                return Err("Modem did not return 200 OK, dumped response to log");
+        let body = res.text().await?;
+        let serialized_json: Result<serde_json::Value, Error> = match self.transport {
+            HnapTransport::Json => {
+                self.dump_raw(action, "json", &body);
+                serde_json::from_str(&body).map_err(Error::from)
+            }
+            HnapTransport::Xml => {
+                self.dump_raw(action, "xml", &body);
+                xml::body_to_json(&body).map_err(Error::from)
             }
         };
+        let result = match &serialized_json {
+            Ok(json) => json[format!("{}Response", action)][format!("{}Result", action)]
+                .as_str()
+                .unwrap_or("none"),
+            Err(_) => "unparseable",
+        };
+        self.record_request(action, started, body.len(), result);
+        let serialized_json = serialized_json?;
         debug!("JSON reply from modem: {:?}", serialized_json);
-        // rebind here to the concrete type so that we can return the right type
-        let mut serialized_json =
-            serde_json::value::from_value::<HashMap<String, T>>(serialized_json).unwrap_or_log();
-
-        // serialized_json[action + "Response"][action + "Result"] will tell us if bad JSON returned
-        // unclear why they couldn't just 400/500 that, but whatever.
-        let unwrapped_json = serialized_json
-            .remove(&(action.to_owned() + "Response"))
-            .unwrap_or_log();
-        match unwrapped_json.get_result().as_str() {
-            "ERROR" => {
-                error!("{:?}", serialized_json);
-                Err("JSON said there was an ERROR, aborting")
-            }
-            _ => Ok(unwrapped_json),
+        parse_reply(action, serialized_json)
+    }
+
+    /// Into [ClientStats::requests] and the `request` span
+    fn record_request(
+        &mut self,
+        action: &str,
+        started: Instant,
+        response_bytes: usize,
+        result: &str,
+    ) {
+        let latency = started.elapsed();
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            span.record("latency_ms", latency.as_millis() as u64);
+            span.record("response_bytes", response_bytes as u64);
+            span.record("result", result);
+        }
+        debug!(
+            "{} took {:?}: {}, {} bytes",
+            action, latency, result, response_bytes
+        );
+
+        let request = self.stats.requests.entry(action.to_owned()).or_default();
+        request.latency = latency;
+        request.response_bytes = response_bytes as u64;
+        request.result = result.to_owned();
+        *request.results.entry(result.to_owned()).or_default() += 1;
+    }
+
+    /// Writes `body` to `<timestamp>-<host>-<action>.<extension>` under the `dump_raw` directory.
+    /// Login replies are left out since they carry the session. Failing to write only gets logged.
+    fn dump_raw(&self, action: &str, extension: &str, body: &str) {
+        let directory = match &self.dump_raw {
+            Some(directory) if action != "Login" => directory,
+            _ => return,
+        };
+        let host = reqwest::Url::parse(&self.endpoint)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_owned))
+            .unwrap_or_default();
+        let path = directory.join(format!(
+            "{}-{}-{}.{}",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+            host,
+            action,
+            extension
+        ));
+        if let Err(e) = std::fs::create_dir_all(directory).and_then(|_| std::fs::write(&path, body))
+        {
+            warn!("Unable to write {}: {}", path.display(), e);
         }
     }
 
-    #[instrument]
+    /// Warns about each line in `parsed` that didn't parse, once while it's still in the log
+    pub(crate) fn quarantine_log_lines(&mut self, parsed: ParsedLogs) -> Vec<LogEntry> {
+        self.quarantined_log_lines
+            .retain(|line| parsed.unparsed.contains(line));
+        for line in parsed.unparsed {
+            if !self.quarantined_log_lines.contains(&line) {
+                warn!("Unable to parse log line {:?}", line);
+                self.quarantined_log_lines.insert(line);
+            }
+        }
+        parsed.entries
+    }
+
+    fn cookie_header(&self) -> String {
+        match self.variant {
+            HnapVariant::Arris => format!(
+                "Secure; uid={}; PrivateKey={}",
+                self.cookie, self.private_key
+            ),
+            // motorola doesn't like the leading `Secure`
+            HnapVariant::Motorola => {
+                format!("uid={}; PrivateKey={}", self.cookie, self.private_key)
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "tracing", instrument(skip(self, password, cookie)))]
     async fn login_with_challenge(
         &mut self,
         username: &str,
@@ -122,7 +507,7 @@ impl SOAPClient {
         public_key: &str,
         challenge: &str,
         cookie: &str,
-    ) -> Result<LoginWithChallengeResponse, &str> {
+    ) -> Result<LoginWithChallengeResponse, Error> {
         self.cookie = cookie.to_string();
         // compute the private key, which is HMAC(pubkey + password, challenge)
         let private_key = hex_hmac_md5(
@@ -145,32 +530,51 @@ impl SOAPClient {
             ("PrivateLogin", "LoginPassword"),
         ]);
 
-        let login_response: LoginWithChallengeResponse = self
-            .send_soap_action("Login", &request_hashmap)
-            .await
-            .expect("Unable to login with calculated credentials");
+        let login_response: LoginWithChallengeResponse =
+            self.request("Login", &request_hashmap).await?;
 
         match login_response.get_result().as_str() {
-            "OK_CHANGED" => Err("May need to reset login settings, idk haven't actually hit this"),
-            "FAILED" => Err("Username or password error"),
-            "LOCKUP" => Err("Max number of login attempts reached"),
-            "REBOOT" => Err("Account locked, reboot required to re-enable account"),
-            "OK" => Ok(login_response),
-            _ => Err("Unknown response from modem"),
+            "OK_CHANGED" => Err(Error::Login(
+                "May need to reset login settings, idk haven't actually hit this",
+            )),
+            "FAILED" => {
+                self.login_guard.failed();
+                Err(Error::Login("Username or password error"))
+            }
+            "LOCKUP" => {
+                self.login_guard.locked_out();
+                Err(Error::Login("Max number of login attempts reached"))
+            }
+            "REBOOT" => {
+                self.login_guard.locked_out();
+                Err(Error::Login(
+                    "Account locked, reboot required to re-enable account",
+                ))
+            }
+            "OK" => {
+                self.login_guard.succeeded();
+                Ok(login_response)
+            }
+            _ => Err(Error::Login("Unknown response from modem")),
         }
     }
 
-    #[instrument]
-    pub async fn login(&mut self, username: &str, password: &str) {
+    #[cfg_attr(feature = "tracing", instrument(skip(self, password)))]
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        self.credentials = Some((username.to_owned(), password.to_owned()));
+        // another failure now would only get the account locked, or locked for longer
+        if let Some(not_before) = self.login_guard.not_before() {
+            return Err(Error::LoginHeldOff(not_before));
+        }
+        // a stale key from an expired session would fail the challenge request
+        self.private_key = UNDEFINED_PRIVATE_KEY.to_string();
+
         // the first login request has an Action: request and retrieves the challenge + public key
         let request_hashmap: HashMap<&str, &str> =
             HashMap::from([("Action", "request"), ("Username", username)]);
 
         // challenge and pubkey should be contained here
-        let response: LoginResponse = self
-            .send_soap_action("Login", &request_hashmap)
-            .await
-            .expect("Unable to get challenge and pubkey from modem");
+        let response: LoginResponse = self.request("Login", &request_hashmap).await?;
 
         self.login_with_challenge(
             username,
@@ -179,42 +583,115 @@ impl SOAPClient {
             &response.challenge,
             &response.cookie,
         )
-        .await
-        .unwrap_or_log();
+        .await?;
+        self.stats.logins += 1;
+        Ok(())
+    }
+
+    /// Arris reboots through `SetArrisConfigurationInfo`, Motorola through `SetStatusSecuritySettings`
+    #[cfg_attr(feature = "tracing", instrument(skip(self)))]
+    pub async fn reboot(&mut self) -> Result<(), Error> {
+        match self.variant {
+            HnapVariant::Arris => {
+                let request_hashmap: HashMap<&str, &str> = HashMap::from([
+                    ("Action", "reboot"),
+                    ("SetEEEEnable", ""),
+                    ("LED_Status", ""),
+                ]);
+                let _: SetArrisConfigurationInfoResponse = self
+                    .send_soap_action("SetArrisConfigurationInfo", &request_hashmap)
+                    .await?;
+            }
+            HnapVariant::Motorola => {
+                let request_hashmap: HashMap<&str, &str> = HashMap::from([
+                    ("MotoStatusSecurityAction", "1"),
+                    ("MotoStatusSecXXX", "XXX"),
+                ]);
+                let _: SetStatusSecuritySettingsResponse = self
+                    .send_soap_action("SetStatusSecuritySettings", &request_hashmap)
+                    .await?;
+            }
+        }
+        Ok(())
     }
 
-    #[instrument]
-    pub async fn metrics(&mut self) -> GetMultipleHNAPsMetricsResponse {
+    #[cfg_attr(feature = "tracing", instrument(skip(self)))]
+    pub async fn metrics(&mut self) -> Result<GetMultipleHNAPsMetricsResponse, Error> {
         let request_hashmap: HashMap<&str, &str> = HashMap::from([
             ("GetArrisDeviceStatus", ""),
             ("GetArrisRegisterInfo", ""),
-            // ("GetArrisRegisterStatus", ""), // ok we don't really care
             ("GetCustomerStatusStartupSequence", ""),
             ("GetCustomerStatusConnectionInfo", ""),
             ("GetCustomerStatusDownstreamChannelInfo", ""),
             ("GetCustomerStatusUpstreamChannelInfo", ""),
         ]);
         let response: GetMultipleHNAPsMetricsResponse = self
+            .send_soap_action_with_retry("GetMultipleHNAPs", &request_hashmap)
+            .await?;
+
+        info!("{:#?}", response);
+        Ok(response)
+    }
+
+    /// Things that don't change between scrapes, like the serial number and config file
+    #[cfg_attr(feature = "tracing", instrument(skip(self)))]
+    pub async fn device_info(&mut self) -> Result<GetMultipleHNAPsDeviceInfoResponse, Error> {
+        let request_hashmap: HashMap<&str, &str> = HashMap::from([
+            ("GetArrisConfigurationInfo", ""),
+            ("GetArrisRegisterStatus", ""),
+            ("GetArrisRegisterInfo", ""),
+            ("GetArrisDeviceStatus", ""),
+        ]);
+        let response: GetMultipleHNAPsDeviceInfoResponse = self
             .send_soap_action("GetMultipleHNAPs", &request_hashmap)
-            .await
-            .expect("Unable to get metrics from modem");
+            .await?;
 
         info!("{:#?}", response);
-        response
+        Ok(response)
     }
 
-    #[instrument]
-    pub async fn logs(&mut self) -> GetMultipleHNAPsLogsResponse {
+    #[cfg_attr(feature = "tracing", instrument(skip(self)))]
+    pub async fn logs(&mut self) -> Result<GetMultipleHNAPsLogsResponse, Error> {
         let request_hashmap: HashMap<&str, &str> = HashMap::from([
             ("GetCustomerStatusLog", ""),
             ("GetCustomerStatusLogXXX", ""), // this just returns `XXX`, useless
         ]);
         let response: GetMultipleHNAPsLogsResponse = self
-            .send_soap_action("GetMultipleHNAPs", &request_hashmap)
-            .await
-            .expect("Unable to get logs from modem");
+            .send_soap_action_with_retry("GetMultipleHNAPs", &request_hashmap)
+            .await?;
 
         info!("{:#?}", response);
-        response
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Modem for SOAPClient {
+    async fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        SOAPClient::login(self, username, password).await
+    }
+
+    async fn metrics(&mut self) -> Result<Metrics, Error> {
+        Ok(SOAPClient::metrics(self).await?.into())
+    }
+
+    async fn logs(&mut self) -> Result<Vec<LogEntry>, Error> {
+        let parsed = SOAPClient::logs(self)
+            .await?
+            .get_customer_status_log_response
+            .customer_status_log_list;
+        Ok(self.quarantine_log_lines(parsed))
+    }
+
+    async fn reboot(&mut self) -> Result<(), Error> {
+        SOAPClient::reboot(self).await
+    }
+
+    async fn device_info(&mut self) -> Result<HashMap<String, String>, Error> {
+        Ok(SOAPClient::device_info(self).await?.tags())
+    }
+
+    fn stats(&self) -> ClientStats {
+        self.stats.clone()
     }
 }
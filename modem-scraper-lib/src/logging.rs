@@ -0,0 +1,7 @@
+//! `debug!` and friends go through tracing with the `tracing` feature, and through plain `log`
+//! without it, so library users get the messages either way
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use log::{debug, error, info, warn};
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{debug, error, info, warn};
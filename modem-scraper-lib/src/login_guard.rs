@@ -0,0 +1,131 @@
+//! Keeps failed logins from locking the account. The S33 answers `LOCKUP` after a handful of bad
+//! logins in a row, and `REBOOT` once it wants a power cycle before it'll take any more, which also
+//! locks everyone out of the web UI.
+
+use crate::logging::{error, warn};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long to hold off logging in after failures
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct LoginLimits {
+    /// after the first failed login. Doubles with every failure after that.
+    pub initial_backoff_seconds: u64,
+    pub max_backoff_seconds: u64,
+    /// after the modem says it's locked the account
+    pub lockout_seconds: u64,
+    /// where the failures are kept, so a restart (or a crash loop) doesn't start from scratch
+    pub state_file: Option<PathBuf>,
+}
+
+impl Default for LoginLimits {
+    fn default() -> Self {
+        LoginLimits {
+            initial_backoff_seconds: 60,
+            max_backoff_seconds: 3600,
+            lockout_seconds: 3600,
+            state_file: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct LoginState {
+    /// in a row
+    failures: u32,
+    /// no logins before this
+    not_before: Option<DateTime<Utc>>,
+}
+
+/// One per client
+#[derive(Debug, Default)]
+pub(crate) struct LoginGuard {
+    limits: LoginLimits,
+    state: LoginState,
+}
+
+impl LoginGuard {
+    /// Picks up where the state file left off, if there is one
+    pub(crate) fn new(limits: LoginLimits) -> LoginGuard {
+        let state = limits
+            .state_file
+            .as_ref()
+            .and_then(|path| match std::fs::read(path) {
+                Ok(contents) => serde_json::from_slice(&contents)
+                    .map_err(|e| warn!("Ignoring {}: {}", path.display(), e))
+                    .ok(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    warn!("Unable to read {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        LoginGuard { limits, state }
+    }
+
+    /// When logging in is allowed again, if it isn't now
+    pub(crate) fn not_before(&self) -> Option<DateTime<Utc>> {
+        self.state
+            .not_before
+            .filter(|not_before| *not_before > Utc::now())
+    }
+
+    pub(crate) fn succeeded(&mut self) {
+        if self.state != LoginState::default() {
+            self.state = LoginState::default();
+            self.save();
+        }
+    }
+
+    /// Wrong username or password
+    pub(crate) fn failed(&mut self) {
+        self.state.failures += 1;
+        let backoff = self
+            .limits
+            .initial_backoff_seconds
+            .saturating_mul(2u64.saturating_pow(self.state.failures - 1))
+            .min(self.limits.max_backoff_seconds);
+        self.hold_off(Duration::from_secs(backoff));
+    }
+
+    /// The modem locked the account
+    pub(crate) fn locked_out(&mut self) {
+        self.state.failures += 1;
+        self.hold_off(Duration::from_secs(self.limits.lockout_seconds));
+    }
+
+    fn hold_off(&mut self, duration: Duration) {
+        // a silly number of seconds in the config means never, not a panic
+        let not_before = chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|duration| Utc::now().checked_add_signed(duration))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        warn!(
+            "{} failed logins in a row, not logging in again until {}",
+            self.state.failures, not_before
+        );
+        self.state.not_before = Some(not_before);
+        self.save();
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.limits.state_file else {
+            return;
+        };
+        let written = serde_json::to_vec(&self.state)
+            .map_err(std::io::Error::from)
+            .and_then(|contents| {
+                if let Some(directory) = path.parent() {
+                    std::fs::create_dir_all(directory)?;
+                }
+                std::fs::write(path, contents)
+            });
+        if let Err(e) = written {
+            error!("Unable to write {}: {}", path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use crate::logging::info;
+use crate::modem::{ClientOptions, ClientStats, Modem};
+use crate::payloads::*;
+use crate::{Error, HnapVariant, SOAPClient};
+use async_trait::async_trait;
+use std::collections::HashMap;
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+/// Motorola MB8600/MB8611. Same HNAP login dance as the S33, but with `GetMoto*` actions.
+#[derive(Default, Debug)]
+pub struct MB86xxClient {
+    soap_client: SOAPClient,
+}
+
+impl MB86xxClient {
+    pub fn new(endpoint: String, options: &ClientOptions) -> Result<MB86xxClient, Error> {
+        Ok(MB86xxClient {
+            soap_client: SOAPClient::new(endpoint, options)?.with_variant(HnapVariant::Motorola),
+        })
+    }
+
+    #[cfg_attr(feature = "tracing", instrument(skip(self, password)))]
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        self.soap_client.login(username, password).await
+    }
+
+    #[cfg_attr(feature = "tracing", instrument(skip(self)))]
+    pub async fn metrics(&mut self) -> Result<GetMultipleHNAPsMotoMetricsResponse, Error> {
+        let request_hashmap: HashMap<&str, &str> = HashMap::from([
+            ("GetMotoStatusConnectionInfo", ""),
+            ("GetMotoStatusDownstreamChannelInfo", ""),
+            ("GetMotoStatusUpstreamChannelInfo", ""),
+        ]);
+        let response: GetMultipleHNAPsMotoMetricsResponse = self
+            .soap_client
+            .send_soap_action_with_retry("GetMultipleHNAPs", &request_hashmap)
+            .await?;
+
+        info!("{:#?}", response);
+        Ok(response)
+    }
+
+    #[cfg_attr(feature = "tracing", instrument(skip(self)))]
+    pub async fn logs(&mut self) -> Result<GetMultipleHNAPsMotoLogsResponse, Error> {
+        let request_hashmap: HashMap<&str, &str> =
+            HashMap::from([("GetMotoStatusLog", ""), ("GetMotoStatusLogXXX", "")]);
+        let response: GetMultipleHNAPsMotoLogsResponse = self
+            .soap_client
+            .send_soap_action_with_retry("GetMultipleHNAPs", &request_hashmap)
+            .await?;
+
+        info!("{:#?}", response);
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Modem for MB86xxClient {
+    async fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        MB86xxClient::login(self, username, password).await
+    }
+
+    async fn metrics(&mut self) -> Result<Metrics, Error> {
+        Ok(MB86xxClient::metrics(self).await?.into())
+    }
+
+    async fn logs(&mut self) -> Result<Vec<LogEntry>, Error> {
+        let parsed = MB86xxClient::logs(self)
+            .await?
+            .get_moto_status_log_response
+            .moto_status_log_list;
+        Ok(self.soap_client.quarantine_log_lines(parsed))
+    }
+
+    async fn reboot(&mut self) -> Result<(), Error> {
+        self.soap_client.reboot().await
+    }
+
+    fn stats(&self) -> ClientStats {
+        self.soap_client.stats()
+    }
+}
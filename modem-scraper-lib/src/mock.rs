@@ -0,0 +1,181 @@
+//! A fake HNAP modem, so [crate::SOAPClient] can be tested without one. It does the login
+//! challenge, checks `HNAP_AUTH` and the session cookie on everything else, and answers from canned
+//! replies. Only speaks JSON.
+
+use crate::{hex_hmac_md5, SOAP_DOMAIN};
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+const PUBLIC_KEY: &str = "MOCKPUBLICKEY";
+const CHALLENGE: &str = "MOCKCHALLENGE";
+
+#[derive(Debug, Default)]
+struct MockState {
+    username: String,
+    password: String,
+    /// keyed on action, e.g. `GetCustomerStatusLog`, without the `Response`
+    replies: HashMap<String, Value>,
+    /// `(uid, private key)` once logged in
+    session: Option<(String, String)>,
+    logins: u32,
+    /// like newer S33 firmware, which answers `ERROR` to sub-actions signed with milliseconds
+    timestamps_in_seconds: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MockModem {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockModem {
+    pub fn new(username: &str, password: &str) -> MockModem {
+        let state = MockState {
+            username: username.to_owned(),
+            password: password.to_owned(),
+            ..Default::default()
+        };
+        MockModem {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// What to answer `action` with, on its own or as part of `GetMultipleHNAPs`
+    pub fn reply(self, action: &str, reply: Value) -> MockModem {
+        self.state
+            .lock()
+            .unwrap()
+            .replies
+            .insert(action.to_owned(), reply);
+        self
+    }
+
+    /// Only takes `HNAP_AUTH` timestamps in seconds, like newer S33 firmware
+    pub fn timestamps_in_seconds(self) -> MockModem {
+        self.state.lock().unwrap().timestamps_in_seconds = true;
+        self
+    }
+
+    /// Forgets the session, like the modem does after a while
+    pub fn expire_session(&self) {
+        self.state.lock().unwrap().session = None;
+    }
+
+    /// How many times the client has logged in successfully
+    pub fn logins(&self) -> u32 {
+        self.state.lock().unwrap().logins
+    }
+
+    /// Listens on a random local port and returns the endpoint to point the client at
+    pub async fn serve(&self) -> String {
+        let app = Router::new()
+            .route("/HNAP1/", post(hnap))
+            .with_state(self.clone());
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service());
+        let address = server.local_addr();
+        tokio::spawn(server);
+        format!("http://{}/HNAP1/", address)
+    }
+}
+
+fn result(action: &str, result: &str) -> Value {
+    json!({ format!("{}Response", action): { format!("{}Result", action): result } })
+}
+
+async fn hnap(
+    State(modem): State<MockModem>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Json<Value> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_owned()
+    };
+    let soap_action = header("SOAPAction");
+    let action = soap_action.trim_start_matches(SOAP_DOMAIN);
+    let params = &body[action];
+    let mut state = modem.state.lock().unwrap();
+
+    if action == "Login" {
+        return Json(login(&mut state, params));
+    }
+
+    // the cookie has to carry the uid, and HNAP_AUTH has to be signed with the private key
+    let auth = header("HNAP_AUTH");
+    let (hash, timestamp) = auth.split_once(' ').unwrap_or_default();
+    let authorized = state.session.as_ref().is_some_and(|(uid, private_key)| {
+        let expected = hex_hmac_md5(
+            private_key.as_bytes(),
+            format!("{}{}{}", timestamp, SOAP_DOMAIN, action).as_bytes(),
+        );
+        hash == expected && header("Cookie").contains(&format!("uid={};", uid))
+    });
+    if !authorized {
+        return Json(result(action, "UN-AUTH"));
+    }
+
+    if action != "GetMultipleHNAPs" {
+        return Json(
+            state
+                .replies
+                .get(action)
+                .map(|reply| json!({ format!("{}Response", action): reply }))
+                .unwrap_or_else(|| result(action, "ERROR")),
+        );
+    }
+    let mut replies = Map::new();
+    let mut multiple_result = "OK";
+    let timestamp_ok = !state.timestamps_in_seconds
+        || timestamp
+            .parse::<u64>()
+            .is_ok_and(|timestamp| timestamp < 2_000_000_000);
+    for sub_action in params.as_object().into_iter().flat_map(Map::keys) {
+        match state.replies.get(sub_action) {
+            Some(reply) if timestamp_ok => {
+                replies.insert(format!("{}Response", sub_action), reply.clone());
+            }
+            _ => multiple_result = "ERROR",
+        }
+    }
+    replies.insert("GetMultipleHNAPsResult".to_owned(), multiple_result.into());
+    Json(json!({ "GetMultipleHNAPsResponse": replies }))
+}
+
+/// `Action: request` hands out the challenge, `Action: login` checks the answer to it
+fn login(state: &mut MockState, params: &Value) -> Value {
+    let private_key = hex_hmac_md5(
+        (PUBLIC_KEY.to_owned() + &state.password).as_bytes(),
+        CHALLENGE.as_bytes(),
+    );
+    match params["Action"].as_str() {
+        Some("request") => json!({
+            "LoginResponse": {
+                "Challenge": CHALLENGE,
+                "Cookie": format!("uid{}", state.logins + 1),
+                "PublicKey": PUBLIC_KEY,
+                "LoginResult": "OK",
+            }
+        }),
+        Some("login") => {
+            let login_password = hex_hmac_md5(private_key.as_bytes(), CHALLENGE.as_bytes());
+            if params["Username"] != state.username.as_str()
+                || params["LoginPassword"] != login_password.as_str()
+            {
+                return result("Login", "FAILED");
+            }
+            state.logins += 1;
+            state.session = Some((format!("uid{}", state.logins), private_key));
+            result("Login", "OK")
+        }
+        _ => result("Login", "ERROR"),
+    }
+}
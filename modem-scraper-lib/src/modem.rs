@@ -0,0 +1,336 @@
+use crate::logging::{debug, error, info};
+use crate::mb86xx::MB86xxClient;
+use crate::netgear::NetgearClient;
+use crate::payloads::{LogEntry, Metrics, RequestStats};
+use crate::sb8200::SB8200Client;
+use crate::snapshot::ModemSnapshot;
+#[cfg(feature = "snmp")]
+use crate::snmp::SnmpClient;
+use crate::{
+    hnap_auth, Error, HnapTimestamp, HnapTransport, LoginLimits, RetryPolicy, SOAPClient,
+    SOAP_DOMAIN, UNDEFINED_PRIVATE_KEY,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{StatusCode, Url};
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, ServerName};
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Display};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+/// Everything the scraper needs from a modem. Each supported model implements this, so adding a
+/// model shouldn't require changes to the binary.
+#[async_trait]
+pub trait Modem: Debug + Send {
+    async fn login(&mut self, username: &str, password: &str) -> Result<(), Error>;
+    async fn metrics(&mut self) -> Result<Metrics, Error>;
+    async fn logs(&mut self) -> Result<Vec<LogEntry>, Error>;
+
+    /// Extra tags describing the modem itself, fetched once after login
+    async fn device_info(&mut self) -> Result<HashMap<String, String>, Error> {
+        Ok(HashMap::new())
+    }
+
+    /// Not every model can be rebooted remotely
+    async fn reboot(&mut self) -> Result<(), Error> {
+        Err(Error::Unsupported("Rebooting"))
+    }
+
+    /// Device info, metrics and logs together, for library users who want the lot without caring
+    /// how the model splits it up. Needs a [Modem::login] first, like the rest.
+    async fn scrape(&mut self) -> Result<ModemSnapshot, Error> {
+        let device_info = self.device_info().await?.into();
+        let metrics = self.metrics().await?;
+        let logs = self.logs().await?;
+        Ok(ModemSnapshot {
+            time: metrics.time.unwrap_or_else(Utc::now),
+            device_info,
+            metrics,
+            logs,
+        })
+    }
+
+    /// HNAP models only, the others don't keep track
+    fn stats(&self) -> ClientStats {
+        ClientStats::default()
+    }
+}
+
+/// What a client has been up to since it was created, for the scraper's own metrics
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientStats {
+    /// successful logins, including every time the session expired
+    pub logins: u64,
+    /// by action
+    pub requests: BTreeMap<String, RequestStats>,
+}
+
+/// Supported modem models
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    S33,
+    SB8200,
+    MB86xx,
+    Netgear,
+    /// not really a model, any modem that speaks DOCS-IF-MIB over SNMP
+    #[cfg(feature = "snmp")]
+    Snmp,
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "s33" => Ok(Model::S33),
+            "sb8200" => Ok(Model::SB8200),
+            "mb8600" | "mb8611" | "mb86xx" => Ok(Model::MB86xx),
+            "cm1000" | "cm2000" | "netgear" => Ok(Model::Netgear),
+            #[cfg(feature = "snmp")]
+            "snmp" => Ok(Model::Snmp),
+            _ => Err(format!("Unsupported modem model: {}", s)),
+        }
+    }
+}
+
+impl Display for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Model::S33 => write!(f, "s33"),
+            Model::SB8200 => write!(f, "sb8200"),
+            Model::MB86xx => write!(f, "mb86xx"),
+            Model::Netgear => write!(f, "netgear"),
+            #[cfg(feature = "snmp")]
+            Model::Snmp => write!(f, "snmp"),
+        }
+    }
+}
+
+/// Same names as [Model::from_str], so `model` in the config can be typed
+impl<'de> Deserialize<'de> for Model {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Client settings shared by every model. Models ignore whatever doesn't apply to them.
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    pub accept_invalid_certs: bool,
+    /// extra CAs to trust, e.g. whatever signed the modem's certificate
+    pub root_certificates: Vec<reqwest::Certificate>,
+    /// hex SHA-256 of the modem's certificate. When set, that certificate is the only one accepted,
+    /// self-signed or not, and the endpoint has to be HTTPS.
+    pub pinned_certificate: Option<String>,
+    /// for modems that are only reachable through a jump host; http(s):// and socks5(h):// work
+    pub proxy: Option<reqwest::Proxy>,
+    /// how long to wait for the TCP/TLS handshake
+    pub connect_timeout: Duration,
+    /// how long a whole request may take, so a hung modem can't stall the scrape loop
+    pub timeout: Duration,
+    /// HNAP models only
+    pub hnap_transport: HnapTransport,
+    /// HNAP models only
+    pub retry_policy: RetryPolicy,
+    /// HNAP models only
+    pub login_limits: LoginLimits,
+    /// HNAP models only
+    pub hnap_timestamp: HnapTimestamp,
+    /// HNAP models only. Every reply body is written here as-is before it's deserialized, so
+    /// payloads from firmware we can't parse can be shared.
+    pub dump_raw: Option<PathBuf>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            accept_invalid_certs: false,
+            root_certificates: Vec::new(),
+            pinned_certificate: None,
+            proxy: None,
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(30),
+            hnap_transport: HnapTransport::default(),
+            retry_policy: RetryPolicy::default(),
+            login_limits: LoginLimits::default(),
+            hnap_timestamp: HnapTimestamp::default(),
+            dump_raw: None,
+        }
+    }
+}
+
+impl ClientOptions {
+    /// Every HTTP client talking to the modem starts from this
+    pub(crate) fn client_builder(&self) -> reqwest::ClientBuilder {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        if let Some(pin) = &self.pinned_certificate {
+            // nothing else gets a say, not even the CAs
+            let tls = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_custom_certificate_verifier(Arc::new(PinVerifier {
+                    pin: pin.replace(':', "").to_lowercase(),
+                }))
+                .with_no_client_auth();
+            return builder.use_preconfigured_tls(tls);
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        builder.danger_accept_invalid_certs(self.accept_invalid_certs)
+    }
+
+    /// A pinned certificate needs HTTPS, or there's nothing to check it against before the
+    /// credentials go out
+    pub(crate) fn check_endpoint(&self, endpoint: &str) -> Result<(), Error> {
+        match &self.pinned_certificate {
+            Some(_) if !endpoint.starts_with("https://") => {
+                error!(
+                    "{} isn't HTTPS, so it can't have the pinned certificate",
+                    endpoint
+                );
+                Err(Error::PinMismatch)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Accepts the pinned certificate and nothing else, during the handshake, so no request goes out
+/// to anything that isn't the modem
+struct PinVerifier {
+    /// hex SHA-256, lowercase and without colons
+    pin: String,
+}
+
+impl ServerCertVerifier for PinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = hex::encode(Sha256::digest(&end_entity.0));
+        if fingerprint == self.pin {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            error!("Expected certificate {}, got {}", self.pin, fingerprint);
+            Err(rustls::Error::General(
+                "certificate doesn't match the pinned fingerprint".to_owned(),
+            ))
+        }
+    }
+}
+
+/// Build the client for `model`
+pub fn connect(
+    model: Model,
+    endpoint: String,
+    options: &ClientOptions,
+) -> Result<Box<dyn Modem>, Error> {
+    Ok(match model {
+        Model::S33 => Box::new(SOAPClient::new(endpoint, options)?),
+        Model::SB8200 => Box::new(SB8200Client::new(endpoint, options)?),
+        Model::MB86xx => Box::new(MB86xxClient::new(endpoint, options)?),
+        Model::Netgear => Box::new(NetgearClient::new(endpoint, options)?),
+        #[cfg(feature = "snmp")]
+        Model::Snmp => Box::new(SnmpClient::new(endpoint)),
+    })
+}
+
+/// `scheme://host[:port]` of `endpoint`. The HTML modems only care about this, so users can point
+/// `device_address` at the HNAP URL regardless of model.
+pub(crate) fn origin(endpoint: &str) -> String {
+    match Url::parse(endpoint) {
+        Ok(url) => url.origin().ascii_serialization(),
+        Err(_) => endpoint.trim_end_matches('/').to_owned(),
+    }
+}
+
+/// Asks the HNAP endpoint for its model name. `GetDeviceSettings` doesn't need a login.
+async fn probe_hnap(
+    client: &reqwest::Client,
+    endpoint: &str,
+    timestamp: HnapTimestamp,
+) -> Option<String> {
+    let action = "GetDeviceSettings";
+    let body = HashMap::from([(action, HashMap::<&str, &str>::new())]);
+    let res = client
+        .post(endpoint)
+        .header("SOAPAction", SOAP_DOMAIN.to_owned() + action)
+        .header(
+            "HNAP_AUTH",
+            hnap_auth(UNDEFINED_PRIVATE_KEY, action, timestamp),
+        )
+        .json(&body)
+        .send()
+        .await
+        .ok()?;
+    if res.status() != StatusCode::OK {
+        return None;
+    }
+    let json: serde_json::Value = res.json().await.ok()?;
+    debug!("GetDeviceSettings reply: {:?}", json);
+    json.get("GetDeviceSettingsResponse")?
+        .get("ModelName")?
+        .as_str()
+        .map(str::to_owned)
+}
+
+/// Whether `path` exists on the modem. Auth-walled pages count, since they're still there.
+async fn probe_path(client: &reqwest::Client, base: &str, path: &str) -> bool {
+    match client.get(format!("{}{}", base, path)).send().await {
+        Ok(res) => matches!(res.status(), StatusCode::OK | StatusCode::UNAUTHORIZED),
+        Err(_) => false,
+    }
+}
+
+/// Figures out which [Model] is at `endpoint`: HNAP's `GetDeviceSettings` first, then the known
+/// HTML status pages. Nothing if it's none of them.
+#[cfg_attr(feature = "tracing", instrument)]
+pub async fn detect(endpoint: &str, options: &ClientOptions) -> Result<Option<Model>, Error> {
+    let client = options.client_builder().build()?;
+
+    if let Some(model_name) = probe_hnap(&client, endpoint, options.hnap_timestamp).await {
+        info!("HNAP reports model {}", model_name);
+        let model_name = model_name.to_lowercase();
+        if model_name.starts_with("s33") {
+            return Ok(Some(Model::S33));
+        }
+        if model_name.starts_with("mb86") {
+            return Ok(Some(Model::MB86xx));
+        }
+        if let Ok(model) = model_name.parse() {
+            return Ok(Some(model));
+        }
+    }
+
+    let base = origin(endpoint);
+    if probe_path(&client, &base, crate::sb8200::CONNECTION_STATUS_PATH).await {
+        return Ok(Some(Model::SB8200));
+    }
+    if probe_path(&client, &base, crate::netgear::DOCSIS_STATUS_PATH).await {
+        return Ok(Some(Model::Netgear));
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,172 @@
+use crate::logging::{debug, error, info};
+use crate::modem::{origin, ClientOptions, Modem};
+use crate::payloads::*;
+use crate::Error;
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::{self, StatusCode};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+pub(crate) const DOCSIS_STATUS_PATH: &str = "/DocsisStatus.htm";
+/// Channel, Lock Status, Modulation, Channel ID, Frequency, Power, SNR, Corrected, Uncorrectables
+const DOWNSTREAM_FIELDS: usize = 9;
+/// Channel, Lock Status, US Channel Type, Channel ID, Symbol Rate, Frequency, Power
+const UPSTREAM_FIELDS: usize = 7;
+
+/// Netgear CM1000/CM2000. These use basic auth and render the channel tables from a `|` separated
+/// string embedded in the page's javascript.
+#[derive(Default, Debug)]
+pub struct NetgearClient {
+    client: reqwest::Client,
+    endpoint: String,
+    username: String,
+    password: String,
+}
+
+/// Pulls `tagValueList` out of `function <function_name>()`. The first value is the number of rows.
+fn tag_value_list(html: &str, function_name: &str) -> Option<Vec<String>> {
+    let re = Regex::new(&format!(
+        r"function {}\(\)[\s\S]*?var tagValueList = '(?P<values>[^']*)'",
+        function_name
+    ))
+    .ok()?;
+    let values = re.captures(html)?.name("values")?.as_str();
+    Some(values.split('|').map(|v| v.trim().to_owned()).collect())
+}
+
+/// Splits a tag value list into `fields`-sized rows, honouring the row count at the front
+fn rows(values: &[String], fields: usize) -> Vec<&[String]> {
+    let count: usize = values
+        .first()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or_default();
+    values[1..]
+        .chunks(fields)
+        .take(count)
+        .filter(|row| row.len() == fields)
+        .collect()
+}
+
+fn parse_downstream_row(row: &[String]) -> Option<Channel> {
+    Some(Channel::Downstream(DownstreamChannel {
+        channel_id: parse_leading_number(&row[3])?,
+        lock_status: row[1] == "Locked",
+        modulation: row[2].as_str().into(),
+        frequency: parse_leading_number(&row[4])?,
+        power: parse_leading_number(&row[5])?,
+        snr: parse_leading_number(&row[6])?,
+        corrected: parse_counter(&row[7])?,
+        uncorrectables: parse_counter(&row[8])?,
+    }))
+}
+
+fn parse_upstream_row(row: &[String]) -> Option<Channel> {
+    // only the symbol rate is reported; channels are 1.25x as wide (5120 Ksym/sec == 6.4 MHz)
+    let symbol_rate: u32 = parse_leading_number(&row[4])?;
+    Some(Channel::Upstream(UpstreamChannel {
+        channel_id: parse_leading_number(&row[3])?,
+        lock_status: row[1] == "Locked",
+        modulation: row[2].as_str().into(),
+        frequency: parse_leading_number(&row[5])?,
+        width: symbol_rate.saturating_mul(1250),
+        power: parse_leading_number(&row[6])?,
+    }))
+}
+
+/// Parses the downstream and upstream bonded channel tables on `/DocsisStatus.htm`
+pub fn parse_docsis_status(html: &str) -> Vec<Channel> {
+    let mut channels: Vec<Channel> = Vec::new();
+
+    if let Some(values) = tag_value_list(html, "InitDsTableTagValue") {
+        for row in rows(&values, DOWNSTREAM_FIELDS) {
+            match parse_downstream_row(row) {
+                Some(c) => channels.push(c),
+                None => debug!("Skipping downstream row {:?}", row),
+            }
+        }
+    }
+    if let Some(values) = tag_value_list(html, "InitUsTableTagValue") {
+        for row in rows(&values, UPSTREAM_FIELDS) {
+            match parse_upstream_row(row) {
+                Some(c) => channels.push(c),
+                None => debug!("Skipping upstream row {:?}", row),
+            }
+        }
+    }
+
+    channels
+}
+
+impl NetgearClient {
+    pub fn new(endpoint: String, options: &ClientOptions) -> Result<NetgearClient, Error> {
+        options.check_endpoint(&endpoint)?;
+        Ok(NetgearClient {
+            client: options.client_builder().cookie_store(true).build()?,
+            endpoint: origin(&endpoint),
+            ..Default::default()
+        })
+    }
+
+    async fn get_page(&self, path: &str) -> Result<String, Error> {
+        let url = format!("{}{}", self.endpoint, path);
+        let mut res = self
+            .client
+            .get(&url)
+            .basic_auth(&self.username, Some(&self.password))
+            .send()
+            .await?;
+        // the first request after boot 401s to hand out an XSRF_TOKEN cookie, so try again once
+        if res.status() == StatusCode::UNAUTHORIZED {
+            res = self
+                .client
+                .get(&url)
+                .basic_auth(&self.username, Some(&self.password))
+                .send()
+                .await?;
+        }
+        match res.status() {
+            StatusCode::OK => Ok(res.text().await?),
+            status => {
+                error!("{:?}", res);
+                Err(Error::Status(status))
+            }
+        }
+    }
+
+    /// Basic auth is sent with every request, so this just stashes the credentials
+    #[cfg_attr(feature = "tracing", instrument(skip(password)))]
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        self.username = username.to_owned();
+        self.password = password.to_owned();
+        Ok(())
+    }
+
+    // skip self, it has the password in it
+    #[cfg_attr(feature = "tracing", instrument(skip(self)))]
+    pub async fn channels(&mut self) -> Result<Vec<Channel>, Error> {
+        let html = self.get_page(DOCSIS_STATUS_PATH).await?;
+        let channels = parse_docsis_status(&html);
+        info!("{:#?}", channels);
+        Ok(channels)
+    }
+}
+
+#[async_trait]
+impl Modem for NetgearClient {
+    async fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        NetgearClient::login(self, username, password).await
+    }
+
+    async fn metrics(&mut self) -> Result<Metrics, Error> {
+        Ok(Metrics {
+            channels: self.channels().await?,
+            ..Default::default()
+        })
+    }
+
+    /// The event log isn't exposed anywhere we can parse yet
+    async fn logs(&mut self) -> Result<Vec<LogEntry>, Error> {
+        Ok(Vec::new())
+    }
+}
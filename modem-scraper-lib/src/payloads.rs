@@ -1,2 +1,874 @@
+use crate::events::{self, EventCode};
+use chrono::offset::Utc;
+use chrono::DateTime;
+use log::Level;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::time::Duration;
+use telegraf::*;
+
+pub trait HasResult {
+    fn get_result(&self) -> String;
+}
+
+// declared before the model modules so they can use it too
+macro_rules! impl_has_result {
+    ($($t:ty),+ $(,)?) => ($(
+        impl HasResult for $t {
+            fn get_result(&self) -> String {
+                return self.result.clone();
+            }
+        }
+    )+)
+}
+
+pub use self::mb86xx::*;
 pub use self::s33::*;
+pub mod mb86xx;
 pub mod s33;
+
+/// HTML modems suffix values with units (`795000000 Hz`, `-1.2 dBmV`), so only parse the number
+pub(crate) fn parse_leading_number<T: std::str::FromStr>(s: &str) -> Option<T> {
+    s.split_whitespace().next()?.parse().ok()
+}
+
+/// Codeword counters are only ever digits, so one too big even for a u64 is pinned at the max
+/// rather than failing the whole scrape
+pub(crate) fn parse_counter(s: &str) -> Option<u64> {
+    let digits = s.split_whitespace().next()?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(digits.parse().unwrap_or(u64::MAX))
+}
+
+/// Modems in partial service list the channels they couldn't bond with `----` in place of some or
+/// all of the readings
+pub(crate) fn is_placeholder_row(row: &str) -> bool {
+    row.split('^').any(|field| {
+        let field = field.trim();
+        field.len() > 1 && field.bytes().all(|b| b == b'-')
+    })
+}
+
+/// Firmware revisions disagree on how to write these (`aa:bb:..`, `AA-BB-..`, `aabb.ccdd.eeff`), so
+/// this accepts any of them and always displays as `AA:BB:CC:DD:EE:FF`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddress(pub [u8; 6]);
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid MAC address {0:?}")]
+pub struct InvalidMacAddress(String);
+
+impl std::str::FromStr for MacAddress {
+    type Err = InvalidMacAddress;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex: String = s
+            .trim()
+            .chars()
+            .filter(|c| !matches!(c, ':' | '-' | '.'))
+            .collect();
+        let mut octets = [0; 6];
+        if hex.len() != 12 || hex::decode_to_slice(&hex, &mut octets).is_err() {
+            return Err(InvalidMacAddress(s.to_owned()));
+        }
+        Ok(MacAddress(octets))
+    }
+}
+
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let octets: Vec<String> = self.0.iter().map(|o| format!("{:02X}", o)).collect();
+        write!(f, "{}", octets.join(":"))
+    }
+}
+
+/// As the modem reports it, less any padding. Never empty.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SerialNumber(String);
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid serial number {0:?}")]
+pub struct InvalidSerialNumber(String);
+
+impl SerialNumber {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::str::FromStr for SerialNumber {
+    type Err = InvalidSerialNumber;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        if trimmed.is_empty()
+            || !trimmed
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        {
+            return Err(InvalidSerialNumber(s.to_owned()));
+        }
+        Ok(SerialNumber(trimmed.to_owned()))
+    }
+}
+
+impl Display for SerialNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Maps the syslog-style priority the modems report to a log level. Some firmware sends the whole
+/// syslog PRI (facility × 8 + severity), so only the severity is looked at.
+pub fn priority_to_level(priority: u8) -> Level {
+    match priority & 7 {
+        3 => Level::Error,
+        4 => Level::Warn,
+        5 => Level::Info,
+        6 => Level::Debug,
+        _ => Level::Error,
+    }
+}
+
+/// Syslog facility names, by number
+const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+/// Overrides for [priority_to_level], for firmware that numbers its levels differently, e.g.
+/// `{6: info, 7: debug}`. A full syslog PRI that isn't listed falls back to its severity.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct LogLevels(pub BTreeMap<u8, Level>);
+
+impl LogLevels {
+    pub fn level(&self, priority: u8) -> Level {
+        self.0
+            .get(&priority)
+            .or_else(|| self.0.get(&(priority & 7)))
+            .copied()
+            .unwrap_or_else(|| priority_to_level(priority))
+    }
+
+    /// Re-levels every entry that came with a priority
+    pub fn apply(&self, logs: &mut [LogEntry]) {
+        if self.0.is_empty() {
+            return;
+        }
+        for log_entry in logs {
+            if let Some(priority) = log_entry.priority {
+                log_entry.level = self.level(priority);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub level: Level,
+    pub message: String,
+    /// as the modem reported it, for the models that report one
+    #[serde(default)]
+    pub priority: Option<u8>,
+}
+
+/// The digits in `<82000200>`, or after `Event ID`, as some firmware puts the DOCSIS event ID in
+/// the message
+fn parse_event_id(message: &str) -> Option<u32> {
+    let bracketed = message.split('<').skip(1).find_map(|rest| {
+        let (inside, _) = rest.split_once('>')?;
+        if !inside.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        inside.parse().ok()
+    });
+    bracketed.or_else(|| {
+        let (_, rest) = message.split_once("Event ID")?;
+        let rest = rest.trim_start_matches([':', ' ']);
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        rest[..end].parse().ok()
+    })
+}
+
+/// `Event Type Code: 16; Chan ID: 32; ...` out of a `CM-STATUS message sent` line
+fn cm_status(message: &str) -> Vec<(&'static str, String)> {
+    if !message.contains("CM-STATUS") {
+        return Vec::new();
+    }
+    [
+        ("Event Type Code:", "cm_status_event"),
+        ("Chan ID:", "channel_id"),
+    ]
+    .into_iter()
+    .filter_map(|(prefix, field)| {
+        let (_, rest) = message.split_once(prefix)?;
+        let value = rest.split(';').next()?.trim();
+        (!value.is_empty() && value != "N/A").then(|| (field, value.to_owned()))
+    })
+    .collect()
+}
+
+/// DOCSIS events worth counting, going by the log message. Only the common ones are recognized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum LogEvent {
+    /// no UCDs received
+    T1Timeout,
+    /// no broadcast maintenance opportunities
+    T2Timeout,
+    /// no response to a ranging request
+    T3Timeout,
+    /// no unicast maintenance opportunities
+    T4Timeout,
+    /// lost downstream timing sync
+    SyncLoss,
+    /// stopped seeing MAC domain descriptors
+    MddTimeout,
+    /// anything else
+    Other,
+}
+
+impl LogEvent {
+    pub const ALL: [LogEvent; 7] = [
+        LogEvent::T1Timeout,
+        LogEvent::T2Timeout,
+        LogEvent::T3Timeout,
+        LogEvent::T4Timeout,
+        LogEvent::SyncLoss,
+        LogEvent::MddTimeout,
+        LogEvent::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogEvent::T1Timeout => "t1_timeout",
+            LogEvent::T2Timeout => "t2_timeout",
+            LogEvent::T3Timeout => "t3_timeout",
+            LogEvent::T4Timeout => "t4_timeout",
+            LogEvent::SyncLoss => "sync_loss",
+            LogEvent::MddTimeout => "mdd_timeout",
+            LogEvent::Other => "other",
+        }
+    }
+
+    /// Firmwares word these slightly differently (`T3 time-out`, `T4 time out`, `No UCDs Received
+    /// - Timeout`), so this only looks for the distinctive bits
+    pub fn classify(message: &str) -> LogEvent {
+        let message = message.to_lowercase().replace("time-out", "time out");
+        if message.contains("t1 time out") || message.contains("no ucds received") {
+            LogEvent::T1Timeout
+        } else if message.contains("t2 time out") {
+            LogEvent::T2Timeout
+        } else if message.contains("t3 time out") {
+            LogEvent::T3Timeout
+        } else if message.contains("t4 time out") {
+            LogEvent::T4Timeout
+        } else if message.contains("sync timing synchronization failure") {
+            LogEvent::SyncLoss
+        } else if message.contains("lost mdd timeout") {
+            LogEvent::MddTimeout
+        } else {
+            LogEvent::Other
+        }
+    }
+}
+
+/// What part of keeping the connection up a log entry is about, roughly. For splitting the log up
+/// rather than counting, so unlike [LogEvent] everything lands somewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum LogCategory {
+    /// T1 to T4 timeouts, ranging retries and aborts
+    Ranging,
+    /// downstream lock, sync, MDDs and OFDM profiles
+    Sync,
+    /// transmit power, and battery backup
+    Power,
+    /// DHCP, time of day, the config file and registration itself
+    Registration,
+    /// firmware downloads and upgrades
+    Firmware,
+    Other,
+}
+
+impl LogCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogCategory::Ranging => "ranging",
+            LogCategory::Sync => "sync",
+            LogCategory::Power => "power",
+            LogCategory::Registration => "registration",
+            LogCategory::Firmware => "firmware",
+            LogCategory::Other => "other",
+        }
+    }
+
+    /// By keywords in the message, or a CM-STATUS line's event type
+    pub fn classify(message: &str) -> LogCategory {
+        let cm_status_event = cm_status(message)
+            .into_iter()
+            .find(|(field, _)| *field == "cm_status_event")
+            .and_then(|(_, value)| value.parse::<u8>().ok());
+        if let Some(code) = cm_status_event {
+            return match code {
+                6..=8 => LogCategory::Ranging,
+                9 | 10 => LogCategory::Power,
+                11 => LogCategory::Other,
+                _ => LogCategory::Sync,
+            };
+        }
+        let message = message.to_lowercase();
+        let mentions = |keywords: &[&str]| keywords.iter().any(|k| message.contains(k));
+        if mentions(&["sw download", "sw upgrade", "firmware", "software download"]) {
+            LogCategory::Firmware
+        } else if mentions(&[
+            "ranging",
+            "maintenance",
+            "no ucds",
+            "t1 time",
+            "t2 time",
+            "t3 time",
+            "t4 time",
+        ]) {
+            LogCategory::Ranging
+        } else if mentions(&["power", "battery", "dynamic range"]) {
+            LogCategory::Power
+        } else if mentions(&[
+            "dhcp",
+            "tod ",
+            "time of day",
+            "registration",
+            "reg-",
+            "reg req",
+            "reg rsp",
+            "tftp",
+            "config file",
+            "provisioning",
+            "bpi",
+            "auth",
+        ]) {
+            LogCategory::Registration
+        } else if mentions(&["sync", "mdd", "qam", "fec framing", "profile", "plc"]) {
+            LogCategory::Sync
+        } else {
+            LogCategory::Other
+        }
+    }
+}
+
+impl LogEntry {
+    pub fn event(&self) -> LogEvent {
+        LogEvent::classify(&self.message)
+    }
+
+    pub fn category(&self) -> LogCategory {
+        LogCategory::classify(&self.message)
+    }
+
+    /// The catalog's entry for this line, by the event ID in the message or else its wording
+    pub fn event_code(&self) -> Option<&'static EventCode> {
+        match parse_event_id(&self.message) {
+            Some(event_id) => events::by_id(event_id),
+            None => events::by_message(&self.message),
+        }
+    }
+
+    /// The DOCSIS event ID, whether or not it's in the catalog
+    pub fn event_id(&self) -> Option<u32> {
+        parse_event_id(&self.message).or_else(|| Some(self.event_code()?.id))
+    }
+
+    /// Whatever structure there is in the message: the DOCSIS event ID and, if it's in the catalog,
+    /// its severity and description, a CM-STATUS event's type code, description and channel, the
+    /// `;CM-MAC=...;CMTS-MAC=...;` that most DOCSIS events end with, and the syslog facility if the
+    /// priority was a full PRI. Keys are lowercase with underscores.
+    pub fn fields(&self) -> BTreeMap<String, String> {
+        let mut fields = BTreeMap::new();
+        if let Some(event_id) = self.event_id() {
+            fields.insert("event_id".to_owned(), event_id.to_string());
+        }
+        if let Some(event_code) = self.event_code() {
+            fields.insert("event_severity".to_owned(), event_code.severity.to_owned());
+            fields.insert(
+                "event_description".to_owned(),
+                event_code.description.to_owned(),
+            );
+        }
+        for (field, value) in cm_status(&self.message) {
+            if field == "cm_status_event" {
+                if let Some(description) = value.parse().ok().and_then(events::cm_status_event) {
+                    fields.insert("cm_status_description".to_owned(), description.to_owned());
+                }
+            }
+            fields.insert(field.to_owned(), value);
+        }
+        for pair in self.message.split(';').skip(1) {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            if !key.is_empty() && key.bytes().all(|b| b.is_ascii_uppercase() || b == b'-') {
+                fields.insert(
+                    key.to_lowercase().replace('-', "_"),
+                    value.trim().to_owned(),
+                );
+            }
+        }
+        if let Some(priority) = self.priority.filter(|priority| *priority > 7) {
+            if let Some(facility) = FACILITIES.get(usize::from(priority >> 3)) {
+                fields.insert("facility".to_owned(), (*facility).to_owned());
+            }
+        }
+        fields
+    }
+}
+
+/// A modem's log, minus the lines that couldn't be parsed
+#[derive(Debug, Clone, Default)]
+pub struct ParsedLogs {
+    pub entries: Vec<LogEntry>,
+    /// as the modem sent them
+    pub unparsed: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Modulation {
+    QAM64,
+    QAM256,
+    OFDMPLC,
+    SCQAM, // unclear if modulation method, but maybe
+    ATDMA,
+    OFDMA,
+    /// whatever the modem called it, so new firmware values still make a useful tag
+    Other(String),
+}
+
+impl Display for Modulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Modulation::QAM64 => write!(f, "QAM-64"),
+            Modulation::QAM256 => write!(f, "QAM-256"),
+            Modulation::OFDMPLC => write!(f, "OFDM-PLC"),
+            Modulation::SCQAM => write!(f, "SC-QAM"),
+            Modulation::ATDMA => write!(f, "ATDMA"),
+            Modulation::OFDMA => write!(f, "OFDMA"),
+            Modulation::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<&str> for Modulation {
+    fn from(s: &str) -> Self {
+        match s {
+            "QAM64" => Modulation::QAM64,
+            "QAM256" => Modulation::QAM256,
+            "OFDM PLC" => Modulation::OFDMPLC,
+            "SC-QAM" => Modulation::SCQAM,
+            "ATDMA" => Modulation::ATDMA,
+            "OFDMA" => Modulation::OFDMA,
+            // an empty tag value isn't valid line protocol
+            s if s.trim().is_empty() => Modulation::Other("Unknown".to_owned()),
+            s => Modulation::Other(s.trim().to_owned()),
+        }
+    }
+}
+
+impl IntoFieldData for Modulation {
+    fn field_data(&self) -> FieldData {
+        FieldData::Str(self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Metric, Serialize, Deserialize)]
+#[measurement = "modem_downstream_channel"]
+pub struct DownstreamChannel {
+    #[telegraf(tag)]
+    pub channel_id: u8,
+    #[telegraf(tag)]
+    pub modulation: Modulation,
+    pub lock_status: bool,
+    pub frequency: u32,
+    /// dBmV
+    pub power: f64,
+    /// dB
+    pub snr: f64,
+    /// running totals since the modem booted, which outgrow a u32 on long uptimes
+    pub corrected: u64,
+    pub uncorrectables: u64,
+}
+
+#[derive(Debug, Clone, Metric, Serialize, Deserialize)]
+#[measurement = "modem_upstream_channel"]
+pub struct UpstreamChannel {
+    #[telegraf(tag)]
+    pub channel_id: u8,
+    #[telegraf(tag)]
+    pub modulation: Modulation,
+    pub lock_status: bool,
+    pub frequency: u32,
+    pub width: u32,
+    pub power: f64,
+}
+
+/// DOCSIS 3.1 upstream. These span a range of subcarriers rather than a single frequency.
+#[derive(Debug, Clone, Metric, Serialize, Deserialize)]
+#[measurement = "modem_ofdma_upstream_channel"]
+pub struct OfdmaUpstreamChannel {
+    #[telegraf(tag)]
+    pub channel_id: u8,
+    pub lock_status: bool,
+    /// number of subcarriers, 2048 or 4096
+    pub fft_size: u32,
+    /// start of the occupied range
+    pub frequency: u32,
+    pub width: u32,
+    pub power: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Channel {
+    Downstream(DownstreamChannel),
+    Upstream(UpstreamChannel),
+    OfdmaUpstream(OfdmaUpstreamChannel),
+}
+
+impl Channel {
+    pub fn to_point(&self) -> Point {
+        match self {
+            Channel::Downstream(c) => c.to_point(),
+            Channel::Upstream(c) => c.to_point(),
+            Channel::OfdmaUpstream(c) => c.to_point(),
+        }
+    }
+}
+
+/// A channel table, minus the rows that couldn't be parsed
+#[derive(Debug, Clone, Default)]
+pub struct ParsedChannels {
+    pub channels: Vec<Channel>,
+    pub skipped: u32,
+    /// rows that were only `----` placeholders, see [is_placeholder_row]
+    pub placeholders: u32,
+}
+
+/// Channel rows the modem listed as `----` placeholders, per direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaceholderChannels {
+    pub downstream: u32,
+    pub upstream: u32,
+}
+
+/// Where a startup sequence step is at, going by what the modem says about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StartupStatus {
+    #[default]
+    NotStarted,
+    InProgress,
+    Complete,
+    Error,
+}
+
+impl StartupStatus {
+    /// for exporting as a metric value, so anything but 2 can be alerted on
+    pub fn as_number(&self) -> u8 {
+        match self {
+            StartupStatus::NotStarted => 0,
+            StartupStatus::InProgress => 1,
+            StartupStatus::Complete => 2,
+            StartupStatus::Error => 3,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StartupStatus::NotStarted => "not_started",
+            StartupStatus::InProgress => "in_progress",
+            StartupStatus::Complete => "complete",
+            StartupStatus::Error => "error",
+        }
+    }
+}
+
+impl From<&str> for StartupStatus {
+    fn from(s: &str) -> Self {
+        let s = s.trim().to_lowercase();
+        match s.as_str() {
+            "" | "not started" | "notstarted" | "not_started" => StartupStatus::NotStarted,
+            // security is `Disabled` when BPI+ is off, which is still a finished step
+            "ok" | "locked" | "operational" | "allowed" | "enabled" | "disabled" | "complete"
+            | "completed" | "done" | "success" => StartupStatus::Complete,
+            s if [
+                "progress",
+                "scanning",
+                "acquiring",
+                "ranging",
+                "registering",
+            ]
+            .iter()
+            .any(|word| s.contains(word)) =>
+            {
+                StartupStatus::InProgress
+            }
+            _ => StartupStatus::Error,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StartupStatus {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        Ok(s.as_str().into())
+    }
+}
+
+/// As [StartupStatus::as_str], which reads back as the same status
+impl Serialize for StartupStatus {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Model-agnostic state of the modem itself, as opposed to its channels. Models fill in what they
+/// report and leave the rest empty.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModemStatus {
+    /// whether the CMTS lets the modem onto the network
+    pub network_access: Option<bool>,
+    pub internet_connected: Option<bool>,
+    /// startup sequence step (`boot`, `security`...) and where it's at
+    pub startup: Vec<(String, StartupStatus)>,
+}
+
+/// Codewords a downstream channel saw between two scrapes. The modem only reports running totals,
+/// so this is worked out by whoever keeps the previous scrape around.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CodewordDelta {
+    pub corrected: u64,
+    pub uncorrectables: u64,
+    /// time between the two scrapes
+    pub seconds: f64,
+}
+
+/// How one HNAP action has been going. A modem whose web stack is struggling gets slow, then starts
+/// answering `ERROR` or 500s, well before it stops answering altogether.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestStats {
+    /// how long the latest request took, up to the end of the reply
+    pub latency: Duration,
+    /// size of the latest reply's body
+    pub response_bytes: u64,
+    /// the latest reply's `<action>Result`, e.g. `OK` or `ERROR`, or its HTTP status if it wasn't
+    /// a 200
+    pub result: String,
+    /// how many replies came back with each result since startup
+    pub results: BTreeMap<String, u64>,
+}
+
+/// Model-agnostic result of a metrics scrape. Every [crate::Modem] implementation converts its
+/// own payloads into this so the binary doesn't need to know which modem it's talking to.
+/// Serializes so it can be kept on disk until it's sent.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Metrics {
+    pub channels: Vec<Channel>,
+    /// time since the modem last booted, for models that report it
+    pub uptime: Option<Duration>,
+    /// the modem's own clock when it answered, for models that report it
+    pub time: Option<DateTime<Utc>>,
+    pub status: ModemStatus,
+    /// channel rows the modem sent that couldn't be parsed, for models that can tell
+    pub skipped_channels: u32,
+    /// for models that list the channels they couldn't bond
+    pub placeholder_channels: PlaceholderChannels,
+}
+
+impl Metrics {
+    /// A `modem_startup` point per startup sequence step, tagged with the `stage`. `status` is
+    /// [StartupStatus::as_number] and `state` is [StartupStatus::as_str].
+    pub fn startup_points(&self) -> Vec<Point> {
+        self.status
+            .startup
+            .iter()
+            .map(|(stage, status)| {
+                Point::new(
+                    "modem_startup".to_owned(),
+                    vec![("stage".to_owned(), stage.clone())],
+                    vec![
+                        (
+                            "status".to_owned(),
+                            Box::new(status.as_number()) as Box<dyn IntoFieldData>,
+                        ),
+                        ("state".to_owned(), Box::new(status.as_str().to_owned())),
+                    ],
+                    None,
+                )
+            })
+            .collect()
+    }
+
+    /// Uptime, [ModemStatus] and the overall [Metrics::health] as a `modem_status` point, or
+    /// nothing if there's none of it
+    pub fn status_point(&self, codeword_deltas: &HashMap<u8, CodewordDelta>) -> Option<Point> {
+        let mut fields: Vec<(String, Box<dyn IntoFieldData>)> = Vec::new();
+        if self.skipped_channels > 0 {
+            fields.push((
+                "skipped_channels".to_owned(),
+                Box::new(self.skipped_channels),
+            ));
+        }
+        if let Some(health) = self.health(codeword_deltas) {
+            fields.push(("health".to_owned(), Box::new(health)));
+        }
+        if let Some(uptime) = self.uptime {
+            fields.push(("uptime_seconds".to_owned(), Box::new(uptime.as_secs())));
+        }
+        if let Some(network_access) = self.status.network_access {
+            fields.push(("network_access".to_owned(), Box::new(network_access)));
+        }
+        if let Some(internet_connected) = self.status.internet_connected {
+            fields.push((
+                "internet_connected".to_owned(),
+                Box::new(internet_connected),
+            ));
+        }
+        if fields.is_empty() {
+            return None;
+        }
+        Some(Point::new(
+            "modem_status".to_owned(),
+            Vec::new(),
+            fields,
+            None,
+        ))
+    }
+
+    /// Whether the modem is in partial service, per direction: it listed placeholders for channels
+    /// it couldn't bond, or channels that aren't locked. Directions without any channels are left
+    /// out.
+    pub fn partial_service(&self) -> Vec<(&'static str, bool)> {
+        let mut directions = Vec::new();
+        for (direction, placeholders) in [
+            ("downstream", self.placeholder_channels.downstream),
+            ("upstream", self.placeholder_channels.upstream),
+        ] {
+            let lock_statuses: Vec<bool> = self
+                .channels
+                .iter()
+                .filter_map(|channel| match (direction, channel) {
+                    ("downstream", Channel::Downstream(c)) => Some(c.lock_status),
+                    ("upstream", Channel::Upstream(c)) => Some(c.lock_status),
+                    ("upstream", Channel::OfdmaUpstream(c)) => Some(c.lock_status),
+                    _ => None,
+                })
+                .collect();
+            if lock_statuses.is_empty() && placeholders == 0 {
+                continue;
+            }
+            let unlocked = lock_statuses.iter().any(|locked| !locked);
+            directions.push((direction, placeholders > 0 || unlocked));
+        }
+        directions
+    }
+
+    /// A `modem_partial_service` point per direction from [Metrics::partial_service], tagged with
+    /// the `direction`, with how many `placeholder_channels` there were
+    pub fn partial_service_points(&self) -> Vec<Point> {
+        self.partial_service()
+            .into_iter()
+            .map(|(direction, partial_service)| {
+                let placeholders = match direction {
+                    "downstream" => self.placeholder_channels.downstream,
+                    _ => self.placeholder_channels.upstream,
+                };
+                Point::new(
+                    "modem_partial_service".to_owned(),
+                    vec![("direction".to_owned(), direction.to_owned())],
+                    vec![
+                        (
+                            "partial_service".to_owned(),
+                            Box::new(partial_service) as Box<dyn IntoFieldData>,
+                        ),
+                        ("placeholder_channels".to_owned(), Box::new(placeholders)),
+                    ],
+                    None,
+                )
+            })
+            .collect()
+    }
+}
+
+impl From<GetMultipleHNAPsMetricsResponse> for Metrics {
+    fn from(response: GetMultipleHNAPsMetricsResponse) -> Self {
+        let downstream = response
+            .get_customer_status_downstream_channel_info_response
+            .customer_conn_downstream_channel;
+        let upstream = response
+            .get_customer_status_upstream_channel_info_response
+            .customer_conn_upstream_channel;
+        let mut channels = downstream.channels;
+        channels.extend(upstream.channels);
+        let connection_info = response.get_customer_status_connection_info_response;
+        let startup = response.get_customer_status_startup_sequence_response;
+        Metrics {
+            channels,
+            skipped_channels: downstream.skipped + upstream.skipped,
+            placeholder_channels: PlaceholderChannels {
+                downstream: downstream.placeholders,
+                upstream: upstream.placeholders,
+            },
+            uptime: Some(connection_info.customer_conn_system_up_time),
+            time: Some(connection_info.customer_cur_system_time),
+            status: ModemStatus {
+                network_access: Some(connection_info.customer_conn_network_access == "Allowed"),
+                internet_connected: Some(
+                    response
+                        .get_arris_device_status_response
+                        .internet_connection
+                        == "Connected",
+                ),
+                startup: vec![
+                    (
+                        "downstream".to_owned(),
+                        startup.customer_conn_d_s_comment.as_str().into(),
+                    ),
+                    (
+                        "connectivity".to_owned(),
+                        startup.customer_conn_connectivity_status,
+                    ),
+                    ("boot".to_owned(), startup.customer_conn_boot_status),
+                    (
+                        "configuration_file".to_owned(),
+                        startup.customer_conn_configuration_file_status,
+                    ),
+                    ("security".to_owned(), startup.customer_conn_security_status),
+                ],
+            },
+        }
+    }
+}
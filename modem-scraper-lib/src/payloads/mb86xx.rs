@@ -0,0 +1,217 @@
+use super::*;
+use crate::logging::{debug, warn};
+use chrono::NaiveDateTime;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+/// Motorola reports frequencies in MHz (`483.0`), everything else wants Hz
+fn mhz_to_hz(mhz: &str) -> Option<u32> {
+    mhz.trim()
+        .parse::<f64>()
+        .ok()
+        .map(|f| (f * 1_000_000.0).round() as u32)
+}
+
+/// `10:23:45^Thu Jan 14 2021^3^message`, or nothing if the line isn't laid out like that
+fn parse_moto_log_line(line: &str, re: &Regex) -> Option<LogEntry> {
+    let captures = re.captures(line)?;
+    let capture_datetime = format!("{} {}", captures["date"].trim(), &captures["time"]);
+    let timestamp = NaiveDateTime::parse_from_str(&capture_datetime, "%a %b %d %Y %T")
+        .ok()?
+        .and_utc();
+    let priority = captures["level"].parse().ok()?;
+    Some(LogEntry {
+        timestamp,
+        level: priority_to_level(priority),
+        message: captures["message"].trim().to_string(),
+        priority: Some(priority),
+    })
+}
+
+/// Parses `10:23:45^Thu Jan 14 2021^3^message}-{...` into log entries. Lines that don't parse are
+/// set aside, same as the S33.
+fn moto_log_parser<'de, D>(deserializer: D) -> Result<ParsedLogs, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    let re =
+        Regex::new(r"^\s*(?P<time>[:\d]+)\^(?P<date>[^\^]+)\^(?P<level>\d+)\^(?P<message>.*)$")
+            .unwrap();
+
+    let mut parsed = ParsedLogs::default();
+    for line in s.split("}-{").filter(|l| !l.trim().is_empty()) {
+        match parse_moto_log_line(line, &re) {
+            Some(log_entry) => parsed.entries.push(log_entry),
+            None => parsed.unparsed.push(line.to_owned()),
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Channel^Lock Status^Modulation^Channel ID^Freq (MHz)^Pwr (dBmV)^SNR (dB)^Corrected^Uncorrected
+const MOTO_DOWNSTREAM_CHANNEL_PATTERN: &str = r"(?:\d+)\^(?P<lock_status>[\w ]+)\^(?P<modulation>[\w\d -]+)\^(?P<channel_id>\d+)\^(?P<frequency>[\d.]+)\^\s*(?P<power>-?[\d.]+)\^\s*(?P<snr>-?[\d.]+)\^(?P<corrected>\d+)\^(?P<uncorrectables>\d+)\^?";
+/// Channel^Lock Status^Channel Type^Channel ID^Symb Rate (Ksym/sec)^Freq (MHz)^Pwr (dBmV)
+const MOTO_UPSTREAM_CHANNEL_PATTERN: &str = r"(?:\d+)\^(?P<lock_status>[\w ]+)\^(?P<modulation>[\w\d -]+)\^(?P<channel_id>\d+)\^(?P<symbol_rate>\d+)\^(?P<frequency>[\d.]+)\^\s*(?P<power>-?[\d.]+)\^?";
+
+/// Reads one `^`-separated channel row, or nothing if it doesn't look like any kind of channel
+fn parse_moto_channel(
+    line: &str,
+    downstream_channel_regex: &Regex,
+    upstream_channel_regex: &Regex,
+) -> Option<Channel> {
+    // the downstream pattern has more fields, so try it first
+    if let Some(captures) = downstream_channel_regex.captures(line) {
+        return Some(Channel::Downstream(DownstreamChannel {
+            channel_id: captures["channel_id"].parse().ok()?,
+            lock_status: &captures["lock_status"] == "Locked",
+            modulation: captures["modulation"].into(),
+            frequency: mhz_to_hz(&captures["frequency"])?,
+            power: captures["power"].parse().ok()?,
+            snr: captures["snr"].parse().ok()?,
+            corrected: parse_counter(&captures["corrected"])?,
+            uncorrectables: parse_counter(&captures["uncorrectables"])?,
+        }));
+    }
+
+    let captures = upstream_channel_regex.captures(line)?;
+    // no width here, only symbol rate. DOCSIS channels are 1.25x as wide as the symbol rate
+    // (5120 Ksym/s == 6.4 MHz)
+    let symbol_rate: u32 = captures["symbol_rate"].parse().ok()?;
+    Some(Channel::Upstream(UpstreamChannel {
+        channel_id: captures["channel_id"].parse().ok()?,
+        lock_status: &captures["lock_status"] == "Locked",
+        modulation: captures["modulation"].into(),
+        frequency: mhz_to_hz(&captures["frequency"])?,
+        width: symbol_rate.saturating_mul(1250),
+        power: captures["power"].parse().ok()?,
+    }))
+}
+
+/// Rows that don't parse are logged and counted, same as the S33
+fn moto_channel_parser<'de, D>(deserializer: D) -> Result<ParsedChannels, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    let downstream_channel_regex = Regex::new(MOTO_DOWNSTREAM_CHANNEL_PATTERN).unwrap();
+    let upstream_channel_regex = Regex::new(MOTO_UPSTREAM_CHANNEL_PATTERN).unwrap();
+
+    let mut parsed = ParsedChannels::default();
+    for line in s.split("|+|").filter(|l| !l.trim().is_empty()) {
+        if is_placeholder_row(line) {
+            debug!("Channel row {:?} is a placeholder", line);
+            parsed.placeholders += 1;
+            continue;
+        }
+        match parse_moto_channel(line, &downstream_channel_regex, &upstream_channel_regex) {
+            Some(channel) => parsed.channels.push(channel),
+            None => {
+                warn!("Skipping channel row {:?}", line);
+                parsed.skipped += 1;
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct SetStatusSecuritySettingsResponse {
+    #[serde(rename = "SetStatusSecuritySettingsResult")]
+    result: String,
+}
+impl_has_result!(SetStatusSecuritySettingsResponse);
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct MotoStatusDownstreamChannelInfo {
+    #[serde(deserialize_with = "moto_channel_parser")]
+    pub moto_conn_downstream_channel: ParsedChannels,
+    #[serde(rename = "GetMotoStatusDownstreamChannelInfoResult")]
+    result: String,
+}
+impl_has_result!(MotoStatusDownstreamChannelInfo);
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct MotoStatusUpstreamChannelInfo {
+    #[serde(deserialize_with = "moto_channel_parser")]
+    pub moto_conn_upstream_channel: ParsedChannels,
+    #[serde(rename = "GetMotoStatusUpstreamChannelInfoResult")]
+    result: String,
+}
+impl_has_result!(MotoStatusUpstreamChannelInfo);
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct MotoStatusConnectionInfo {
+    pub moto_conn_system_up_time: String,
+    pub moto_conn_network_access: String,
+    #[serde(rename = "GetMotoStatusConnectionInfoResult")]
+    result: String,
+}
+impl_has_result!(MotoStatusConnectionInfo);
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct GetMultipleHNAPsMotoMetricsResponse {
+    pub get_moto_status_connection_info_response: MotoStatusConnectionInfo,
+    pub get_moto_status_downstream_channel_info_response: MotoStatusDownstreamChannelInfo,
+    pub get_moto_status_upstream_channel_info_response: MotoStatusUpstreamChannelInfo,
+    #[serde(rename = "GetMultipleHNAPsResult")]
+    result: String,
+}
+impl_has_result!(GetMultipleHNAPsMotoMetricsResponse);
+
+impl From<GetMultipleHNAPsMotoMetricsResponse> for Metrics {
+    fn from(response: GetMultipleHNAPsMotoMetricsResponse) -> Self {
+        let downstream = response
+            .get_moto_status_downstream_channel_info_response
+            .moto_conn_downstream_channel;
+        let upstream = response
+            .get_moto_status_upstream_channel_info_response
+            .moto_conn_upstream_channel;
+        let mut channels = downstream.channels;
+        channels.extend(upstream.channels);
+        Metrics {
+            channels,
+            skipped_channels: downstream.skipped + upstream.skipped,
+            placeholder_channels: PlaceholderChannels {
+                downstream: downstream.placeholders,
+                upstream: upstream.placeholders,
+            },
+            status: ModemStatus {
+                network_access: Some(
+                    response
+                        .get_moto_status_connection_info_response
+                        .moto_conn_network_access
+                        == "Allowed",
+                ),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct MotoStatusLogResponse {
+    #[serde(deserialize_with = "moto_log_parser")]
+    pub moto_status_log_list: ParsedLogs,
+    #[serde(rename = "GetMotoStatusLogResult")]
+    result: String,
+}
+impl_has_result!(MotoStatusLogResponse);
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct GetMultipleHNAPsMotoLogsResponse {
+    pub get_moto_status_log_response: MotoStatusLogResponse,
+    #[serde(rename = "GetMultipleHNAPsResult")]
+    result: String,
+}
+impl_has_result!(GetMultipleHNAPsMotoLogsResponse);
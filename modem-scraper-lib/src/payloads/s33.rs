@@ -1,279 +1,205 @@
+use super::*;
+use crate::logging::{debug, warn};
 use chrono::offset::Utc;
 use chrono::{DateTime, NaiveDateTime};
-use log::Level;
-use regex::{Captures, Regex};
+use regex::Regex;
 use serde::de::Error;
 use serde::{Deserialize, Deserializer};
-use std::fmt::Display;
+use std::collections::HashMap;
 use std::time::Duration;
-use telegraf::*;
-use tracing::debug;
 
-/// Parses `0 days 13h:14m:15s` to a Duration
+/// Parses `0 days 13h:14m:15s` to a Duration. Some firmware says `1 day`, or leaves the days out
+/// entirely.
 fn duration_deserializer<'de, D>(deserializer: D) -> Result<Duration, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s: String = Deserialize::deserialize(deserializer)?;
-    let re = Regex::new(r"(?P<days>\d+) days (?P<hours>\d+)h:(?P<minutes>\d+)m:(?P<seconds>\d+)s")
-        .unwrap();
-    let captures = re.captures(s.as_str()).unwrap();
+    let re = Regex::new(
+        r"^\s*(?:(?P<days>\d+) days? )?(?P<hours>\d+)h:(?P<minutes>\d+)m:(?P<seconds>\d+)s\s*$",
+    )
+    .unwrap();
+    let invalid = || Error::custom(format!("Unable to parse uptime {:?}", s));
+    let captures = re.captures(s.as_str()).ok_or_else(invalid)?;
 
     // using a u64 for all these is a little inefficient, but that makes using it in Duration::new()
     // a lot easier, so
-    let days = captures
-        .name("days")
-        .unwrap()
-        .as_str()
-        .parse::<u64>()
-        .expect("Unable to convert captured days to a u16");
-    let hours = captures
-        .name("hours")
-        .unwrap()
-        .as_str()
-        .parse::<u64>()
-        .unwrap();
-    let minutes = captures
-        .name("minutes")
-        .unwrap()
-        .as_str()
-        .parse::<u64>()
-        .unwrap();
-    let seconds = captures
-        .name("seconds")
-        .unwrap()
-        .as_str()
-        .parse::<u64>()
-        .unwrap();
-
-    let duration = Duration::new(
-        days * 24 * 60 * 60 + hours * 60 * 60 + minutes * 60 + seconds,
-        0,
+    let field = |name: &str| -> Result<u64, D::Error> {
+        captures
+            .name(name)
+            .map_or(Ok(0), |m| m.as_str().parse::<u64>())
+            .map_err(|_| invalid())
+    };
+    let (days, hours, minutes, seconds) = (
+        field("days")?,
+        field("hours")?,
+        field("minutes")?,
+        field("seconds")?,
     );
+    let seconds = days
+        .checked_mul(24 * 60 * 60)
+        .and_then(|total| total.checked_add(hours.checked_mul(60 * 60)?))
+        .and_then(|total| total.checked_add(minutes.checked_mul(60)?))
+        .and_then(|total| total.checked_add(seconds))
+        .ok_or_else(invalid)?;
+    let duration = Duration::from_secs(seconds);
 
     debug!("Deserialized {} to {:?}", s, duration);
 
     Ok(duration)
 }
 
-/// Parses the locale-based timestamp to a UTC timestamp. Assumes modem is already reporting UTC.
+/// For the MAC address and serial number, which come back with every metrics scrape, so one we
+/// can't read isn't worth failing over. It's logged, since it likely means the firmware changed
+/// how it writes them. Empty is just missing.
+fn lenient_deserializer<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: Display,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    if s.trim().is_empty() {
+        return Ok(None);
+    }
+    match s.parse() {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => {
+            warn!("Leaving out the {}", e);
+            Ok(None)
+        }
+    }
+}
+
+/// Parses the locale-based timestamp to a UTC timestamp. Assumes modem is already reporting UTC;
+/// callers that know better have to convert it themselves.
 fn timestamp_deserializer<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s: String = Deserialize::deserialize(deserializer)?;
-    Ok(NaiveDateTime::parse_from_str(&s, "%c").unwrap().and_utc())
+    NaiveDateTime::parse_from_str(&s, "%c")
+        .map(|timestamp| timestamp.and_utc())
+        .map_err(|_| Error::custom(format!("Unable to parse timestamp {:?}", s)))
 }
 
-#[derive(Debug, Clone)]
-pub struct LogEntry {
-    pub timestamp: DateTime<Utc>,
-    pub level: Level,
-    pub message: String,
+/// `0^12:34:56^31/12/2023^5^message`, or nothing if the line isn't laid out like that
+fn parse_log_line(line: &str, re: &Regex) -> Option<LogEntry> {
+    let captures = re.captures(line)?;
+    let capture_datetime = format!("{} {}", &captures["date"], &captures["time"]);
+    let timestamp = NaiveDateTime::parse_from_str(&capture_datetime, "%d/%m/%Y %T")
+        .ok()?
+        .and_utc();
+    let priority = captures["level"].parse().ok()?;
+    Some(LogEntry {
+        timestamp,
+        level: priority_to_level(priority),
+        message: captures["message"].to_string(),
+        priority: Some(priority),
+    })
 }
 
-fn log_parser<'de, D>(deserializer: D) -> Result<Vec<LogEntry>, D::Error>
+/// Lines that don't parse are set aside rather than failing the whole log
+fn log_parser<'de, D>(deserializer: D) -> Result<ParsedLogs, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s: String = Deserialize::deserialize(deserializer)?;
-    let re = Regex::new(r"0\^(?P<time>[:\d]+)\^(?P<date>[/\d]+)\^(?P<level>\d)\^(?P<message>.*)")
+    let re = Regex::new(r"0\^(?P<time>[:\d]+)\^(?P<date>[/\d]+)\^(?P<level>\d+)\^(?P<message>.*)")
         .unwrap();
 
-    let mut log_entries: Vec<LogEntry> = Vec::new();
-    for line in s.split("}-{") {
-        let captures = re
-            .captures(line)
-            .unwrap_or_else(|| panic!("Unable to parse: {}", line));
-
-        // parse the date first
-        let capture_time = captures.name("time").unwrap().as_str();
-        let capture_date = captures.name("date").unwrap().as_str();
-        let capture_datetime = capture_date.to_owned() + " " + capture_time;
-        let timestamp = NaiveDateTime::parse_from_str(&capture_datetime, "%d/%m/%Y %T").unwrap().and_utc();
-
-        let level: Level = match captures
-            .name("level")
-            .unwrap()
-            .as_str()
-            .parse::<u8>()
-            .unwrap()
-        {
-            3 => Level::Error,
-            4 => Level::Warn,
-            5 => Level::Info,
-            6 => Level::Debug,
-            _ => Level::Error,
-        };
-        let message: String = captures.name("message").unwrap().as_str().to_string();
-
-        log_entries.push(LogEntry {
-            timestamp,
-            level,
-            message,
-        })
+    let mut parsed = ParsedLogs::default();
+    for line in s.split("}-{").filter(|l| !l.trim().is_empty()) {
+        match parse_log_line(line, &re) {
+            Some(log_entry) => parsed.entries.push(log_entry),
+            None => parsed.unparsed.push(line.to_owned()),
+        }
     }
 
-    Ok(log_entries)
+    Ok(parsed)
 }
 
-#[derive(Debug, Clone)]
-pub enum Modulation {
-    QAM256,
-    OFDMPLC,
-    SCQAM, // unclear if modulation method, but maybe
-    Unknown,
-}
-
-impl Display for Modulation {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Modulation::QAM256 => write!(f, "QAM-256"),
-            Modulation::OFDMPLC => write!(f, "OFDM-PLC"),
-            Modulation::SCQAM => write!(f, "SC-QAM"),
-            Modulation::Unknown => write!(f, "Unknown"),
-        }
+const DOWNSTREAM_CHANNEL_PATTERN: &str = r"(?:\d+)\^(?P<lock_status>[\w ]+)\^(?P<modulation>[\w\d ]+)\^(?P<channel_id>\d+)\^(?P<frequency>\d+)\^(?P<power>-?\d+(?:\.\d+)?)\^(?P<snr>-?\d+(?:\.\d+)?)\^(?P<corrected>\d+)\^(?P<uncorrectables>\d+)\^";
+const UPSTREAM_CHANNEL_REGEX: &str = r"(?:\d+)\^(?P<lock_status>[\w ]+)\^(?P<modulation>[\w\d -]+)\^(?P<channel_id>\d+)\^(?P<width>\d+)\^(?P<frequency>\d+)\^(?P<power>-?\d+(?:\.\d+)?)\^";
+/// OFDMA rows swap the width for the FFT size (`2K`/`4K`) and append the occupied width at the end
+const OFDMA_UPSTREAM_CHANNEL_REGEX: &str = r"(?:\d+)\^(?P<lock_status>[\w ]+)\^OFDMA\^(?P<channel_id>\d+)\^(?P<fft_size>\d+)K\^(?P<frequency>\d+)\^(?P<power>-?\d+(?:\.\d+)?)\^(?P<width>\d+)\^";
+/// Reads one `^`-separated channel row, or nothing if it doesn't look like any kind of channel
+fn parse_channel(
+    line: &str,
+    downstream_channel_regex: &Regex,
+    upstream_channel_regex: &Regex,
+    ofdma_upstream_channel_regex: &Regex,
+) -> Option<Channel> {
+    // check these first so OFDMA rows never get read with the SC-QAM layout
+    if let Some(captures) = ofdma_upstream_channel_regex.captures(line) {
+        let fft_size: u32 = captures["fft_size"].parse().ok()?;
+        return Some(Channel::OfdmaUpstream(OfdmaUpstreamChannel {
+            channel_id: captures["channel_id"].parse().ok()?,
+            lock_status: &captures["lock_status"] == "Locked",
+            fft_size: fft_size * 1024,
+            frequency: captures["frequency"].parse().ok()?,
+            width: captures["width"].parse().ok()?,
+            power: captures["power"].parse().ok()?,
+        }));
     }
-}
 
-impl IntoFieldData for Modulation {
-    fn field_data(&self) -> FieldData {
-        FieldData::Str(self.to_string())
+    if let Some(captures) = downstream_channel_regex.captures(line) {
+        return Some(Channel::Downstream(DownstreamChannel {
+            channel_id: captures["channel_id"].parse().ok()?,
+            lock_status: &captures["lock_status"] == "Locked",
+            modulation: captures["modulation"].into(),
+            frequency: captures["frequency"].parse().ok()?,
+            power: captures["power"].parse().ok()?,
+            snr: captures["snr"].parse().ok()?,
+            corrected: parse_counter(&captures["corrected"])?,
+            uncorrectables: parse_counter(&captures["uncorrectables"])?,
+        }));
     }
-}
-
-#[derive(Debug, Clone, Metric)]
-#[measurement = "modem_downstream_channel"]
-pub struct DownstreamChannel {
-    #[telegraf(tag)]
-    pub channel_id: u8,
-    #[telegraf(tag)]
-    pub modulation: Modulation,
-    pub lock_status: bool,
-    pub frequency: u32,
-    pub power: u8,
-    pub snr: u8,
-    pub corrected: u32,      // TODO: does this need to be bigger?
-    pub uncorrectables: u32, // TODO: does this need to be bigger?
-}
 
-#[derive(Debug, Clone, Metric)]
-#[measurement = "modem_upstream_channel"]
-pub struct UpstreamChannel {
-    #[telegraf(tag)]
-    pub channel_id: u8,
-    #[telegraf(tag)]
-    pub modulation: Modulation,
-    pub lock_status: bool,
-    pub frequency: u32,
-    pub width: u32,
-    pub power: f64,
+    let captures = upstream_channel_regex.captures(line)?;
+    Some(Channel::Upstream(UpstreamChannel {
+        channel_id: captures["channel_id"].parse().ok()?,
+        lock_status: &captures["lock_status"] == "Locked",
+        modulation: captures["modulation"].into(),
+        frequency: captures["frequency"].parse().ok()?,
+        width: captures["width"].parse().ok()?,
+        power: captures["power"].parse().ok()?,
+    }))
 }
 
-#[derive(Debug, Clone)]
-pub enum Channel {
-    Downstream(DownstreamChannel),
-    Upstream(UpstreamChannel),
-}
-
-const DOWNSTREAM_CHANNEL_PATTERN: &str = r"(?:\d+)\^(?P<lock_status>\w+)\^(?P<modulation>[\w\d ]+)\^(?P<channel_id>\d+)\^(?P<frequency>\d+)\^(?P<power>\d+)\^(?P<snr>\d+)\^(?P<corrected>\d+)\^(?P<uncorrectables>\d+)\^";
-const UPSTREAM_CHANNEL_REGEX: &str = r"(?:\d+)\^(?P<lock_status>\w+)\^(?P<modulation>[\w\d -]+)\^(?P<channel_id>\d+)\^(?P<width>\d+)\^(?P<frequency>\d+)\^(?P<power>[\d.]+)\^";
-fn channel_parser<'de, D>(deserializer: D) -> Result<Vec<Channel>, D::Error>
+/// One odd row (new firmware, a channel the modem is still acquiring) shouldn't cost us every
+/// other channel, so rows that don't parse are logged and counted instead
+fn channel_parser<'de, D>(deserializer: D) -> Result<ParsedChannels, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s: String = Deserialize::deserialize(deserializer)?;
-    let mut channels: Vec<Channel> = Vec::new();
     let downstream_channel_regex = Regex::new(DOWNSTREAM_CHANNEL_PATTERN).unwrap();
     let upstream_channel_regex = Regex::new(UPSTREAM_CHANNEL_REGEX).unwrap();
-
-    for line in s.split("|+|") {
-        let captures: Captures;
-        let mut is_downstream_channel: bool = false;
-        if downstream_channel_regex.is_match(line) {
-            captures = downstream_channel_regex.captures(line).unwrap();
-            is_downstream_channel = true
-        } else if upstream_channel_regex.is_match(line) {
-            captures = upstream_channel_regex.captures(line).unwrap();
-        } else {
-            return Err(Error::custom(format!("Unable to match {} with any channel regex", line).as_str()));
+    let ofdma_upstream_channel_regex = Regex::new(OFDMA_UPSTREAM_CHANNEL_REGEX).unwrap();
+
+    let mut parsed = ParsedChannels::default();
+    for line in s.split("|+|").filter(|l| !l.trim().is_empty()) {
+        if is_placeholder_row(line) {
+            debug!("Channel row {:?} is a placeholder", line);
+            parsed.placeholders += 1;
+            continue;
         }
-
-        let channel_id: u8 = captures
-            .name("channel_id")
-            .unwrap()
-            .as_str()
-            .parse::<u8>()
-            .unwrap();
-        let lock_status: bool = matches!(captures.name("lock_status").unwrap().as_str(), "Locked");
-        let modulation: Modulation = match captures.name("modulation").unwrap().as_str() {
-            "QAM256" => Modulation::QAM256,
-            "OFDM PLC" => Modulation::OFDMPLC,
-            "SC-QAM" => Modulation::SCQAM,
-            _ => Modulation::Unknown,
-        };
-        let frequency: u32 = captures
-            .name("frequency")
-            .unwrap()
-            .as_str()
-            .parse()
-            .unwrap();
-
-        // different types, or different values
-        if is_downstream_channel {
-            let power: u8 = captures.name("power").unwrap().as_str().parse().unwrap();
-            let snr: u8 = captures.name("snr").unwrap().as_str().parse().unwrap();
-            let corrected: u32 = captures
-                .name("corrected")
-                .unwrap()
-                .as_str()
-                .parse()
-                .unwrap();
-            let uncorrectables: u32 = captures
-                .name("uncorrectables")
-                .unwrap()
-                .as_str()
-                .parse()
-                .unwrap();
-            channels.push(Channel::Downstream(DownstreamChannel {
-                channel_id,
-                lock_status,
-                modulation,
-                frequency,
-                power,
-                snr,
-                corrected,
-                uncorrectables,
-            }))
-        } else {
-            let width: u32 = captures.name("width").unwrap().as_str().parse().unwrap();
-            let power: f64 = captures.name("power").unwrap().as_str().parse().unwrap();
-            channels.push(Channel::Upstream(UpstreamChannel {
-                channel_id,
-                lock_status,
-                modulation,
-                frequency,
-                power,
-                width,
-            }))
+        match parse_channel(
+            line,
+            &downstream_channel_regex,
+            &upstream_channel_regex,
+            &ofdma_upstream_channel_regex,
+        ) {
+            Some(channel) => parsed.channels.push(channel),
+            None => {
+                warn!("Skipping channel row {:?}", line);
+                parsed.skipped += 1;
+            }
         }
     }
 
-    Ok(channels)
-}
-
-pub trait HasResult {
-    fn get_result(&self) -> String;
-}
-
-macro_rules! impl_has_result {
-    ($($t:ty),+ $(,)?) => ($(
-        impl HasResult for $t {
-            fn get_result(&self) -> String {
-                return self.result.clone();
-            }
-        }
-    )+)
+    Ok(parsed)
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -295,18 +221,26 @@ pub struct LoginResponse {
 }
 impl_has_result!(LoginResponse);
 
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct SetArrisConfigurationInfoResponse {
+    #[serde(rename = "SetArrisConfigurationInfoResult")]
+    result: String,
+}
+impl_has_result!(SetArrisConfigurationInfoResponse);
+
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct StatusStartupSequenceResponse {
     pub customer_conn_d_s_freq: String,
     pub customer_conn_d_s_comment: String,
-    pub customer_conn_connectivity_status: String,
+    pub customer_conn_connectivity_status: StartupStatus,
     pub customer_conn_connectivity_comment: String,
-    pub customer_conn_boot_status: String,
+    pub customer_conn_boot_status: StartupStatus,
     pub customer_conn_boot_comment: String,
-    pub customer_conn_configuration_file_status: String,
+    pub customer_conn_configuration_file_status: StartupStatus,
     pub customer_conn_configuration_file_comment: String,
-    pub customer_conn_security_status: String,
+    pub customer_conn_security_status: StartupStatus,
     pub customer_conn_security_comment: String,
     #[serde(rename = "GetCustomerStatusStartupSequenceResult")]
     result: String,
@@ -342,19 +276,54 @@ impl_has_result!(ArrisDeviceStatusResponse);
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct ArrisRegisterInfoResponse {
-    pub mac_address: String, // TODO: maybe make this a specific type?
-    pub serial_number: String,
+    /// empty if the modem sent something that isn't a MAC address
+    #[serde(deserialize_with = "lenient_deserializer")]
+    pub mac_address: Option<MacAddress>,
+    /// likewise
+    #[serde(deserialize_with = "lenient_deserializer")]
+    pub serial_number: Option<SerialNumber>,
     pub model_name: String,
     #[serde(rename = "GetArrisRegisterInfoResult")]
     result: String,
 }
 impl_has_result!(ArrisRegisterInfoResponse);
 
+/// Firmware revisions disagree on which of these are present, so they all default to empty
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ArrisConfigurationInfoResponse {
+    #[serde(default)]
+    pub downstream_frequency: String,
+    #[serde(default)]
+    pub downstream_plan: String,
+    #[serde(default)]
+    pub upstream_channel_id: String,
+    #[serde(default)]
+    pub configuration_file_name: String,
+    #[serde(rename = "GetArrisConfigurationInfoResult")]
+    result: String,
+}
+impl_has_result!(ArrisConfigurationInfoResponse);
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ArrisRegisterStatusResponse {
+    #[serde(default)]
+    pub docsis_version: String,
+    #[serde(default)]
+    pub provisioning_status: String,
+    #[serde(default)]
+    pub register_status: String,
+    #[serde(rename = "GetArrisRegisterStatusResult")]
+    result: String,
+}
+impl_has_result!(ArrisRegisterStatusResponse);
+
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct StatusDownstreamChannelInfo {
     #[serde(deserialize_with = "channel_parser")]
-    pub customer_conn_downstream_channel: Vec<Channel>,
+    pub customer_conn_downstream_channel: ParsedChannels,
     #[serde(rename = "GetCustomerStatusDownstreamChannelInfoResult")]
     result: String,
 }
@@ -364,7 +333,7 @@ impl_has_result!(StatusDownstreamChannelInfo);
 #[serde(rename_all = "PascalCase")]
 pub struct StatusUpstreamChannelInfo {
     #[serde(deserialize_with = "channel_parser")]
-    pub customer_conn_upstream_channel: Vec<Channel>,
+    pub customer_conn_upstream_channel: ParsedChannels,
     #[serde(rename = "GetCustomerStatusUpstreamChannelInfoResult")]
     result: String,
 }
@@ -388,7 +357,7 @@ impl_has_result!(GetMultipleHNAPsMetricsResponse);
 #[serde(rename_all = "PascalCase")]
 pub struct StatusLogResponse {
     #[serde(deserialize_with = "log_parser")]
-    pub customer_status_log_list: Vec<LogEntry>,
+    pub customer_status_log_list: ParsedLogs,
     #[serde(rename = "GetCustomerStatusLogResult")]
     result: String,
 }
@@ -402,3 +371,50 @@ pub struct GetMultipleHNAPsLogsResponse {
     result: String,
 }
 impl_has_result!(GetMultipleHNAPsLogsResponse);
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct GetMultipleHNAPsDeviceInfoResponse {
+    pub get_arris_configuration_info_response: ArrisConfigurationInfoResponse,
+    pub get_arris_register_status_response: ArrisRegisterStatusResponse,
+    pub get_arris_register_info_response: ArrisRegisterInfoResponse,
+    pub get_arris_device_status_response: ArrisDeviceStatusResponse,
+    #[serde(rename = "GetMultipleHNAPsResult")]
+    result: String,
+}
+impl_has_result!(GetMultipleHNAPsDeviceInfoResponse);
+
+impl GetMultipleHNAPsDeviceInfoResponse {
+    /// Identity, firmware, provisioning state and config file, as tags. Empty values are left out.
+    pub fn tags(&self) -> HashMap<String, String> {
+        let configuration = &self.get_arris_configuration_info_response;
+        let register_status = &self.get_arris_register_status_response;
+        let register_info = &self.get_arris_register_info_response;
+        let mac_address = register_info
+            .mac_address
+            .map(|mac_address| mac_address.to_string())
+            .unwrap_or_default();
+        let serial_number = register_info
+            .serial_number
+            .as_ref()
+            .map(SerialNumber::to_string)
+            .unwrap_or_default();
+        [
+            ("mac_address", &mac_address),
+            ("serial_number", &serial_number),
+            ("model_name", &register_info.model_name),
+            (
+                "firmware_version",
+                &self.get_arris_device_status_response.firmware_version,
+            ),
+            ("config_file", &configuration.configuration_file_name),
+            ("docsis_version", &register_status.docsis_version),
+            ("provisioning_status", &register_status.provisioning_status),
+            ("register_status", &register_status.register_status),
+        ]
+        .into_iter()
+        .filter(|(_, v)| !v.is_empty())
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .collect()
+    }
+}
@@ -0,0 +1,92 @@
+//! Feeds replies saved with [crate::ClientOptions::dump_raw] back through the deserializers, so
+//! parsers can be debugged and regression tested without a modem
+
+use crate::logging::warn;
+use crate::payloads::*;
+use crate::{parse_reply, xml, Error};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// What a saved reply turned out to be
+#[derive(Debug)]
+pub enum Replayed {
+    Metrics(Box<Metrics>),
+    Logs(Vec<LogEntry>),
+    DeviceInfo(HashMap<String, String>),
+}
+
+/// A file written by `dump_raw`, named `<timestamp>-<host>-<action>.<json|xml>`
+#[derive(Debug)]
+pub struct SavedReply {
+    pub host: String,
+    pub replayed: Replayed,
+}
+
+fn warn_unparsed(parsed: ParsedLogs) -> Vec<LogEntry> {
+    for line in &parsed.unparsed {
+        warn!("Unable to parse log line {:?}", line);
+    }
+    parsed.entries
+}
+
+/// Every model's `GetMultipleHNAPs` replies share an action name, so the only way to tell them
+/// apart is to see which one deserializes
+fn replay_multiple_hnaps(serialized_json: serde_json::Value) -> Result<Replayed, Error> {
+    const ACTION: &str = "GetMultipleHNAPs";
+    let metrics_error =
+        match parse_reply::<GetMultipleHNAPsMetricsResponse>(ACTION, serialized_json.clone()) {
+            Ok(response) => return Ok(Replayed::Metrics(Box::new(response.into()))),
+            Err(e) => e,
+        };
+    if let Ok(response) =
+        parse_reply::<GetMultipleHNAPsLogsResponse>(ACTION, serialized_json.clone())
+    {
+        return Ok(Replayed::Logs(warn_unparsed(
+            response
+                .get_customer_status_log_response
+                .customer_status_log_list,
+        )));
+    }
+    if let Ok(response) =
+        parse_reply::<GetMultipleHNAPsDeviceInfoResponse>(ACTION, serialized_json.clone())
+    {
+        return Ok(Replayed::DeviceInfo(response.tags()));
+    }
+    if let Ok(response) =
+        parse_reply::<GetMultipleHNAPsMotoMetricsResponse>(ACTION, serialized_json.clone())
+    {
+        return Ok(Replayed::Metrics(Box::new(response.into())));
+    }
+    if let Ok(response) = parse_reply::<GetMultipleHNAPsMotoLogsResponse>(ACTION, serialized_json) {
+        return Ok(Replayed::Logs(warn_unparsed(
+            response.get_moto_status_log_response.moto_status_log_list,
+        )));
+    }
+    // most saved replies are metrics, so that's the likeliest reason it didn't parse
+    Err(metrics_error)
+}
+
+pub fn replay_file(path: &Path) -> Result<SavedReply, Error> {
+    let invalid_name = || Error::Parse(format!("{} isn't a saved reply", path.display()));
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(invalid_name)?;
+    let (rest, action) = stem.rsplit_once('-').ok_or_else(invalid_name)?;
+    let (_, host) = rest.split_once('-').ok_or_else(invalid_name)?;
+
+    let body = std::fs::read_to_string(path)
+        .map_err(|e| Error::Parse(format!("unable to read {}: {}", path.display(), e)))?;
+    let serialized_json = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("xml") => xml::body_to_json(&body)?,
+        _ => serde_json::from_str(&body)?,
+    };
+    let replayed = match action {
+        "GetMultipleHNAPs" => replay_multiple_hnaps(serialized_json)?,
+        action => return Err(Error::Parse(format!("can't replay {} replies", action))),
+    };
+    Ok(SavedReply {
+        host: host.to_owned(),
+        replayed,
+    })
+}
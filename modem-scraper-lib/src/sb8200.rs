@@ -0,0 +1,223 @@
+use crate::logging::{debug, error, info};
+use crate::modem::{origin, ClientOptions, Modem};
+use crate::payloads::*;
+use crate::Error;
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::NaiveDateTime;
+use reqwest::{self, StatusCode};
+use scraper::{ElementRef, Html, Selector};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+pub(crate) const CONNECTION_STATUS_PATH: &str = "/cmconnectionstatus.html";
+const EVENT_LOG_PATH: &str = "/cmeventlog.html";
+
+/// Arris SB8200. There's no HNAP here, so we scrape the HTML status pages instead.
+#[derive(Default, Debug)]
+pub struct SB8200Client {
+    client: reqwest::Client,
+    endpoint: String,
+    credential: Option<String>,
+}
+
+/// Text content of a cell, whitespace trimmed
+fn cell_text(cell: &ElementRef) -> String {
+    cell.text().collect::<String>().trim().to_string()
+}
+
+/// Parses the downstream and upstream bonded channel tables on `/cmconnectionstatus.html`
+pub fn parse_connection_status(html: &str) -> Vec<Channel> {
+    let document = Html::parse_document(html);
+    let table_selector = Selector::parse("table").unwrap();
+    let header_selector = Selector::parse("th").unwrap();
+    let row_selector = Selector::parse("tr").unwrap();
+    let cell_selector = Selector::parse("td").unwrap();
+
+    let mut channels: Vec<Channel> = Vec::new();
+    for table in document.select(&table_selector) {
+        let title = match table.select(&header_selector).next() {
+            Some(header) => cell_text(&header),
+            None => continue,
+        };
+        let is_downstream_table = title.contains("Downstream Bonded Channels");
+        if !is_downstream_table && !title.contains("Upstream Bonded Channels") {
+            continue;
+        }
+
+        for row in table.select(&row_selector) {
+            let cells: Vec<String> = row.select(&cell_selector).map(|c| cell_text(&c)).collect();
+            // header rows don't start with a number, so they fall out here
+            let channel = if is_downstream_table {
+                parse_downstream_row(&cells)
+            } else {
+                parse_upstream_row(&cells)
+            };
+            match channel {
+                Some(c) => channels.push(c),
+                None => debug!("Skipping channel row {:?}", cells),
+            }
+        }
+    }
+
+    channels
+}
+
+/// Channel ID, Lock Status, Modulation, Frequency, Power, SNR/MER, Corrected, Uncorrectables
+fn parse_downstream_row(cells: &[String]) -> Option<Channel> {
+    if cells.len() < 8 {
+        return None;
+    }
+    Some(Channel::Downstream(DownstreamChannel {
+        channel_id: parse_leading_number(&cells[0])?,
+        lock_status: cells[1] == "Locked",
+        modulation: cells[2].as_str().into(),
+        frequency: parse_leading_number(&cells[3])?,
+        // power and SNR are fractional (and power can go negative) here
+        power: parse_leading_number(&cells[4])?,
+        snr: parse_leading_number(&cells[5])?,
+        corrected: parse_counter(&cells[6])?,
+        uncorrectables: parse_counter(&cells[7])?,
+    }))
+}
+
+/// Channel, Channel ID, Lock Status, US Channel Type, Frequency, Width, Power
+fn parse_upstream_row(cells: &[String]) -> Option<Channel> {
+    if cells.len() < 7 {
+        return None;
+    }
+    Some(Channel::Upstream(UpstreamChannel {
+        channel_id: parse_leading_number(&cells[1])?,
+        lock_status: cells[2] == "Locked",
+        // `SC-QAM Upstream`
+        modulation: cells[3].trim_end_matches(" Upstream").into(),
+        frequency: parse_leading_number(&cells[4])?,
+        width: parse_leading_number(&cells[5])?,
+        power: parse_leading_number(&cells[6])?,
+    }))
+}
+
+/// Parses the event log table on `/cmeventlog.html`: Date Time, Event ID, Event Level, Description
+pub fn parse_event_log(html: &str) -> Vec<LogEntry> {
+    let document = Html::parse_document(html);
+    let row_selector = Selector::parse("tr").unwrap();
+    let cell_selector = Selector::parse("td").unwrap();
+
+    let mut log_entries: Vec<LogEntry> = Vec::new();
+    for row in document.select(&row_selector) {
+        let cells: Vec<String> = row.select(&cell_selector).map(|c| cell_text(&c)).collect();
+        if cells.len() < 4 {
+            continue;
+        }
+        // entries logged before the modem gets the time from the CMTS say `Time Not Established`
+        let timestamp = match NaiveDateTime::parse_from_str(&cells[0], "%c") {
+            Ok(t) => t.and_utc(),
+            Err(_) => {
+                debug!("Skipping log row {:?}", cells);
+                continue;
+            }
+        };
+        let Some(priority) = parse_leading_number::<u8>(&cells[2]) else {
+            continue;
+        };
+        log_entries.push(LogEntry {
+            timestamp,
+            level: priority_to_level(priority),
+            message: cells[3].to_owned(),
+            priority: Some(priority),
+        });
+    }
+
+    log_entries
+}
+
+impl SB8200Client {
+    pub fn new(endpoint: String, options: &ClientOptions) -> Result<SB8200Client, Error> {
+        options.check_endpoint(&endpoint)?;
+        Ok(SB8200Client {
+            client: options.client_builder().cookie_store(true).build()?,
+            endpoint: origin(&endpoint),
+            credential: None,
+        })
+    }
+
+    async fn get_page(&self, path: &str) -> Result<String, Error> {
+        // newer firmware wants the credential token in the query string too, not just the cookie
+        let url = match &self.credential {
+            Some(credential) => format!("{}{}?ct_{}", self.endpoint, path, credential),
+            None => format!("{}{}", self.endpoint, path),
+        };
+        let res = self.client.get(url).send().await?;
+        match res.status() {
+            StatusCode::OK => Ok(res.text().await?),
+            status => {
+                error!("{:?}", res);
+                Err(Error::Status(status))
+            }
+        }
+    }
+
+    /// Older firmware doesn't require a login at all; newer firmware hands back a credential token
+    /// in exchange for basic auth
+    #[cfg_attr(feature = "tracing", instrument(skip(password)))]
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        if username.is_empty() {
+            return Ok(());
+        }
+        let token =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        let res = self
+            .client
+            .get(format!(
+                "{}{}?login_{}",
+                self.endpoint, CONNECTION_STATUS_PATH, token
+            ))
+            .basic_auth(username, Some(password))
+            .send()
+            .await?;
+        match res.status() {
+            StatusCode::OK => {
+                self.credential = Some(res.text().await?.trim().to_owned());
+                Ok(())
+            }
+            _ => {
+                error!("{:?}", res);
+                Err(Error::Login("Username or password error"))
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn channels(&mut self) -> Result<Vec<Channel>, Error> {
+        let html = self.get_page(CONNECTION_STATUS_PATH).await?;
+        let channels = parse_connection_status(&html);
+        info!("{:#?}", channels);
+        Ok(channels)
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn event_log(&mut self) -> Result<Vec<LogEntry>, Error> {
+        let html = self.get_page(EVENT_LOG_PATH).await?;
+        let log_entries = parse_event_log(&html);
+        info!("{:#?}", log_entries);
+        Ok(log_entries)
+    }
+}
+
+#[async_trait]
+impl Modem for SB8200Client {
+    async fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        SB8200Client::login(self, username, password).await
+    }
+
+    async fn metrics(&mut self) -> Result<Metrics, Error> {
+        Ok(Metrics {
+            channels: self.channels().await?,
+            ..Default::default()
+        })
+    }
+
+    async fn logs(&mut self) -> Result<Vec<LogEntry>, Error> {
+        self.event_log().await
+    }
+}
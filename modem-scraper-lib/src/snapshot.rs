@@ -0,0 +1,44 @@
+use crate::payloads::{LogEntry, Metrics};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// What [crate::Modem::device_info] found out about the modem, by name. Models only fill in what
+/// they report.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub mac_address: Option<String>,
+    pub serial_number: Option<String>,
+    pub model_name: Option<String>,
+    pub firmware_version: Option<String>,
+    pub config_file: Option<String>,
+    pub docsis_version: Option<String>,
+    pub provisioning_status: Option<String>,
+    pub register_status: Option<String>,
+}
+
+impl From<HashMap<String, String>> for DeviceInfo {
+    fn from(mut tags: HashMap<String, String>) -> Self {
+        DeviceInfo {
+            mac_address: tags.remove("mac_address"),
+            serial_number: tags.remove("serial_number"),
+            model_name: tags.remove("model_name"),
+            firmware_version: tags.remove("firmware_version"),
+            config_file: tags.remove("config_file"),
+            docsis_version: tags.remove("docsis_version"),
+            provisioning_status: tags.remove("provisioning_status"),
+            register_status: tags.remove("register_status"),
+        }
+    }
+}
+
+/// Everything a modem reports in one go, from [crate::Modem::scrape]. Timestamps are as the modem
+/// gave them, which the clients assume is UTC.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModemSnapshot {
+    /// the modem's own clock if it reports one, otherwise when the scrape finished
+    pub time: DateTime<Utc>,
+    pub device_info: DeviceInfo,
+    pub metrics: Metrics,
+    pub logs: Vec<LogEntry>,
+}
@@ -0,0 +1,291 @@
+use crate::logging::{debug, info};
+use crate::modem::Modem;
+use crate::payloads::*;
+use crate::Error;
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use log::Level;
+use snmp::{SyncSession, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+// DOCS-IF-MIB docsIfDownstreamChannelTable
+const DOWN_CHANNEL_ID: &[u32] = &[1, 3, 6, 1, 2, 1, 10, 127, 1, 1, 1, 1, 1];
+const DOWN_CHANNEL_FREQUENCY: &[u32] = &[1, 3, 6, 1, 2, 1, 10, 127, 1, 1, 1, 1, 2];
+const DOWN_CHANNEL_MODULATION: &[u32] = &[1, 3, 6, 1, 2, 1, 10, 127, 1, 1, 1, 1, 4];
+const DOWN_CHANNEL_POWER: &[u32] = &[1, 3, 6, 1, 2, 1, 10, 127, 1, 1, 1, 1, 6];
+// DOCS-IF-MIB docsIfSignalQualityTable, same index as the downstream table
+const SIGQ_CORRECTEDS: &[u32] = &[1, 3, 6, 1, 2, 1, 10, 127, 1, 1, 4, 1, 3];
+const SIGQ_UNCORRECTABLES: &[u32] = &[1, 3, 6, 1, 2, 1, 10, 127, 1, 1, 4, 1, 4];
+const SIGQ_SIGNAL_NOISE: &[u32] = &[1, 3, 6, 1, 2, 1, 10, 127, 1, 1, 4, 1, 5];
+// DOCS-IF-MIB docsIfUpstreamChannelTable
+const UP_CHANNEL_ID: &[u32] = &[1, 3, 6, 1, 2, 1, 10, 127, 1, 1, 2, 1, 1];
+const UP_CHANNEL_FREQUENCY: &[u32] = &[1, 3, 6, 1, 2, 1, 10, 127, 1, 1, 2, 1, 2];
+const UP_CHANNEL_WIDTH: &[u32] = &[1, 3, 6, 1, 2, 1, 10, 127, 1, 1, 2, 1, 3];
+// DOCS-IF3-MIB docsIf3CmStatusUsTxPower, same index as the upstream table
+const US_TX_POWER: &[u32] = &[1, 3, 6, 1, 4, 1, 4491, 2, 1, 20, 1, 2, 1, 1];
+// DOCS-CABLE-DEVICE-MIB docsDevEventTable
+const EVENT_FIRST_TIME: &[u32] = &[1, 3, 6, 1, 2, 1, 69, 1, 5, 8, 1, 2];
+const EVENT_LEVEL: &[u32] = &[1, 3, 6, 1, 2, 1, 69, 1, 5, 8, 1, 5];
+const EVENT_TEXT: &[u32] = &[1, 3, 6, 1, 2, 1, 69, 1, 5, 8, 1, 7];
+
+const DEFAULT_COMMUNITY: &str = "public";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Values copied out of the response buffer so they can outlive the session
+#[derive(Debug, Clone)]
+enum OwnedValue {
+    Number(i64),
+    Bytes(Vec<u8>),
+    Other,
+}
+
+impl OwnedValue {
+    fn number(&self) -> Option<i64> {
+        match self {
+            OwnedValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl From<Value<'_>> for OwnedValue {
+    fn from(value: Value<'_>) -> Self {
+        match value {
+            Value::Integer(n) => OwnedValue::Number(n),
+            Value::Counter32(n) | Value::Unsigned32(n) | Value::Timeticks(n) => {
+                OwnedValue::Number(n.into())
+            }
+            Value::Counter64(n) => OwnedValue::Number(n as i64),
+            Value::OctetString(b) => OwnedValue::Bytes(b.to_vec()),
+            _ => OwnedValue::Other,
+        }
+    }
+}
+
+/// Table column, keyed by row index
+type Column = BTreeMap<u32, OwnedValue>;
+
+/// Walks the column under `base` with GETNEXT until we fall off the end of it
+fn walk(session: &mut SyncSession, base: &[u32]) -> Column {
+    let mut column = Column::new();
+    let mut current: Vec<u32> = base.to_vec();
+    loop {
+        let response = match session.getnext(&current) {
+            Ok(response) => response,
+            Err(e) => {
+                debug!("SNMP walk of {:?} stopped: {:?}", base, e);
+                break;
+            }
+        };
+        let mut next: Option<Vec<u32>> = None;
+        for (name, value) in response.varbinds {
+            let mut buf: snmp::ObjIdBuf = [0; 128];
+            let oid = match name.read_name(&mut buf) {
+                Ok(oid) => oid,
+                Err(_) => break,
+            };
+            if !oid.starts_with(base) || oid.len() <= base.len() {
+                break;
+            }
+            column.insert(*oid.last().unwrap(), value.into());
+            next = Some(oid.to_vec());
+        }
+        match next {
+            Some(oid) => current = oid,
+            None => break,
+        }
+    }
+    column
+}
+
+fn number(column: &Column, index: &u32) -> Option<i64> {
+    column.get(index).and_then(OwnedValue::number)
+}
+
+/// `DateAndTime` is 8 or 11 bytes: 2 byte year, month, day, hour, minute, second, deci-second
+fn parse_date_and_time(bytes: &[u8]) -> Option<chrono::DateTime<Utc>> {
+    if bytes.len() < 8 {
+        return None;
+    }
+    let year = u16::from_be_bytes([bytes[0], bytes[1]]);
+    Some(
+        NaiveDate::from_ymd_opt(year.into(), bytes[2].into(), bytes[3].into())?
+            .and_hms_opt(bytes[4].into(), bytes[5].into(), bytes[6].into())?
+            .and_utc(),
+    )
+}
+
+/// docsDevEvLevel goes emergency(1) to debug(8)
+fn event_level_to_level(level: i64) -> Level {
+    match level {
+        1..=4 => Level::Error,
+        5 => Level::Warn,
+        6 | 7 => Level::Info,
+        8 => Level::Debug,
+        _ => Level::Error,
+    }
+}
+
+fn poll_channels(session: &mut SyncSession) -> Vec<Channel> {
+    let mut channels: Vec<Channel> = Vec::new();
+
+    let down_ids = walk(session, DOWN_CHANNEL_ID);
+    let down_frequencies = walk(session, DOWN_CHANNEL_FREQUENCY);
+    let down_modulations = walk(session, DOWN_CHANNEL_MODULATION);
+    let down_powers = walk(session, DOWN_CHANNEL_POWER);
+    let correcteds = walk(session, SIGQ_CORRECTEDS);
+    let uncorrectables = walk(session, SIGQ_UNCORRECTABLES);
+    let signal_noises = walk(session, SIGQ_SIGNAL_NOISE);
+    for (index, id) in down_ids.iter() {
+        let channel_id = match id.number() {
+            Some(id) => id as u8,
+            None => continue,
+        };
+        channels.push(Channel::Downstream(DownstreamChannel {
+            channel_id,
+            // rows only exist for channels the modem has acquired
+            lock_status: true,
+            modulation: match number(&down_modulations, index) {
+                Some(3) => Modulation::QAM64,
+                Some(4) => Modulation::QAM256,
+                _ => Modulation::Other("Unknown".to_owned()),
+            },
+            frequency: number(&down_frequencies, index).unwrap_or_default() as u32,
+            // TenthdBmV and TenthdB
+            power: number(&down_powers, index).unwrap_or_default() as f64 / 10.0,
+            snr: number(&signal_noises, index).unwrap_or_default() as f64 / 10.0,
+            corrected: number(&correcteds, index).unwrap_or_default() as u64,
+            uncorrectables: number(&uncorrectables, index).unwrap_or_default() as u64,
+        }));
+    }
+
+    let up_ids = walk(session, UP_CHANNEL_ID);
+    let up_frequencies = walk(session, UP_CHANNEL_FREQUENCY);
+    let up_widths = walk(session, UP_CHANNEL_WIDTH);
+    let up_powers = walk(session, US_TX_POWER);
+    for (index, id) in up_ids.iter() {
+        let channel_id = match id.number() {
+            Some(id) => id as u8,
+            None => continue,
+        };
+        channels.push(Channel::Upstream(UpstreamChannel {
+            channel_id,
+            lock_status: true,
+            modulation: Modulation::Other("Unknown".to_owned()),
+            frequency: number(&up_frequencies, index).unwrap_or_default() as u32,
+            width: number(&up_widths, index).unwrap_or_default() as u32,
+            power: number(&up_powers, index).unwrap_or_default() as f64 / 10.0,
+        }));
+    }
+
+    channels
+}
+
+fn poll_events(session: &mut SyncSession) -> Vec<LogEntry> {
+    let first_times = walk(session, EVENT_FIRST_TIME);
+    let levels = walk(session, EVENT_LEVEL);
+    let texts = walk(session, EVENT_TEXT);
+
+    let mut log_entries: Vec<LogEntry> = Vec::new();
+    for (index, text) in texts.iter() {
+        let message = match text {
+            OwnedValue::Bytes(b) => String::from_utf8_lossy(b).trim().to_string(),
+            _ => continue,
+        };
+        let timestamp = match first_times.get(index) {
+            Some(OwnedValue::Bytes(b)) => match parse_date_and_time(b) {
+                Some(t) => t,
+                None => continue,
+            },
+            _ => continue,
+        };
+        log_entries.push(LogEntry {
+            timestamp,
+            level: event_level_to_level(number(&levels, index).unwrap_or_default()),
+            message,
+            // docsDevEvLevel is numbered differently, and the same on every modem
+            priority: None,
+        });
+    }
+    log_entries
+}
+
+/// Polls DOCS-IF-MIB/DOCS-IF3-MIB over SNMPv2c. Works with most modems that answer SNMP on the
+/// LAN side, regardless of model.
+#[derive(Debug, Clone)]
+pub struct SnmpClient {
+    /// `host:port`, usually `192.168.100.1:161`
+    address: String,
+    community: String,
+}
+
+impl SnmpClient {
+    pub fn new(address: String) -> SnmpClient {
+        SnmpClient {
+            address,
+            community: DEFAULT_COMMUNITY.to_owned(),
+        }
+    }
+
+    /// SNMP is blocking, so every poll gets its own session on the blocking pool
+    async fn poll<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut SyncSession) -> T + Send + 'static,
+    {
+        let address = self.address.clone();
+        let community = self.community.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut session =
+                SyncSession::new(address.as_str(), community.as_bytes(), Some(TIMEOUT), 0)
+                    .map_err(|e| Error::Snmp(format!("unable to open session: {}", e)))?;
+            Ok(f(&mut session))
+        })
+        .await
+        .map_err(|e| Error::Snmp(e.to_string()))?
+    }
+
+    /// There's no login for SNMPv2c; the password is used as the community string if set
+    #[cfg_attr(feature = "tracing", instrument(skip(password)))]
+    pub async fn login(&mut self, _username: &str, password: &str) -> Result<(), Error> {
+        if !password.is_empty() {
+            self.community = password.to_owned();
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn channels(&mut self) -> Result<Vec<Channel>, Error> {
+        let channels = self.poll(poll_channels).await?;
+        info!("{:#?}", channels);
+        Ok(channels)
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn events(&mut self) -> Result<Vec<LogEntry>, Error> {
+        let log_entries = self.poll(poll_events).await?;
+        info!("{:#?}", log_entries);
+        Ok(log_entries)
+    }
+}
+
+#[async_trait]
+impl Modem for SnmpClient {
+    async fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        SnmpClient::login(self, username, password).await
+    }
+
+    async fn metrics(&mut self) -> Result<Metrics, Error> {
+        Ok(Metrics {
+            channels: self.channels().await?,
+            ..Default::default()
+        })
+    }
+
+    async fn logs(&mut self) -> Result<Vec<LogEntry>, Error> {
+        self.events().await
+    }
+}
@@ -0,0 +1,73 @@
+//! XML SOAP envelopes for firmware that won't take the JSON flavour of HNAP
+
+use crate::SOAP_DOMAIN;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Wraps `action` and its params in a SOAP envelope. Each param becomes a child element.
+pub(crate) fn envelope(action: &str, params: &HashMap<&str, &str>) -> String {
+    let body: String = params
+        .iter()
+        .map(|(k, v)| format!("<{}>{}</{}>", k, escape(v), k))
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><soap:Envelope xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:xsd="http://www.w3.org/2001/XMLSchema" xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/"><soap:Body><{} xmlns="{}">{}</{}></soap:Body></soap:Envelope>"#,
+        action, SOAP_DOMAIN, body, action
+    )
+}
+
+/// Converts the contents of the envelope's body to the same shape the JSON flavour returns, so
+/// the payload types don't care which transport was used. Leaf elements become strings.
+pub(crate) fn body_to_json(xml: &str) -> Result<Value, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    // (element name, child elements, text)
+    let mut stack: Vec<(String, Map<String, Value>, String)> =
+        vec![(String::new(), Map::new(), String::new())];
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                stack.push((name, Map::new(), String::new()));
+            }
+            Event::Empty(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                if let Some((_, children, _)) = stack.last_mut() {
+                    children.insert(name, Value::String(String::new()));
+                }
+            }
+            Event::Text(t) => {
+                if let Some((_, _, text)) = stack.last_mut() {
+                    text.push_str(&t.unescape()?);
+                }
+            }
+            Event::CData(t) => {
+                if let Some((_, _, text)) = stack.last_mut() {
+                    text.push_str(&String::from_utf8_lossy(&t.into_inner()));
+                }
+            }
+            // the root entry never gets popped, so there's always a parent
+            Event::End(_) if stack.len() > 1 => {
+                let (name, children, text) = stack.pop().unwrap();
+                let value = match children.is_empty() {
+                    true => Value::String(text),
+                    false => Value::Object(children),
+                };
+                stack.last_mut().unwrap().1.insert(name, value);
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    let root = Value::Object(stack.swap_remove(0).1);
+    let body = root
+        .get("Envelope")
+        .and_then(|envelope| envelope.get("Body"))
+        .cloned();
+    Ok(body.unwrap_or(root))
+}
@@ -0,0 +1,214 @@
+use modem_scraper_lib::payloads::{
+    Channel, Metrics, Modulation, MotoStatusDownstreamChannelInfo, MotoStatusUpstreamChannelInfo,
+    PlaceholderChannels, StatusDownstreamChannelInfo, StatusUpstreamChannelInfo,
+};
+use serde_json::json;
+
+fn downstream(channels: &[Channel]) -> Vec<(u8, f64, f64)> {
+    channels
+        .iter()
+        .filter_map(|channel| match channel {
+            Channel::Downstream(c) => Some((c.channel_id, c.power, c.snr)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn s33_downstream_negative_and_fractional_power() {
+    let response: StatusDownstreamChannelInfo = serde_json::from_value(json!({
+        "CustomerConnDownstreamChannel": "1^Locked^QAM256^20^567000000^-3.5^38.9^12^0^|+|2^Locked^QAM256^21^573000000^-12^35^4294967295^3^|+|3^Locked^QAM256^22^579000000^4^41^0^0^",
+        "GetCustomerStatusDownstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+
+    assert_eq!(
+        downstream(&response.customer_conn_downstream_channel.channels),
+        vec![(20, -3.5, 38.9), (21, -12.0, 35.0), (22, 4.0, 41.0)]
+    );
+}
+
+#[test]
+fn s33_upstream_negative_power() {
+    let response: StatusUpstreamChannelInfo = serde_json::from_value(json!({
+        "CustomerConnUpstreamChannel": "1^Locked^SC-QAM^1^6400000^16400000^-1.0^",
+        "GetCustomerStatusUpstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+
+    match &response.customer_conn_upstream_channel.channels[..] {
+        [Channel::Upstream(c)] => assert_eq!(c.power, -1.0),
+        channels => panic!("expected one upstream channel, got {:?}", channels),
+    }
+}
+
+#[test]
+fn mb86xx_downstream_negative_power() {
+    let response: MotoStatusDownstreamChannelInfo = serde_json::from_value(json!({
+        "MotoConnDownstreamChannel": "1^Locked^QAM256^5^495.0^ -7.2^37.6^10^2^|+|2^Locked^QAM256^6^501.0^ 0.4^40.1^0^0^",
+        "GetMotoStatusDownstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+
+    assert_eq!(
+        downstream(&response.moto_conn_downstream_channel.channels),
+        vec![(5, -7.2, 37.6), (6, 0.4, 40.1)]
+    );
+}
+
+#[test]
+fn mb86xx_downstream_negative_snr() {
+    let response: MotoStatusDownstreamChannelInfo = serde_json::from_value(json!({
+        "MotoConnDownstreamChannel": "1^Locked^QAM256^5^495.0^ -7.2^-1.5^10^2^",
+        "GetMotoStatusDownstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+
+    assert_eq!(
+        downstream(&response.moto_conn_downstream_channel.channels),
+        vec![(5, -7.2, -1.5)]
+    );
+}
+
+#[test]
+fn s33_counters_past_u32() {
+    let response: StatusDownstreamChannelInfo = serde_json::from_value(json!({
+        "CustomerConnDownstreamChannel": "1^Locked^QAM256^20^567000000^1^38^4294967296^123456789012^|+|2^Locked^QAM256^21^573000000^1^38^99999999999999999999999^0^",
+        "GetCustomerStatusDownstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+
+    let counters: Vec<(u64, u64)> = response
+        .customer_conn_downstream_channel
+        .channels
+        .iter()
+        .filter_map(|channel| match channel {
+            Channel::Downstream(c) => Some((c.corrected, c.uncorrectables)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(counters, vec![(4294967296, 123456789012), (u64::MAX, 0)]);
+}
+
+#[test]
+fn s33_skips_malformed_rows() {
+    let response: StatusDownstreamChannelInfo = serde_json::from_value(json!({
+        "CustomerConnDownstreamChannel": "1^Locked^QAM256^20^567000000^1^38^0^0^|+|2^Locked^QAM256^^^garbage^|+|3^Locked^QAM256^999^579000000^1^38^0^0^|+|4^Locked^QAM256^22^585000000^2^39^0^0^",
+        "GetCustomerStatusDownstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+
+    let parsed = response.customer_conn_downstream_channel;
+    // channel 999 doesn't fit a u8
+    assert_eq!(parsed.skipped, 2);
+    assert_eq!(
+        downstream(&parsed.channels),
+        vec![(20, 1.0, 38.0), (22, 2.0, 39.0)]
+    );
+}
+
+#[test]
+fn s33_keeps_unrecognized_modulations() {
+    let response: StatusDownstreamChannelInfo = serde_json::from_value(json!({
+        "CustomerConnDownstreamChannel": "1^Locked^QAM64^20^567000000^1^38^0^0^|+|2^Locked^QAM1024^21^573000000^1^38^0^0^",
+        "GetCustomerStatusDownstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+    let modulations: Vec<Modulation> = response
+        .customer_conn_downstream_channel
+        .channels
+        .into_iter()
+        .filter_map(|channel| match channel {
+            Channel::Downstream(c) => Some(c.modulation),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        modulations,
+        vec![Modulation::QAM64, Modulation::Other("QAM1024".to_owned())]
+    );
+
+    let response: StatusUpstreamChannelInfo = serde_json::from_value(json!({
+        "CustomerConnUpstreamChannel": "1^Locked^ATDMA^1^6400000^16400000^45^",
+        "GetCustomerStatusUpstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+    match &response.customer_conn_upstream_channel.channels[..] {
+        [Channel::Upstream(c)] => assert_eq!(c.modulation, Modulation::ATDMA),
+        channels => panic!("expected one upstream channel, got {:?}", channels),
+    }
+}
+
+#[test]
+fn readings_against_recommended_ranges() {
+    let response: StatusDownstreamChannelInfo = serde_json::from_value(json!({
+        "CustomerConnDownstreamChannel": "1^Locked^QAM256^20^567000000^-3.5^38.9^12^0^|+|2^Locked^QAM256^21^573000000^-12^31^0^0^",
+        "GetCustomerStatusDownstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+
+    let in_range: Vec<_> = response
+        .customer_conn_downstream_channel
+        .channels
+        .iter()
+        .map(Channel::in_range)
+        .collect();
+    assert_eq!(
+        in_range,
+        vec![
+            vec![("power", true), ("snr", true)],
+            vec![("power", false), ("snr", false)],
+        ]
+    );
+}
+
+#[test]
+fn partial_service_placeholders() {
+    let response: StatusDownstreamChannelInfo = serde_json::from_value(json!({
+        "CustomerConnDownstreamChannel": "1^Locked^QAM256^20^567000000^-3.5^38.9^12^0^|+|2^Not Locked^QAM256^21^573000000^0^0^0^0^|+|3^----^----^----^----^----^----^----^----^",
+        "GetCustomerStatusDownstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+    let parsed = response.customer_conn_downstream_channel;
+    assert_eq!((parsed.skipped, parsed.placeholders), (0, 1));
+    assert_eq!(
+        downstream(&parsed.channels),
+        vec![(20, -3.5, 38.9), (21, 0.0, 0.0)]
+    );
+
+    let moto: MotoStatusUpstreamChannelInfo = serde_json::from_value(json!({
+        "MotoConnUpstreamChannel": "1^Locked^SC-QAM^1^5120^16.4^ 44.0^|+|2^Not Locked^----^----^----^----^----^",
+        "GetMotoStatusUpstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+    assert_eq!(moto.moto_conn_upstream_channel.placeholders, 1);
+
+    let mut channels = parsed.channels;
+    channels.extend(moto.moto_conn_upstream_channel.channels);
+    let metrics = Metrics {
+        channels,
+        placeholder_channels: PlaceholderChannels {
+            downstream: 0,
+            upstream: 1,
+        },
+        ..Default::default()
+    };
+    // downstream has an unlocked channel, upstream a placeholder
+    assert_eq!(
+        metrics.partial_service(),
+        vec![("downstream", true), ("upstream", true)]
+    );
+}
+
+#[test]
+fn mb86xx_skips_malformed_rows() {
+    let response: MotoStatusDownstreamChannelInfo = serde_json::from_value(json!({
+        "MotoConnDownstreamChannel": "1^Locked^QAM256^5^495.0.1^ -7.2^37.6^10^2^|+|2^Locked^QAM256^^garbage^|+|3^Locked^QAM256^6^501.0^ 0.4^40.1^0^0^",
+        "GetMotoStatusDownstreamChannelInfoResult": "OK",
+    }))
+    .unwrap();
+
+    let parsed = response.moto_conn_downstream_channel;
+    assert_eq!(parsed.skipped, 2);
+    assert_eq!(downstream(&parsed.channels), vec![(6, 0.4, 40.1)]);
+}
@@ -0,0 +1,46 @@
+use modem_scraper_lib::payloads::StatusConnectionInfoResponse;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+fn connection_info(uptime: &str) -> Result<StatusConnectionInfoResponse, serde_json::Error> {
+    serde_json::from_value(json!({
+        "CustomerConnSystemUpTime": uptime,
+        "CustomerCurSystemTime": "Sun Dec 31 12:34:56 2023",
+        "CustomerConnNetworkAccess": "Allowed",
+        "GetCustomerStatusConnectionInfoResult": "OK",
+    }))
+}
+
+#[test]
+fn uptime_formats() {
+    for (uptime, seconds) in [
+        ("12 days 13h:14m:15s", 12 * 86400 + 13 * 3600 + 14 * 60 + 15),
+        ("1 day 00h:00m:01s", 86401),
+        ("0 day 01h:00m:00s", 3600),
+        ("13h:14m:15s", 13 * 3600 + 14 * 60 + 15),
+    ] {
+        assert_eq!(
+            connection_info(uptime)
+                .unwrap()
+                .customer_conn_system_up_time,
+            Duration::from_secs(seconds),
+            "{}",
+            uptime
+        );
+    }
+}
+
+#[test]
+fn unparseable_uptime_is_an_error() {
+    for uptime in ["", "soon", "12 days", "99999999999999999999 days 0h:0m:0s"] {
+        assert!(connection_info(uptime).is_err(), "{}", uptime);
+    }
+    // and not a panic on something that isn't a string at all
+    let response: Result<StatusConnectionInfoResponse, _> = serde_json::from_value(json!({
+        "CustomerConnSystemUpTime": Value::Null,
+        "CustomerCurSystemTime": "Sun Dec 31 12:34:56 2023",
+        "CustomerConnNetworkAccess": "Allowed",
+        "GetCustomerStatusConnectionInfoResult": "OK",
+    }));
+    assert!(response.is_err());
+}
@@ -0,0 +1,89 @@
+use modem_scraper_lib::payloads::{ArrisRegisterInfoResponse, MacAddress, SerialNumber};
+use serde_json::json;
+
+#[test]
+fn mac_address_formats() {
+    let expected = MacAddress([0xaa, 0xbb, 0xcc, 0x01, 0x02, 0x03]);
+    for s in [
+        "aa:bb:cc:01:02:03",
+        "AA-BB-CC-01-02-03",
+        "aabb.cc01.0203",
+        " AABBCC010203 ",
+    ] {
+        assert_eq!(s.parse::<MacAddress>().unwrap(), expected, "{}", s);
+    }
+    assert_eq!(expected.to_string(), "AA:BB:CC:01:02:03");
+
+    for s in [
+        "",
+        "aa:bb:cc:01:02",
+        "aa:bb:cc:01:02:03:04",
+        "gg:bb:cc:01:02:03",
+    ] {
+        assert!(s.parse::<MacAddress>().is_err(), "{}", s);
+    }
+}
+
+#[test]
+fn register_info_with_bad_mac_address() {
+    let response: ArrisRegisterInfoResponse = serde_json::from_value(json!({
+        "MacAddress": "not a mac",
+        "SerialNumber": "ABC123",
+        "ModelName": "S33",
+        "GetArrisRegisterInfoResult": "OK",
+    }))
+    .unwrap();
+    assert_eq!(response.mac_address, None);
+    assert_eq!(
+        response.serial_number.as_ref().map(SerialNumber::as_str),
+        Some("ABC123")
+    );
+}
+
+fn register_info(mac_address: &str, serial_number: &str) -> ArrisRegisterInfoResponse {
+    serde_json::from_value(json!({
+        "MacAddress": mac_address,
+        "SerialNumber": serial_number,
+        "ModelName": "S33",
+        "GetArrisRegisterInfoResult": "OK",
+    }))
+    .unwrap()
+}
+
+#[test]
+fn register_info_mac_address_forms() {
+    let expected = Some(MacAddress([0xaa, 0xbb, 0xcc, 0x01, 0x02, 0x03]));
+    for accepted in ["AA-BB-CC-01-02-03", "aabbcc010203", "aa:bb:cc:01:02:03"] {
+        assert_eq!(
+            register_info(accepted, "ABC123").mac_address,
+            expected,
+            "{}",
+            accepted
+        );
+    }
+    for rejected in [
+        "aa:bb:cc:01:02",
+        "zz-bb-cc-01-02-03",
+        "aabbcc0102030405",
+        "",
+    ] {
+        assert_eq!(
+            register_info(rejected, "ABC123").mac_address,
+            None,
+            "{}",
+            rejected
+        );
+    }
+}
+
+#[test]
+fn register_info_serial_numbers() {
+    let serial_number = |s: &str| {
+        register_info("aa:bb:cc:01:02:03", s)
+            .serial_number
+            .map(|serial_number| serial_number.to_string())
+    };
+    assert_eq!(serial_number(" ABC123 "), Some("ABC123".to_owned()));
+    assert_eq!(serial_number(""), None);
+    assert_eq!(serial_number("ABC 123"), None);
+}
@@ -0,0 +1,178 @@
+use log::Level;
+use modem_scraper_lib::events;
+use modem_scraper_lib::payloads::{
+    LogCategory, LogLevels, MotoStatusLogResponse, StatusLogResponse,
+};
+use serde_json::json;
+
+#[test]
+fn s33_quarantines_unparseable_lines() {
+    let response: StatusLogResponse = serde_json::from_value(json!({
+        "CustomerStatusLogList": "0^12:34:56^31/12/2023^3^No Ranging Response received - T3 time-out}-{something new^from the firmware}-{0^01:02:03^45/13/2023^5^bad date}-{0^07:08:09^01/01/2024^5^Cable Modem Reboot",
+        "GetCustomerStatusLogResult": "OK",
+    }))
+    .unwrap();
+
+    let parsed = response.customer_status_log_list;
+    let entries: Vec<(String, Level, &str)> = parsed
+        .entries
+        .iter()
+        .map(|e| (e.timestamp.to_rfc3339(), e.level, e.message.as_str()))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (
+                "2023-12-31T12:34:56+00:00".to_owned(),
+                Level::Error,
+                "No Ranging Response received - T3 time-out"
+            ),
+            (
+                "2024-01-01T07:08:09+00:00".to_owned(),
+                Level::Info,
+                "Cable Modem Reboot"
+            ),
+        ]
+    );
+    assert_eq!(
+        parsed.unparsed,
+        vec![
+            "something new^from the firmware",
+            "0^01:02:03^45/13/2023^5^bad date"
+        ]
+    );
+}
+
+#[test]
+fn mb86xx_quarantines_unparseable_lines() {
+    let response: MotoStatusLogResponse = serde_json::from_value(json!({
+        "MotoStatusLogList": "10:23:45^Thu Jan 14 2021^3^No Ranging Response received - T3 time-out}-{something new^from the firmware}-{01:02:03^Thu Jan 45 2021^5^bad date",
+        "GetMotoStatusLogResult": "OK",
+    }))
+    .unwrap();
+
+    let parsed = response.moto_status_log_list;
+    let entries: Vec<(String, &str)> = parsed
+        .entries
+        .iter()
+        .map(|e| (e.timestamp.to_rfc3339(), e.message.as_str()))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![(
+            "2021-01-14T10:23:45+00:00".to_owned(),
+            "No Ranging Response received - T3 time-out"
+        )]
+    );
+    assert_eq!(
+        parsed.unparsed,
+        vec![
+            "something new^from the firmware",
+            "01:02:03^Thu Jan 45 2021^5^bad date"
+        ]
+    );
+}
+
+#[test]
+fn docsis_fields_come_out_of_the_message() {
+    let response: StatusLogResponse = serde_json::from_value(json!({
+        "CustomerStatusLogList": "0^12:34:56^31/12/2023^3^No Ranging Response received - T3 time-out;CM-MAC=aa:bb:cc:dd:ee:ff;CMTS-MAC=00:11:22:33:44:55;CM-QOS=1.1;CM-VER=3.1;}-{0^12:35:00^31/12/2023^134^CM-STATUS message sent. Event Type Code: 16; Chan ID: 32; DSID: N/A; MAC Addr: N/A;}-{0^12:36:00^31/12/2023^4^<66030111> Something only this firmware logs",
+        "GetCustomerStatusLogResult": "OK",
+    }))
+    .unwrap();
+    let entries = response.customer_status_log_list.entries;
+    let fields: Vec<Vec<(String, String)>> = entries
+        .iter()
+        .map(|e| e.fields().into_iter().collect())
+        .collect();
+    let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+    let t3 = events::by_id(82000200).unwrap();
+    assert_eq!(
+        fields,
+        vec![
+            pairs(&[
+                ("cm_mac", "aa:bb:cc:dd:ee:ff"),
+                ("cm_qos", "1.1"),
+                ("cm_ver", "3.1"),
+                ("cmts_mac", "00:11:22:33:44:55"),
+                // matched by its wording, there's no ID in the message
+                ("event_description", t3.description),
+                ("event_id", "82000200"),
+                ("event_severity", "critical"),
+            ]),
+            pairs(&[
+                ("channel_id", "32"),
+                ("cm_status_description", "OFDM downstream profile failure"),
+                ("cm_status_event", "16"),
+                ("facility", "local0"),
+            ]),
+            pairs(&[("event_id", "66030111")]),
+        ]
+    );
+    // 134 is local0 with a severity of 6
+    assert_eq!(entries[1].level, Level::Debug);
+}
+
+#[test]
+fn log_levels_can_be_remapped() {
+    let response: StatusLogResponse = serde_json::from_value(json!({
+        "CustomerStatusLogList": "0^12:34:56^31/12/2023^6^Honoring MDD}-{0^12:34:57^31/12/2023^7^Something chatty}-{0^12:34:58^31/12/2023^3^T3 time-out",
+        "GetCustomerStatusLogResult": "OK",
+    }))
+    .unwrap();
+    let mut entries = response.customer_status_log_list.entries;
+    let log_levels: LogLevels = serde_json::from_value(json!({"6": "info", "7": "debug"})).unwrap();
+    log_levels.apply(&mut entries);
+    let levels: Vec<Level> = entries.iter().map(|e| e.level).collect();
+    assert_eq!(levels, vec![Level::Info, Level::Debug, Level::Error]);
+}
+
+#[test]
+fn event_codes_by_id_or_wording() {
+    let by_wording = events::by_message(
+        "MIMO Event MIMO: Stored MIMO=-1 post cfg file MIMO=-1;CM-MAC=aa:bb:cc:dd:ee:ff;",
+    );
+    assert_eq!(by_wording.map(|event_code| event_code.id), Some(90000000));
+    assert_eq!(
+        events::by_message("sync timing synchronization failure - loss of sync"),
+        events::by_id(84000500)
+    );
+    assert_eq!(
+        events::by_message("Honoring MDD; IP provisioning mode = IPv6"),
+        None
+    );
+    assert_eq!(events::by_id(12345678), None);
+}
+
+#[test]
+fn log_categories() {
+    let categories: Vec<&str> = [
+        "No Ranging Response received - T3 time-out;CM-MAC=aa:bb:cc:dd:ee:ff;",
+        "SYNC Timing Synchronization failure - Loss of Sync",
+        "Dynamic Range Window violation",
+        "DHCP FAILED - Discover sent, no offer received",
+        "SW Download INIT - Via Config file",
+        "CM-STATUS message sent. Event Type Code: 7; Chan ID: 2;",
+        "Cable Modem Reboot",
+    ]
+    .into_iter()
+    .map(|message| LogCategory::classify(message).as_str())
+    .collect();
+    assert_eq!(
+        categories,
+        vec![
+            "ranging",
+            "sync",
+            "power",
+            "registration",
+            "firmware",
+            "ranging",
+            "other"
+        ]
+    );
+}
@@ -0,0 +1,300 @@
+#![cfg(feature = "mock-server")]
+
+use modem_scraper_lib::mock::MockModem;
+use modem_scraper_lib::{
+    ClientOptions, Error, HnapTimestamp, LoginLimits, Modem, RetryPolicy, SOAPClient,
+};
+use serde_json::json;
+
+fn s33() -> MockModem {
+    MockModem::new("admin", "hunter2")
+        .reply(
+            "GetArrisDeviceStatus",
+            json!({
+                "FirmwareVersion": "AT01.01.010.042324_S3.04.735",
+                "InternetConnection": "Connected",
+                "DownstreamFrequency": "567000000 Hz",
+                "DownstreamSignalPower": "1 dBmV",
+                "DownstreamSignalSnr": "38 dB",
+                "GetArrisDeviceStatusResult": "OK",
+            }),
+        )
+        .reply(
+            "GetArrisRegisterInfo",
+            json!({
+                "MacAddress": "AA:BB:CC:01:02:03",
+                "SerialNumber": "123456789",
+                "ModelName": "S33",
+                "GetArrisRegisterInfoResult": "OK",
+            }),
+        )
+        .reply(
+            "GetCustomerStatusStartupSequence",
+            json!({
+                "CustomerConnDSFreq": "567000000",
+                "CustomerConnDSComment": "Locked",
+                "CustomerConnConnectivityStatus": "OK",
+                "CustomerConnConnectivityComment": "Operational",
+                "CustomerConnBootStatus": "OK",
+                "CustomerConnBootComment": "Operational",
+                "CustomerConnConfigurationFileStatus": "OK",
+                "CustomerConnConfigurationFileComment": "",
+                "CustomerConnSecurityStatus": "Enabled",
+                "CustomerConnSecurityComment": "BPI+",
+                "GetCustomerStatusStartupSequenceResult": "OK",
+            }),
+        )
+        .reply(
+            "GetCustomerStatusConnectionInfo",
+            json!({
+                "CustomerConnSystemUpTime": "1 days 02h:03m:04s",
+                "CustomerCurSystemTime": "Sun Dec 31 12:34:56 2023",
+                "CustomerConnNetworkAccess": "Allowed",
+                "GetCustomerStatusConnectionInfoResult": "OK",
+            }),
+        )
+        .reply(
+            "GetCustomerStatusDownstreamChannelInfo",
+            json!({
+                "CustomerConnDownstreamChannel": "1^Locked^QAM256^20^567000000^1^38^12^0^|+|2^Locked^QAM256^21^573000000^2^39^0^0^",
+                "GetCustomerStatusDownstreamChannelInfoResult": "OK",
+            }),
+        )
+        .reply(
+            "GetCustomerStatusUpstreamChannelInfo",
+            json!({
+                "CustomerConnUpstreamChannel": "1^Locked^SC-QAM^1^6400000^16400000^45^",
+                "GetCustomerStatusUpstreamChannelInfoResult": "OK",
+            }),
+        )
+        .reply(
+            "GetCustomerStatusLog",
+            json!({
+                "CustomerStatusLogList": "0^07:08:09^01/01/2024^5^Cable Modem Reboot",
+                "GetCustomerStatusLogResult": "OK",
+            }),
+        )
+        .reply("GetCustomerStatusLogXXX", json!("XXX"))
+}
+
+async fn logged_in(modem: &MockModem) -> SOAPClient {
+    let mut client = SOAPClient::new(modem.serve().await, &ClientOptions::default()).unwrap();
+    client.login("admin", "hunter2").await.unwrap();
+    client
+}
+
+#[tokio::test]
+async fn scrapes_after_login() {
+    let modem = s33();
+    let mut client = logged_in(&modem).await;
+
+    let metrics = Modem::metrics(&mut client).await.unwrap();
+    assert_eq!(metrics.channels.len(), 3);
+    assert_eq!(
+        metrics.uptime.unwrap().as_secs(),
+        86400 + 2 * 3600 + 3 * 60 + 4
+    );
+
+    let logs = Modem::logs(&mut client).await.unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].message, "Cable Modem Reboot");
+    assert_eq!(modem.logins(), 1);
+}
+
+#[tokio::test]
+async fn wrong_password() {
+    let modem = s33();
+    let mut client = SOAPClient::new(modem.serve().await, &ClientOptions::default()).unwrap();
+    assert!(matches!(
+        client.login("admin", "wrong").await,
+        Err(Error::Login(_))
+    ));
+    assert_eq!(modem.logins(), 0);
+}
+
+#[tokio::test]
+async fn holds_off_logging_in_after_a_failure() {
+    let modem = s33();
+    let mut client = SOAPClient::new(modem.serve().await, &ClientOptions::default()).unwrap();
+    assert!(client.login("admin", "wrong").await.is_err());
+    // even with the right password, since the modem would count it towards a lockout
+    assert!(matches!(
+        client.login("admin", "hunter2").await,
+        Err(Error::LoginHeldOff(_))
+    ));
+    assert_eq!(modem.logins(), 0);
+}
+
+#[tokio::test]
+async fn held_off_logins_survive_a_restart() {
+    let state_file = std::env::temp_dir().join(format!(
+        "modem-scraper-login-{}/login.json",
+        std::process::id()
+    ));
+    let options = ClientOptions {
+        login_limits: LoginLimits {
+            state_file: Some(state_file.clone()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let modem = s33();
+    let endpoint = modem.serve().await;
+    let mut client = SOAPClient::new(endpoint.clone(), &options).unwrap();
+    assert!(client.login("admin", "wrong").await.is_err());
+
+    let mut restarted = SOAPClient::new(endpoint, &options).unwrap();
+    assert!(matches!(
+        restarted.login("admin", "hunter2").await,
+        Err(Error::LoginHeldOff(_))
+    ));
+    std::fs::remove_dir_all(state_file.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn huge_backoffs_hold_off_instead_of_overflowing() {
+    let options = ClientOptions {
+        login_limits: LoginLimits {
+            initial_backoff_seconds: u64::MAX,
+            max_backoff_seconds: u64::MAX,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let modem = s33();
+    let mut client = SOAPClient::new(modem.serve().await, &options).unwrap();
+    assert!(client.login("admin", "wrong").await.is_err());
+    assert!(matches!(
+        client.login("admin", "hunter2").await,
+        Err(Error::LoginHeldOff(_))
+    ));
+}
+
+#[tokio::test]
+async fn detects_timestamps_in_seconds() {
+    let modem = s33().timestamps_in_seconds();
+    let mut client = logged_in(&modem).await;
+
+    let metrics = Modem::metrics(&mut client).await.unwrap();
+    assert_eq!(metrics.channels.len(), 3);
+    // and sticks with them
+    Modem::logs(&mut client).await.unwrap();
+}
+
+#[tokio::test]
+async fn timestamps_in_millis_fail_on_newer_firmware() {
+    let modem = s33().timestamps_in_seconds();
+    let options = ClientOptions {
+        hnap_timestamp: HnapTimestamp::Millis,
+        retry_policy: RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut client = SOAPClient::new(modem.serve().await, &options).unwrap();
+    client.login("admin", "hunter2").await.unwrap();
+    assert!(matches!(
+        Modem::metrics(&mut client).await,
+        Err(Error::Modem(_))
+    ));
+}
+
+#[tokio::test]
+async fn unauthenticated_requests_are_refused() {
+    let modem = s33();
+    let mut client = SOAPClient::new(modem.serve().await, &ClientOptions::default()).unwrap();
+    assert!(matches!(
+        Modem::metrics(&mut client).await,
+        Err(Error::Unauthorized)
+    ));
+}
+
+#[tokio::test]
+async fn logs_in_again_when_the_session_expires() {
+    let modem = s33();
+    let mut client = logged_in(&modem).await;
+    modem.expire_session();
+
+    Modem::metrics(&mut client).await.unwrap();
+    assert_eq!(modem.logins(), 2);
+}
+
+#[tokio::test]
+async fn counts_logins_and_times_requests_by_result() {
+    let modem = s33();
+    let mut client = logged_in(&modem).await;
+    modem.expire_session();
+    Modem::metrics(&mut client).await.unwrap();
+
+    let stats = Modem::stats(&client);
+    assert_eq!(stats.logins, 2);
+    assert!(stats.requests.contains_key("Login"));
+    let request = &stats.requests["GetMultipleHNAPs"];
+    assert_eq!(request.result, "OK");
+    assert!(request.response_bytes > 0);
+    assert_eq!(request.results.get("UN-AUTH"), Some(&1));
+    assert!(request.results.contains_key("OK"));
+}
+
+#[tokio::test]
+async fn scrapes_everything_at_once() {
+    let modem = s33()
+        .reply(
+            "GetArrisConfigurationInfo",
+            json!({
+                "ConfigurationFileName": "d11_m_s33_1gig.cfg",
+                "GetArrisConfigurationInfoResult": "OK",
+            }),
+        )
+        .reply(
+            "GetArrisRegisterStatus",
+            json!({
+                "DocsisVersion": "3.1",
+                "GetArrisRegisterStatusResult": "OK",
+            }),
+        );
+    let mut client = logged_in(&modem).await;
+
+    let snapshot = client.scrape().await.unwrap();
+    assert_eq!(
+        snapshot.device_info.serial_number.as_deref(),
+        Some("123456789")
+    );
+    assert_eq!(snapshot.device_info.docsis_version.as_deref(), Some("3.1"));
+    assert_eq!(
+        snapshot.device_info.config_file.as_deref(),
+        Some("d11_m_s33_1gig.cfg")
+    );
+    assert_eq!(snapshot.metrics.channels.len(), 3);
+    assert_eq!(snapshot.logs.len(), 1);
+    assert_eq!(snapshot.time.to_rfc3339(), "2023-12-31T12:34:56+00:00");
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn scrapes_without_async() {
+    use modem_scraper_lib::blocking::BlockingModem;
+
+    // the mock modem still needs a runtime of its own to serve from
+    let server = tokio::runtime::Runtime::new().unwrap();
+    let modem = s33();
+    let endpoint = server.block_on(modem.serve());
+    let client = SOAPClient::new(endpoint, &ClientOptions::default()).unwrap();
+    let mut client = BlockingModem::new(Box::new(client)).unwrap();
+
+    client.login("admin", "hunter2").unwrap();
+    assert_eq!(client.metrics().unwrap().channels.len(), 3);
+    assert_eq!(client.logs().unwrap()[0].message, "Cable Modem Reboot");
+    assert_eq!(client.stats().logins, 1);
+}
+
+#[tokio::test]
+async fn debug_output_leaves_out_the_password() {
+    let modem = s33();
+    let client = logged_in(&modem).await;
+
+    let debug = format!("{:?}", client);
+    assert!(debug.contains("admin"));
+    assert!(!debug.contains("hunter2"));
+}
@@ -0,0 +1,114 @@
+use base64::Engine;
+use modem_scraper_lib::{ClientOptions, Error, RetryPolicy, SOAPClient};
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+/// Self-signed, like a modem's
+const CERTIFICATE: &str = "
+MIIBiDCCAS6gAwIBAgIUNlP0Fyz2aWBR9MBlbMTt5rnB+tIwCgYIKoZIzj0EAwIw
+EDEOMAwGA1UEAwwFbW9kZW0wIBcNMjYxMDE2MTI0MjE4WhgPMjEyNjA5MjIxMjQy
+MThaMBAxDjAMBgNVBAMMBW1vZGVtMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE
+t15+/2066ctsxwrljAPsuGeoiKQ0ZCrXcalk6XDRIZqRU5XYO2chAOhHYwnNMEyz
+HS7vq3UQsKSJgrucEO1vdaNkMGIwHQYDVR0OBBYEFKo1f/KceoFHACE6sZZvVZ0v
+zJUkMB8GA1UdIwQYMBaAFKo1f/KceoFHACE6sZZvVZ0vzJUkMA8GA1UdEwEB/wQF
+MAMBAf8wDwYDVR0RBAgwBocEfwAAATAKBggqhkjOPQQDAgNIADBFAiEA5gRxQGoy
+VKeFZVlYay2cj5x45n1fGmBuFavkw1o8fQoCICqtZ1Ua6nNlIaBYe9wvDTnqg2Fa
+D2lapsMs2kB8u+0V";
+const PRIVATE_KEY: &str = "
+MIGHAgEAMBMGByqGSM49AgEGCCqGSM49AwEHBG0wawIBAQQgVE32e4coM50u6sym
+8YpRlecMB4fzITls+l/x6A+LYJOhRANCAAS3Xn7/bTrpy2zHCuWMA+y4Z6iIpDRk
+KtdxqWTpcNEhmpFTldg7ZyEA6EdjCc0wTLMdLu+rdRCwpImCu5wQ7W91";
+/// hex SHA-256 of [CERTIFICATE]
+const FINGERPRINT: &str = "1eaf3f4f773460fb78b21b990b58953946451f6234f21655e478848e88fe68e5";
+
+fn der(base64: &str) -> Vec<u8> {
+    base64::engine::general_purpose::STANDARD
+        .decode(base64.split_whitespace().collect::<String>())
+        .unwrap()
+}
+
+/// Serves [CERTIFICATE] and keeps whatever the client sends once the handshake's done. Every
+/// request gets a 500, the interesting part is whether it was sent at all.
+async fn serve_tls(received: Arc<Mutex<Vec<u8>>>) -> String {
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![Certificate(der(CERTIFICATE))],
+            PrivateKey(der(PRIVATE_KEY)),
+        )
+        .unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let Ok(mut stream) = acceptor.accept(stream).await else {
+                continue;
+            };
+            let mut buffer = [0; 4096];
+            let read = stream.read(&mut buffer).await.unwrap_or_default();
+            received.lock().unwrap().extend_from_slice(&buffer[..read]);
+            let _ = stream
+                .write_all(b"HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n")
+                .await;
+        }
+    });
+    format!("https://{}/HNAP1/", address)
+}
+
+fn pinned(pin: &str) -> ClientOptions {
+    ClientOptions {
+        pinned_certificate: Some(pin.to_owned()),
+        retry_policy: RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn nothing_is_sent_to_the_wrong_certificate() {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let endpoint = serve_tls(received.clone()).await;
+    let mut client = SOAPClient::new(endpoint, &pinned(&"00".repeat(32))).unwrap();
+
+    assert!(matches!(
+        client.login("admin", "hunter2").await,
+        Err(Error::Http(_))
+    ));
+    assert!(received.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn the_pinned_certificate_gets_through() {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let endpoint = serve_tls(received.clone()).await;
+    // colons and upper case, the way browsers show it
+    let pin = FINGERPRINT
+        .to_uppercase()
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| std::str::from_utf8(pair).unwrap())
+        .collect::<Vec<_>>()
+        .join(":");
+    let mut client = SOAPClient::new(endpoint, &pinned(&pin)).unwrap();
+
+    assert!(client.login("admin", "hunter2").await.is_err());
+    assert!(received.lock().unwrap().starts_with(b"POST /HNAP1/"));
+}
+
+#[test]
+fn pinning_needs_https() {
+    assert!(matches!(
+        SOAPClient::new(
+            "http://192.168.100.1/HNAP1/".to_owned(),
+            &pinned(FINGERPRINT)
+        ),
+        Err(Error::PinMismatch)
+    ));
+}
@@ -0,0 +1,35 @@
+use modem_scraper_lib::replay::{replay_file, Replayed};
+use serde_json::json;
+
+#[test]
+fn replays_saved_logs() {
+    let dir = std::env::temp_dir().join(format!("modem-scraper-replay-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("20240101T000000.000Z-192.168.100.1-GetMultipleHNAPs.json");
+    let reply = json!({
+        "GetMultipleHNAPsResponse": {
+            "GetCustomerStatusLogResponse": {
+                "CustomerStatusLogList": "0^07:08:09^01/01/2024^5^Cable Modem Reboot",
+                "GetCustomerStatusLogResult": "OK",
+            },
+            "GetMultipleHNAPsResult": "OK",
+        }
+    });
+    std::fs::write(&path, reply.to_string()).unwrap();
+
+    let saved = replay_file(&path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(saved.host, "192.168.100.1");
+    match saved.replayed {
+        Replayed::Logs(logs) => {
+            assert_eq!(logs.len(), 1);
+            assert_eq!(logs[0].message, "Cable Modem Reboot");
+        }
+        other => panic!("expected logs, got {:?}", other),
+    }
+}
+
+#[test]
+fn replay_needs_a_saved_file_name() {
+    assert!(replay_file(std::path::Path::new("reply.json")).is_err());
+}
@@ -0,0 +1,36 @@
+use modem_scraper_lib::payloads::StartupStatus;
+
+#[test]
+fn startup_statuses() {
+    for (s, status) in [
+        ("OK", StartupStatus::Complete),
+        ("Locked", StartupStatus::Complete),
+        ("Operational", StartupStatus::Complete),
+        ("Disabled", StartupStatus::Complete),
+        ("", StartupStatus::NotStarted),
+        ("Not Started", StartupStatus::NotStarted),
+        ("In Progress", StartupStatus::InProgress),
+        ("Ranging", StartupStatus::InProgress),
+        ("Failed", StartupStatus::Error),
+        ("Rejected", StartupStatus::Error),
+    ] {
+        assert_eq!(StartupStatus::from(s), status, "{}", s);
+    }
+    assert_eq!(StartupStatus::Complete.as_number(), 2);
+}
+
+#[test]
+fn startup_statuses_round_trip() {
+    for status in [
+        StartupStatus::NotStarted,
+        StartupStatus::InProgress,
+        StartupStatus::Complete,
+        StartupStatus::Error,
+    ] {
+        let json = serde_json::to_string(&status).unwrap();
+        assert_eq!(
+            serde_json::from_str::<StartupStatus>(&json).unwrap(),
+            status
+        );
+    }
+}
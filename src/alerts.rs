@@ -0,0 +1,309 @@
+use crate::scraped::ScrapedMetrics;
+use chrono::{DateTime, Utc};
+use log::Level;
+use modem_scraper_lib::payloads::{Channel, LogEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// `alerts:` in the config. Every check is off unless it's set.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AlertSettings {
+    /// dB, for locked downstream channels
+    pub snr_below_db: Option<f64>,
+    /// per downstream channel, since the previous scrape
+    pub uncorrectables_per_minute_above: Option<f64>,
+    /// dBmV, for locked channels
+    pub downstream_power_range_dbmv: Option<PowerRange>,
+    pub upstream_power_range_dbmv: Option<PowerRange>,
+    #[serde(default)]
+    pub channel_unlocked: bool,
+    /// the modem's uptime went down
+    #[serde(default)]
+    pub rebooted: bool,
+    /// how long before the same alert (same check, same channel) can fire again
+    #[serde(default = "default_cooldown_seconds")]
+    pub cooldown_seconds: u64,
+}
+
+fn default_cooldown_seconds() -> u64 {
+    3600
+}
+
+/// Alerts outside this, either way
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct PowerRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    SnrLow,
+    Uncorrectables,
+    PowerOutOfRange,
+    ChannelUnlocked,
+    Rebooted,
+}
+
+impl AlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertKind::SnrLow => "snr_low",
+            AlertKind::Uncorrectables => "uncorrectables",
+            AlertKind::PowerOutOfRange => "power_out_of_range",
+            AlertKind::ChannelUnlocked => "channel_unlocked",
+            AlertKind::Rebooted => "rebooted",
+        }
+    }
+
+    /// Losing a channel or the whole modem is critical, a degraded channel is a warning
+    pub fn severity(&self) -> Severity {
+        match self {
+            AlertKind::ChannelUnlocked | AlertKind::Rebooted => Severity::Critical,
+            AlertKind::SnrLow | AlertKind::Uncorrectables | AlertKind::PowerOutOfRange => {
+                Severity::Warning
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+/// Something about a modem that someone should hear about
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub severity: Severity,
+    pub time: DateTime<Utc>,
+    /// the modem's tags: hostname, model, serial number, firmware...
+    pub tags: HashMap<String, String>,
+    /// e.g. `downstream 12`, for alerts about a single channel
+    pub channel: Option<String>,
+    pub value: Option<f64>,
+    pub threshold: Option<f64>,
+    /// one line, for humans
+    pub message: String,
+}
+
+impl Alert {
+    /// Everything about the alert as text, for templates: the modem's tags, then `kind`,
+    /// `severity`, `time`, `channel`, `value`, `threshold` and `message`. Anything the alert
+    /// doesn't have is empty.
+    pub fn fields(&self) -> BTreeMap<String, String> {
+        let optional =
+            |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        let mut fields: BTreeMap<String, String> = self.tags.clone().into_iter().collect();
+        fields.extend([
+            ("kind".to_owned(), self.kind.as_str().to_owned()),
+            ("severity".to_owned(), self.severity.as_str().to_owned()),
+            ("time".to_owned(), self.time.to_rfc3339()),
+            (
+                "channel".to_owned(),
+                self.channel.clone().unwrap_or_default(),
+            ),
+            ("value".to_owned(), optional(self.value)),
+            ("threshold".to_owned(), optional(self.threshold)),
+            ("message".to_owned(), self.message.clone()),
+        ]);
+        fields
+    }
+
+    /// To go out with the modem's own logs
+    pub fn log_entry(&self) -> LogEntry {
+        LogEntry {
+            timestamp: self.time,
+            level: match self.severity {
+                Severity::Warning => Level::Warn,
+                Severity::Critical => Level::Error,
+            },
+            message: format!("Alert: {}", self.message),
+            priority: None,
+        }
+    }
+}
+
+impl Display for Alert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hostname = self.tags.get("hostname").map(String::as_str);
+        write!(f, "{}: {}", hostname.unwrap_or("modem"), self.message)
+    }
+}
+
+/// Checks every scrape against [AlertSettings], and holds back an alert that already fired within
+/// the cool-down. One per modem.
+#[derive(Debug)]
+pub struct AlertEngine {
+    /// `None` when there's no `alerts:`, so nothing's checked
+    settings: Option<AlertSettings>,
+    /// by kind and channel
+    last_fired: HashMap<(AlertKind, Option<String>), Instant>,
+}
+
+fn channel_name(channel: &Channel) -> String {
+    match channel {
+        Channel::Downstream(c) => format!("downstream {}", c.channel_id),
+        Channel::Upstream(c) => format!("upstream {}", c.channel_id),
+        Channel::OfdmaUpstream(c) => format!("ofdma upstream {}", c.channel_id),
+    }
+}
+
+impl AlertEngine {
+    pub fn new(settings: Option<AlertSettings>) -> AlertEngine {
+        AlertEngine {
+            settings,
+            last_fired: HashMap::new(),
+        }
+    }
+
+    /// After a reload. What's already fired stays held back.
+    pub fn update_settings(&mut self, settings: Option<AlertSettings>) {
+        self.settings = settings;
+    }
+
+    /// Whatever's wrong in `scraped` that hasn't been alerted on within the cool-down. Needs the
+    /// codeword deltas and reboot filled in.
+    pub fn evaluate(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Vec<Alert> {
+        let Some(settings) = &self.settings else {
+            return Vec::new();
+        };
+        let time = scraped.metrics.time.unwrap_or_else(Utc::now);
+        let alert = |kind: AlertKind,
+                     channel: Option<String>,
+                     value: Option<f64>,
+                     threshold: Option<f64>,
+                     message: String| Alert {
+            kind,
+            severity: kind.severity(),
+            time,
+            tags: tags.clone(),
+            channel,
+            value,
+            threshold,
+            message,
+        };
+
+        let mut alerts = Vec::new();
+        if let (true, Some(rebooted_after)) = (settings.rebooted, scraped.rebooted_after) {
+            alerts.push(alert(
+                AlertKind::Rebooted,
+                None,
+                Some(rebooted_after.as_secs_f64()),
+                None,
+                format!("rebooted after {}s of uptime", rebooted_after.as_secs()),
+            ));
+        }
+        for channel in &scraped.metrics.channels {
+            let name = channel_name(channel);
+            let (lock_status, power, power_range) = match channel {
+                Channel::Downstream(c) => {
+                    (c.lock_status, c.power, settings.downstream_power_range_dbmv)
+                }
+                Channel::Upstream(c) => {
+                    (c.lock_status, c.power, settings.upstream_power_range_dbmv)
+                }
+                Channel::OfdmaUpstream(c) => {
+                    (c.lock_status, c.power, settings.upstream_power_range_dbmv)
+                }
+            };
+            if !lock_status {
+                if settings.channel_unlocked {
+                    alerts.push(alert(
+                        AlertKind::ChannelUnlocked,
+                        Some(name.clone()),
+                        None,
+                        None,
+                        format!("{} isn't locked", name),
+                    ));
+                }
+                // whatever else it reports is meaningless
+                continue;
+            }
+            if let Some(PowerRange { min, max }) = power_range {
+                if power < min || power > max {
+                    alerts.push(alert(
+                        AlertKind::PowerOutOfRange,
+                        Some(name.clone()),
+                        Some(power),
+                        Some(if power < min { min } else { max }),
+                        format!(
+                            "{} power is {:.1} dBmV, outside {} to {}",
+                            name, power, min, max
+                        ),
+                    ));
+                }
+            }
+            let Channel::Downstream(c) = channel else {
+                continue;
+            };
+            if let Some(threshold) = settings.snr_below_db {
+                if c.snr < threshold {
+                    alerts.push(alert(
+                        AlertKind::SnrLow,
+                        Some(name.clone()),
+                        Some(c.snr),
+                        Some(threshold),
+                        format!("{} SNR is {:.1} dB, below {}", name, c.snr, threshold),
+                    ));
+                }
+            }
+            let delta = scraped.codeword_deltas.get(&c.channel_id);
+            if let (Some(threshold), Some(delta)) =
+                (settings.uncorrectables_per_minute_above, delta)
+            {
+                let per_minute = if delta.seconds > 0.0 {
+                    delta.uncorrectables as f64 / delta.seconds * 60.0
+                } else {
+                    0.0
+                };
+                if per_minute > threshold {
+                    alerts.push(alert(
+                        AlertKind::Uncorrectables,
+                        Some(name.clone()),
+                        Some(per_minute),
+                        Some(threshold),
+                        format!(
+                            "{} has {:.0} uncorrectables a minute, above {}",
+                            name, per_minute, threshold
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let cooldown = Duration::from_secs(settings.cooldown_seconds);
+        let now = Instant::now();
+        alerts.retain(|alert| {
+            let key = (alert.kind, alert.channel.clone());
+            match self.last_fired.get(&key) {
+                Some(last_fired) if now.duration_since(*last_fired) < cooldown => false,
+                _ => {
+                    self.last_fired.insert(key, now);
+                    true
+                }
+            }
+        });
+        alerts
+    }
+}
@@ -0,0 +1,108 @@
+use crate::scraped::ScrapedMetrics;
+use chrono::Utc;
+use log::Level;
+use modem_scraper_lib::payloads::{Channel, LogEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::{info, warn};
+
+/// Channels joining or leaving the bonding group in one direction, going by the channel IDs the
+/// modem lists
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BondingChanges {
+    /// since the previous scrape
+    pub added: Vec<u8>,
+    pub removed: Vec<u8>,
+    /// since the scraper started
+    pub added_total: u64,
+    pub removed_total: u64,
+}
+
+/// Remembers which channels the modem listed last scrape, to notice them coming and going.
+/// Channels usually leave the bonding group because of trouble on the plant upstream of the modem.
+/// One per modem.
+#[derive(Debug, Default)]
+pub struct BondingTracker {
+    /// channel ids by direction, from the previous scrape
+    previous: Option<BTreeMap<&'static str, BTreeSet<u8>>>,
+    /// (added, removed) since the scraper started, by direction
+    totals: BTreeMap<&'static str, (u64, u64)>,
+}
+
+impl BondingTracker {
+    /// Fills in `scraped.bonding` against the last scrape, then remembers this one, and returns a
+    /// log entry for each direction that changed. The first scrape gets nothing.
+    pub fn update(&mut self, scraped: &mut ScrapedMetrics) -> Vec<LogEntry> {
+        let mut current: BTreeMap<&'static str, BTreeSet<u8>> = BTreeMap::from([
+            ("downstream", BTreeSet::new()),
+            ("upstream", BTreeSet::new()),
+        ]);
+        for channel in &scraped.metrics.channels {
+            let (direction, channel_id) = match channel {
+                Channel::Downstream(c) => ("downstream", c.channel_id),
+                Channel::Upstream(c) => ("upstream", c.channel_id),
+                Channel::OfdmaUpstream(c) => ("upstream", c.channel_id),
+            };
+            current.entry(direction).or_default().insert(channel_id);
+        }
+        let Some(previous) = self.previous.replace(current.clone()) else {
+            return Vec::new();
+        };
+
+        let mut log_entries = Vec::new();
+        for (direction, channel_ids) in &current {
+            let previous_ids = previous.get(direction).cloned().unwrap_or_default();
+            let added: Vec<u8> = channel_ids.difference(&previous_ids).copied().collect();
+            let removed: Vec<u8> = previous_ids.difference(channel_ids).copied().collect();
+            let (added_total, removed_total) = self.totals.entry(direction).or_default();
+            *added_total += added.len() as u64;
+            *removed_total += removed.len() as u64;
+
+            if !added.is_empty() || !removed.is_empty() {
+                let message = bonding_message(direction, &added, &removed);
+                let level = if removed.is_empty() {
+                    info!("{}", message);
+                    Level::Info
+                } else {
+                    warn!("{}", message);
+                    Level::Warn
+                };
+                log_entries.push(LogEntry {
+                    timestamp: scraped.metrics.time.unwrap_or_else(Utc::now),
+                    level,
+                    message,
+                    priority: None,
+                });
+            }
+            scraped.bonding.insert(
+                direction.to_string(),
+                BondingChanges {
+                    added,
+                    removed,
+                    added_total: *added_total,
+                    removed_total: *removed_total,
+                },
+            );
+        }
+        log_entries
+    }
+}
+
+/// e.g. `Downstream bonding changed: lost channels 5, 6; gained channel 33`
+fn bonding_message(direction: &str, added: &[u8], removed: &[u8]) -> String {
+    let describe = |verb: &str, channel_ids: &[u8]| {
+        let channel_ids: Vec<String> = channel_ids.iter().map(u8::to_string).collect();
+        let plural = if channel_ids.len() == 1 { "" } else { "s" };
+        format!("{} channel{} {}", verb, plural, channel_ids.join(", "))
+    };
+    let mut changes = Vec::new();
+    if !removed.is_empty() {
+        changes.push(describe("lost", removed));
+    }
+    if !added.is_empty() {
+        changes.push(describe("gained", added));
+    }
+    let mut direction = direction.to_owned();
+    direction[..1].make_ascii_uppercase();
+    format!("{} bonding changed: {}", direction, changes.join("; "))
+}
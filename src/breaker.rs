@@ -0,0 +1,95 @@
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// `circuit_breaker:` in a modem's config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerSettings {
+    /// consecutive failed scrapes before the modem is left alone, 0 to never stop polling
+    pub failures: u32,
+    /// how long it's left alone before logging in and trying again
+    pub cool_down_seconds: u64,
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        CircuitBreakerSettings {
+            failures: 5,
+            cool_down_seconds: 300,
+        }
+    }
+}
+
+/// Stops polling a modem that keeps failing, since some get worse when they're polled while they're
+/// already struggling. One per modem.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    settings: CircuitBreakerSettings,
+    consecutive_failures: u32,
+    /// set while the modem is being left alone, and after that until a scrape works again
+    open_until: Option<Instant>,
+    /// how many times it's opened since startup
+    trips: u64,
+}
+
+impl CircuitBreaker {
+    pub fn new(settings: CircuitBreakerSettings) -> CircuitBreaker {
+        CircuitBreaker {
+            settings,
+            consecutive_failures: 0,
+            open_until: None,
+            trips: 0,
+        }
+    }
+
+    /// From a reloaded config. A cool-down that's already started runs its course.
+    pub fn update_settings(&mut self, settings: CircuitBreakerSettings) {
+        self.settings = settings;
+    }
+
+    /// Whether the modem is being left alone
+    pub fn is_open(&self) -> bool {
+        self.open_until
+            .is_some_and(|open_until| Instant::now() < open_until)
+    }
+
+    /// Whether the cool-down is over but nothing has worked since, so the session is likely gone
+    pub fn cooled_down(&self) -> bool {
+        self.open_until
+            .is_some_and(|open_until| Instant::now() >= open_until)
+    }
+
+    /// How much longer the modem is being left alone for
+    pub fn remaining(&self) -> Option<Duration> {
+        self.open_until
+            .map(|open_until| open_until.saturating_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    pub fn trips(&self) -> u64 {
+        self.trips
+    }
+
+    pub fn succeeded(&mut self) {
+        self.consecutive_failures = 0;
+        self.open_until = None;
+    }
+
+    /// Opens the circuit once there have been enough failures in a row. Failing again right after
+    /// a cool-down opens it again straight away.
+    pub fn failed(&mut self, device_address: &str) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.settings.failures == 0 || self.consecutive_failures < self.settings.failures {
+            return;
+        }
+        let cool_down = Duration::from_secs(self.settings.cool_down_seconds);
+        warn!(
+            "{} failed {} times in a row, leaving it alone for {:?}",
+            device_address, self.consecutive_failures, cool_down
+        );
+        self.open_until = Some(Instant::now() + cool_down);
+        self.trips += 1;
+    }
+}
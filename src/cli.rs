@@ -0,0 +1,124 @@
+//! Command line arguments. Anything set here wins over `config.yml` and the environment.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use config::builder::DefaultState;
+use config::{Config, ConfigBuilder, ConfigError};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Parser)]
+#[command(version, about = "Scrapes cable modem stats and logs")]
+pub struct Cli {
+    /// the config file. yml, yaml, toml and json all work.
+    #[arg(long, short, global = true, default_value = "config.yml")]
+    pub config: String,
+    #[command(flatten)]
+    pub overrides: Overrides,
+    /// `run` if left out
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// The config file, then the environment, then [Overrides]
+    pub fn load_config(&self) -> Result<Config, ConfigError> {
+        self.overrides
+            .apply(
+                Config::builder()
+                    .add_source(config::File::with_name(&self.config))
+                    .add_source(config::Environment::with_prefix(
+                        &env!("CARGO_PKG_NAME").replace('-', "_").to_uppercase(),
+                    )),
+            )?
+            .build()
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Scrape every modem on `scrape_interval_seconds` forever
+    Run,
+    /// Scrape every modem once, print the results and exit
+    ScrapeOnce {
+        #[arg(long, value_enum, default_value_t = ScrapeFormat::Table)]
+        format: ScrapeFormat,
+    },
+    /// Log into every modem and report whether that worked
+    LoginTest,
+    /// Scrape every modem once, only saving the raw replies to `dir`
+    DumpRaw { dir: PathBuf },
+    /// Reboot every modem (HNAP models only)
+    Reboot,
+    /// Load the config and report what's wrong with it
+    ValidateConfig,
+    /// Print the latest channels and logs from the SQLite history
+    Query {
+        /// how far back to print logs
+        #[arg(default_value_t = 24)]
+        hours: i64,
+    },
+    /// Send replies saved with `--dump-raw` to the outputs instead of scraping
+    Replay { dir: PathBuf },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScrapeFormat {
+    /// channel tables and the latest log lines
+    Table,
+    /// JSON lines, like `--output stdout`
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// JSON lines on stdout, e.g. to pipe into vector or `jq`
+    Stdout,
+}
+
+/// Config keys that can be set from the command line. The `device_*` ones only apply when there's
+/// no `modems:` list.
+#[derive(Debug, Clone, Default, Args)]
+pub struct Overrides {
+    /// e.g. `https://192.168.100.1/HNAP1/`
+    #[arg(long, global = true)]
+    pub device_address: Option<String>,
+    /// the modem's admin username
+    #[arg(long, global = true)]
+    pub device_username: Option<String>,
+    /// the modem's admin password
+    #[arg(long, global = true)]
+    pub device_password: Option<String>,
+    /// `s33`, `sb8200`, `mb8600` or `cm1000`; detected if left out
+    #[arg(long, global = true)]
+    pub model: Option<String>,
+    /// trust the modem's self-signed certificate
+    #[arg(long, global = true)]
+    pub accept_invalid_certs: bool,
+    /// how long to wait between scrapes
+    #[arg(long, global = true)]
+    pub scrape_interval_seconds: Option<u64>,
+    /// replaces the outputs in the config
+    #[arg(long, global = true, value_enum)]
+    pub output: Option<Output>,
+    /// write every HNAP reply to this directory before parsing it
+    #[arg(long, global = true)]
+    pub dump_raw: Option<PathBuf>,
+}
+
+impl Overrides {
+    /// Sets the keys that were given on `builder`, over whatever the file and environment say
+    pub fn apply(
+        &self,
+        builder: ConfigBuilder<DefaultState>,
+    ) -> Result<ConfigBuilder<DefaultState>, ConfigError> {
+        builder
+            .set_override_option("device_address", self.device_address.clone())?
+            .set_override_option("device_username", self.device_username.clone())?
+            .set_override_option("device_password", self.device_password.clone())?
+            .set_override_option("model", self.model.clone())?
+            .set_override_option(
+                "accept_invalid_certs",
+                self.accept_invalid_certs.then_some(true),
+            )?
+            .set_override_option("scrape_interval_seconds", self.scrape_interval_seconds)
+    }
+}
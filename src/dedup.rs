@@ -0,0 +1,327 @@
+use chrono::{DateTime, TimeZone, Utc};
+use modem_scraper_lib::payloads::LogEntry;
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::Duration;
+
+/// How many entries to remember at least, when the capacity is sized from the modem's log
+pub const MIN_CAPACITY: usize = 64;
+
+/// What a log entry is remembered by: its timestamp, rounded down to the tolerance, and a hash of
+/// its level and message
+pub type EntryKey = (DateTime<Utc>, u64);
+
+/// How [Seen] forgets entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eviction {
+    /// the first inserted first, once there are more than this many
+    Count(usize),
+    /// once the entry's timestamp is this far in the past, however many there are
+    Age(Duration),
+}
+
+/// Log entries that have already been sent somewhere, so they aren't sent again. Iterates in the
+/// order they were inserted, and serializes as a list in that order.
+#[derive(Debug, Clone)]
+pub struct Seen {
+    keys: HashSet<EntryKey>,
+    order: VecDeque<EntryKey>,
+    eviction: Eviction,
+    /// how far apart two entries' timestamps can be and still be the same entry
+    tolerance: Duration,
+}
+
+impl Default for Seen {
+    fn default() -> Self {
+        Seen::new(Eviction::Count(MIN_CAPACITY))
+    }
+}
+
+impl Seen {
+    pub fn new(eviction: Eviction) -> Seen {
+        Seen {
+            keys: HashSet::new(),
+            order: VecDeque::new(),
+            eviction,
+            tolerance: Duration::ZERO,
+        }
+    }
+
+    /// The modem sometimes sends an entry again with its timestamp a second or so off. Whole
+    /// seconds only, anything less is the same as none.
+    pub fn with_tolerance(mut self, tolerance: Duration) -> Seen {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Adds the keys in `path`, as written by [Seen::save]. Nothing happens if it doesn't exist yet.
+    pub fn load(&mut self, path: &Path) -> std::io::Result<()> {
+        let keys: Vec<EntryKey> = match std::fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        self.extend(keys);
+        self.forget_expired();
+        Ok(())
+    }
+
+    /// Written to a temporary file and renamed over `path`, so it's never half written
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        let temporary = path.with_extension("json.tmp");
+        std::fs::write(&temporary, serde_json::to_vec(self)?)?;
+        std::fs::rename(&temporary, path)
+    }
+
+    pub fn eviction(&self) -> Eviction {
+        self.eviction
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// `None` when evicting by age
+    pub fn capacity(&self) -> Option<usize> {
+        match self.eviction {
+            Eviction::Count(capacity) => Some(capacity),
+            Eviction::Age(_) => None,
+        }
+    }
+
+    /// Oldest inserted first
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, EntryKey> {
+        self.order.iter()
+    }
+
+    pub fn contains(&self, key: &EntryKey) -> bool {
+        self.keys.contains(key)
+    }
+
+    pub fn key(&self, log_entry: &LogEntry) -> EntryKey {
+        let mut hasher = Sha256::new();
+        hasher.update(log_entry.level.as_str());
+        hasher.update([0]);
+        hasher.update(&log_entry.message);
+        let hash = hasher.finalize();
+        let mut truncated = [0; 8];
+        truncated.copy_from_slice(&hash[..8]);
+
+        let tolerance = self.tolerance.as_secs() as i64;
+        let timestamp = match tolerance {
+            0 => log_entry.timestamp,
+            _ => {
+                let seconds = log_entry.timestamp.timestamp();
+                Utc.timestamp_opt(seconds - seconds.rem_euclid(tolerance), 0)
+                    .single()
+                    .unwrap_or(log_entry.timestamp)
+            }
+        };
+        (timestamp, u64::from_be_bytes(truncated))
+    }
+
+    /// Whether `log_entry`, or one within the tolerance of it, has been inserted. Rounding puts
+    /// timestamps either side of a boundary in different buckets, so the neighbouring ones are
+    /// checked too.
+    pub fn contains_entry(&self, log_entry: &LogEntry) -> bool {
+        let (timestamp, hash) = self.key(log_entry);
+        if self.tolerance.as_secs() == 0 {
+            return self.contains(&(timestamp, hash));
+        }
+        let tolerance = chrono::Duration::seconds(self.tolerance.as_secs() as i64);
+        [timestamp - tolerance, timestamp, timestamp + tolerance]
+            .into_iter()
+            .any(|timestamp| self.contains(&(timestamp, hash)))
+    }
+
+    /// Entries from before this are forgotten, and shouldn't be sent
+    fn cutoff(&self) -> Option<DateTime<Utc>> {
+        match self.eviction {
+            Eviction::Count(_) => None,
+            Eviction::Age(ttl) => chrono::Duration::from_std(ttl)
+                .ok()
+                .and_then(|ttl| Utc::now().checked_sub_signed(ttl)),
+        }
+    }
+
+    pub fn is_expired(&self, key: &EntryKey) -> bool {
+        self.cutoff().is_some_and(|cutoff| key.0 < cutoff)
+    }
+
+    /// Raises the capacity to `capacity` if it's lower. Only does anything when evicting by count.
+    pub fn grow(&mut self, capacity: usize) {
+        if let Eviction::Count(current) = &mut self.eviction {
+            *current = capacity.max(*current);
+        }
+    }
+
+    /// Only does anything when evicting by age
+    pub fn forget_expired(&mut self) {
+        let Some(cutoff) = self.cutoff() else {
+            return;
+        };
+        let keys = &mut self.keys;
+        self.order.retain(|key| {
+            let keep = key.0 >= cutoff;
+            if !keep {
+                keys.remove(key);
+            }
+            keep
+        });
+    }
+
+    /// Returns whatever was evicted to make room, which is nothing until it's full
+    pub fn insert(&mut self, key: EntryKey) -> Option<EntryKey> {
+        self.insert_many(std::iter::once(key)).pop()
+    }
+
+    /// Inserts every key, then returns everything that was evicted to make room for them, oldest
+    /// first. A batch bigger than the capacity evicts some of itself.
+    pub fn insert_many(&mut self, keys: impl IntoIterator<Item = EntryKey>) -> Vec<EntryKey> {
+        for key in keys {
+            if self.keys.insert(key) {
+                self.order.push_back(key);
+            }
+        }
+        self.evict()
+    }
+
+    /// [Seen::insert_many] with each entry's [Seen::key]
+    pub fn insert_keyed<'a>(
+        &mut self,
+        log_entries: impl IntoIterator<Item = &'a LogEntry>,
+    ) -> Vec<EntryKey> {
+        let keys: Vec<EntryKey> = log_entries
+            .into_iter()
+            .map(|log_entry| self.key(log_entry))
+            .collect();
+        self.insert_many(keys)
+    }
+
+    /// Down to exactly the capacity, when evicting by count
+    fn evict(&mut self) -> Vec<EntryKey> {
+        let Eviction::Count(capacity) = self.eviction else {
+            return Vec::new();
+        };
+        let excess = self.order.len().saturating_sub(capacity);
+        let evicted: Vec<EntryKey> = self.order.drain(..excess).collect();
+        for key in &evicted {
+            self.keys.remove(key);
+        }
+        evicted
+    }
+}
+
+impl Extend<EntryKey> for Seen {
+    fn extend<I: IntoIterator<Item = EntryKey>>(&mut self, iter: I) {
+        self.insert_many(iter);
+    }
+}
+
+/// Evicts by count, with room for everything
+impl FromIterator<EntryKey> for Seen {
+    fn from_iter<I: IntoIterator<Item = EntryKey>>(iter: I) -> Self {
+        let keys: Vec<EntryKey> = iter.into_iter().collect();
+        let mut seen = Seen::new(Eviction::Count(keys.len().max(MIN_CAPACITY)));
+        seen.extend(keys);
+        seen
+    }
+}
+
+impl IntoIterator for Seen {
+    type Item = EntryKey;
+    type IntoIter = std::collections::vec_deque::IntoIter<EntryKey>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.order.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Seen {
+    type Item = &'a EntryKey;
+    type IntoIter = std::collections::vec_deque::Iter<'a, EntryKey>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Serialize for Seen {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(n: i64) -> EntryKey {
+        (Utc.timestamp_opt(n, 0).unwrap(), n as u64)
+    }
+
+    #[test]
+    fn keys_round_down_to_the_tolerance() {
+        let mut seen = Seen::default().with_tolerance(Duration::from_secs(2));
+        let at = |seconds: i64| LogEntry {
+            timestamp: Utc.timestamp_opt(seconds, 500_000_000).unwrap(),
+            level: log::Level::Warn,
+            message: "T3 time-out".to_owned(),
+            priority: None,
+        };
+        let timestamp = |seconds: i64| seen.key(&at(seconds)).0.timestamp();
+        assert_eq!((timestamp(10), timestamp(11), timestamp(12)), (10, 10, 12));
+        assert_eq!(seen.key(&at(10)).1, seen.key(&at(12)).1);
+        seen.insert(seen.key(&at(11)));
+        assert!(seen.contains_entry(&at(12)));
+        assert!(!seen.contains_entry(&at(15)));
+
+        // less than a second is the same as none
+        let seen = Seen::default().with_tolerance(Duration::from_millis(900));
+        assert_eq!(seen.key(&at(11)).0, at(11).timestamp);
+    }
+
+    #[test]
+    fn full_evicts_nothing() {
+        let mut seen = Seen::new(Eviction::Count(3));
+        for n in 0..3 {
+            assert_eq!(seen.insert(key(n)), None);
+        }
+        assert_eq!(seen.len(), 3);
+    }
+
+    #[test]
+    fn one_past_full_evicts_the_oldest() {
+        let mut seen = Seen::new(Eviction::Count(3));
+        seen.insert_many((0..3).map(key));
+        assert_eq!(seen.insert(key(3)), Some(key(0)));
+        assert_eq!(
+            seen.iter().copied().collect::<Vec<_>>(),
+            vec![key(1), key(2), key(3)]
+        );
+    }
+
+    #[test]
+    fn big_batches_evict_their_oldest_in_order() {
+        let mut seen = Seen::new(Eviction::Count(3));
+        seen.insert(key(0));
+        assert_eq!(
+            seen.insert_many((1..6).map(key)),
+            vec![key(0), key(1), key(2)]
+        );
+        assert_eq!(
+            seen.iter().copied().collect::<Vec<_>>(),
+            vec![key(3), key(4), key(5)]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use crate::scraped::ScrapedMetrics;
+use modem_scraper_lib::payloads::{Channel, CodewordDelta};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Remembers each downstream channel's codeword totals so the next scrape can report what changed
+/// in between. One per modem.
+#[derive(Debug, Default)]
+pub struct CodewordTracker {
+    /// channel id to (corrected, uncorrectables)
+    previous: HashMap<u8, (u64, u64)>,
+    previous_at: Option<Instant>,
+    previous_uptime: Option<Duration>,
+}
+
+impl CodewordTracker {
+    /// Fills in `scraped.codeword_deltas` against the last scrape, then remembers this one. The
+    /// first scrape, and channels that weren't around last time, get nothing.
+    pub fn update(&mut self, scraped: &mut ScrapedMetrics) {
+        let now = Instant::now();
+        // totals start over when the modem reboots, even if they've already caught back up
+        let rebooted = matches!(
+            (self.previous_uptime, scraped.metrics.uptime),
+            (Some(previous), Some(current)) if current < previous
+        );
+
+        let current: HashMap<u8, (u64, u64)> = scraped
+            .metrics
+            .channels
+            .iter()
+            .filter_map(|channel| match channel {
+                Channel::Downstream(c) => Some((c.channel_id, (c.corrected, c.uncorrectables))),
+                _ => None,
+            })
+            .collect();
+
+        if let Some(previous_at) = self.previous_at {
+            let seconds = now.duration_since(previous_at).as_secs_f64();
+            // a total going backwards means it was reset, so all of it is new
+            let delta = |current: u64, previous: u64| {
+                if rebooted || current < previous {
+                    current
+                } else {
+                    current - previous
+                }
+            };
+            for (channel_id, (corrected, uncorrectables)) in &current {
+                if let Some((previous_corrected, previous_uncorrectables)) =
+                    self.previous.get(channel_id)
+                {
+                    scraped.codeword_deltas.insert(
+                        *channel_id,
+                        CodewordDelta {
+                            corrected: delta(*corrected, *previous_corrected),
+                            uncorrectables: delta(*uncorrectables, *previous_uncorrectables),
+                            seconds,
+                        },
+                    );
+                }
+            }
+        }
+
+        self.previous = current;
+        self.previous_at = Some(now);
+        self.previous_uptime = scraped.metrics.uptime;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::metrics_to_points;
+    use modem_scraper_lib::payloads::{DownstreamChannel, Metrics, Modulation};
+    use telegraf::protocol::FieldData;
+
+    fn scrape(uptime: u64, corrected: u64, uncorrectables: u64) -> ScrapedMetrics {
+        ScrapedMetrics::from(Metrics {
+            channels: vec![Channel::Downstream(DownstreamChannel {
+                channel_id: 5,
+                modulation: Modulation::QAM256,
+                lock_status: true,
+                frequency: 567_000_000,
+                power: 1.0,
+                snr: 38.0,
+                corrected,
+                uncorrectables,
+            })],
+            uptime: Some(Duration::from_secs(uptime)),
+            ..Default::default()
+        })
+    }
+
+    /// (corrected, uncorrectables) for channel 5 after scraping each of `scrapes` in turn
+    fn deltas(scrapes: Vec<ScrapedMetrics>) -> Option<(u64, u64)> {
+        let mut tracker = CodewordTracker::default();
+        let mut last = None;
+        for mut scraped in scrapes {
+            tracker.update(&mut scraped);
+            last = Some(scraped);
+        }
+        let delta = last?.codeword_deltas.get(&5).copied()?;
+        Some((delta.corrected, delta.uncorrectables))
+    }
+
+    #[test]
+    fn first_scrape_has_no_delta() {
+        assert_eq!(deltas(vec![scrape(100, 10, 1)]), None);
+    }
+
+    #[test]
+    fn counts_what_changed() {
+        assert_eq!(
+            deltas(vec![scrape(100, 10, 1), scrape(160, 25, 3)]),
+            Some((15, 2))
+        );
+    }
+
+    #[test]
+    fn counters_going_backwards_start_over() {
+        assert_eq!(
+            deltas(vec![scrape(100, 1000, 50), scrape(160, 20, 2)]),
+            Some((20, 2))
+        );
+    }
+
+    #[test]
+    fn a_reboot_starts_over_even_when_the_totals_caught_up() {
+        assert_eq!(
+            deltas(vec![scrape(100_000, 10, 1), scrape(60, 500, 7)]),
+            Some((500, 7))
+        );
+    }
+
+    #[test]
+    fn a_reset_doesnt_spike_the_points() {
+        let mut tracker = CodewordTracker::default();
+        tracker.update(&mut scrape(100, u64::MAX - 5, 1000));
+        let mut scraped = scrape(160, 3, 4);
+        tracker.update(&mut scraped);
+
+        let points = metrics_to_points(&scraped, &HashMap::new());
+        let field = |name: &str| {
+            points[0]
+                .fields
+                .iter()
+                .find(|field| field.name == name)
+                .map(|field| field.value.clone())
+        };
+        assert!(matches!(
+            field("corrected_delta"),
+            Some(FieldData::UNumber(3))
+        ));
+        assert!(matches!(
+            field("uncorrectables_delta"),
+            Some(FieldData::UNumber(4))
+        ));
+        if let Some(FieldData::Float(rate)) = field("uncorrectables_rate") {
+            assert!(rate.is_finite() && rate >= 0.0);
+        }
+    }
+}
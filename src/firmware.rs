@@ -0,0 +1,33 @@
+use crate::scraped::ScrapedMetrics;
+use chrono::Utc;
+use log::Level;
+use modem_scraper_lib::payloads::LogEntry;
+use std::collections::HashMap;
+use tracing::info;
+
+/// Compares `firmware_version` in the device info tags from before and after. If it changed, sets
+/// `scraped.firmware_changed_from` and returns a log entry saying so.
+pub fn firmware_change(
+    previous_device_info: &HashMap<String, String>,
+    device_info: &HashMap<String, String>,
+    scraped: &mut ScrapedMetrics,
+) -> Option<LogEntry> {
+    let previous_version = previous_device_info.get("firmware_version")?;
+    let version = device_info.get("firmware_version")?;
+    if previous_version == version {
+        return None;
+    }
+
+    let message = format!(
+        "Modem firmware changed from {} to {}",
+        previous_version, version
+    );
+    info!("{}", message);
+    scraped.firmware_changed_from = Some(previous_version.clone());
+    Some(LogEntry {
+        timestamp: scraped.metrics.time.unwrap_or_else(Utc::now),
+        level: Level::Info,
+        message,
+        priority: None,
+    })
+}
@@ -1,14 +1,45 @@
 use log::Level;
 use serde::{self, Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use telegraf::protocol::Tag;
+use telegraf::Point;
+
+pub mod alerts;
+pub mod bonding;
+pub mod breaker;
+pub mod cli;
+pub mod dedup;
+pub mod deltas;
+pub mod firmware;
+pub mod liveness;
+pub mod log_events;
+pub mod notifiers;
+pub mod outages;
+pub mod probe;
+pub mod reboots;
+pub mod reload;
+pub mod report;
+pub mod scraped;
+pub mod settings;
+pub mod sinks;
+pub mod speedtest;
+pub mod spool;
+pub mod table;
+pub mod timezone;
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 struct LokiStream {
     /// k/v label pairs
     stream: HashMap<String, String>,
-    /// log entries (timestamp, log line)
-    // suspect this may need a custom deserializer
-    values: Vec<(String, String)>,
+    values: Vec<LokiValue>,
+}
+
+/// `[timestamp, line]`, or `[timestamp, line, structured metadata]`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+enum LokiValue {
+    WithMetadata(String, String, BTreeMap<String, String>),
+    Line(String, String),
 }
 
 /// https://grafana.com/docs/loki/latest/api/#push-log-entries-to-loki
@@ -17,25 +48,48 @@ pub struct LokiStreams {
     streams: Vec<LokiStream>,
 }
 
+/// One log line on its way to Loki
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LokiEntry {
+    pub level: Level,
+    /// ns since the epoch
+    pub timestamp: u128,
+    pub line: String,
+    /// on top of the stream's labels, so entries with different ones go in different streams
+    pub labels: BTreeMap<String, String>,
+    /// structured metadata, which needs Loki 3 or 2.9 with it turned on
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// A stream per level and set of extra labels, each oldest first
 pub fn construct_loki_streams(
     labels: HashMap<String, String>,
-    logs: Vec<(Level, u128, String)>,
+    mut logs: Vec<LokiEntry>,
 ) -> LokiStreams {
-    let mut bucket_logs: HashMap<Level, Vec<(String, String)>> = HashMap::new();
+    logs.sort_by_key(|log_entry| log_entry.timestamp);
+    let mut bucket_logs: HashMap<(Level, BTreeMap<String, String>), Vec<LokiValue>> =
+        HashMap::new();
 
     for log_entry in logs {
-        match bucket_logs.get_mut(&log_entry.0) {
-            Some(current_logs) => current_logs.push((log_entry.1.to_string(), log_entry.2)),
-            None => {
-                bucket_logs.insert(log_entry.0, vec![(log_entry.1.to_string(), log_entry.2)]);
-            }
-        }
+        let value = if log_entry.metadata.is_empty() {
+            LokiValue::Line(log_entry.timestamp.to_string(), log_entry.line)
+        } else {
+            LokiValue::WithMetadata(
+                log_entry.timestamp.to_string(),
+                log_entry.line,
+                log_entry.metadata,
+            )
+        };
+        bucket_logs
+            .entry((log_entry.level, log_entry.labels))
+            .or_default()
+            .push(value);
     }
 
     let streams: Vec<LokiStream> = bucket_logs
-        .iter()
-        .map(|(key, value)| {
-            let log_level_str = match key {
+        .into_iter()
+        .map(|((level, extra_labels), values)| {
+            let log_level_str = match level {
                 Level::Trace => "trace",
                 Level::Debug => "debug",
                 Level::Info => "info",
@@ -43,13 +97,24 @@ pub fn construct_loki_streams(
                 Level::Error => "error",
             };
             let mut local_labels = labels.clone();
+            local_labels.extend(extra_labels);
             local_labels.insert("level".to_owned(), log_level_str.to_owned());
             LokiStream {
                 stream: local_labels,
-                values: value.to_owned(),
+                values,
             }
         })
         .collect();
 
     LokiStreams { streams }
 }
+
+/// Adds `tags` to every point so multiple modems can be told apart
+pub fn add_tags(points: &mut [Point], tags: &HashMap<String, String>) {
+    for point in points.iter_mut() {
+        point.tags.extend(tags.iter().map(|(name, value)| Tag {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        }));
+    }
+}
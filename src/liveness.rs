@@ -0,0 +1,156 @@
+//! `/healthz` and `/readyz`, for Docker and Kubernetes health checks
+
+use crate::sinks::SinkResults;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct ModemLiveness {
+    /// false once the scrape task has given up, which only happens when the first login fails
+    running: bool,
+    logged_in: bool,
+    last_scrape: Option<DateTime<Utc>>,
+    /// cleared by the next successful scrape
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SinkFailure {
+    at: DateTime<Utc>,
+    error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct Report {
+    /// by `device_address`
+    modems: BTreeMap<String, ModemLiveness>,
+    /// by sink name, cleared by the sink's next successful write
+    sink_errors: BTreeMap<&'static str, SinkFailure>,
+}
+
+/// What the scrape tasks have been up to. Cloning shares it.
+#[derive(Debug, Clone, Default)]
+pub struct Liveness {
+    report: Arc<Mutex<Report>>,
+}
+
+impl Liveness {
+    fn update_modem(&self, device_address: &str, update: impl FnOnce(&mut ModemLiveness)) {
+        let mut report = self.report.lock().unwrap();
+        update(
+            report
+                .modems
+                .entry(device_address.to_owned())
+                .or_insert_with(|| ModemLiveness {
+                    running: true,
+                    ..Default::default()
+                }),
+        );
+    }
+
+    /// A modem to report on. Until it's scraped, it isn't ready.
+    pub fn register(&self, device_address: &str) {
+        self.update_modem(device_address, |_| {});
+    }
+
+    pub fn logged_in(&self, device_address: &str) {
+        self.update_modem(device_address, |modem| modem.logged_in = true);
+    }
+
+    /// The scrape task for `device_address` has stopped
+    pub fn gave_up(&self, device_address: &str, error: &str) {
+        self.update_modem(device_address, |modem| {
+            modem.running = false;
+            modem.logged_in = false;
+            modem.last_error = Some(error.to_owned());
+        });
+    }
+
+    pub fn scraped(&self, device_address: &str) {
+        self.update_modem(device_address, |modem| {
+            modem.logged_in = true;
+            modem.last_scrape = Some(Utc::now());
+            modem.last_error = None;
+        });
+    }
+
+    pub fn scrape_failed(&self, device_address: &str, error: &modem_scraper_lib::Error) {
+        self.update_modem(device_address, |modem| {
+            if let modem_scraper_lib::Error::Login(_) | modem_scraper_lib::Error::Unauthorized =
+                error
+            {
+                modem.logged_in = false;
+            }
+            modem.last_error = Some(error.to_string());
+        });
+    }
+
+    pub fn sinks_written(&self, results: &SinkResults) {
+        let mut report = self.report.lock().unwrap();
+        for (name, result) in results {
+            match result {
+                Ok(_) => {
+                    report.sink_errors.remove(name);
+                }
+                Err(error) => {
+                    report.sink_errors.insert(
+                        name,
+                        SinkFailure {
+                            at: Utc::now(),
+                            error: error.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    fn report(&self) -> Report {
+        self.report.lock().unwrap().clone()
+    }
+}
+
+fn respond(healthy: bool, report: Report) -> (StatusCode, Json<Report>) {
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+/// Fails once a scrape task has given up, since restarting is the only way to get it back
+async fn healthz(State(liveness): State<Liveness>) -> (StatusCode, Json<Report>) {
+    let report = liveness.report();
+    respond(report.modems.values().all(|modem| modem.running), report)
+}
+
+/// Fails until every modem is logged in and its latest scrape worked
+async fn readyz(State(liveness): State<Liveness>) -> (StatusCode, Json<Report>) {
+    let report = liveness.report();
+    let ready = report.modems.values().all(|modem| {
+        modem.running
+            && modem.logged_in
+            && modem.last_scrape.is_some()
+            && modem.last_error.is_none()
+    });
+    respond(ready, report)
+}
+
+/// Serves `/healthz` and `/readyz` on `address` until the process exits
+pub async fn serve(address: SocketAddr, liveness: Liveness) -> Result<(), hyper::Error> {
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(liveness);
+    axum::Server::bind(&address)
+        .serve(app.into_make_service())
+        .await
+}
@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use modem_scraper_lib::payloads::{LogEntry, LogEvent};
+use std::collections::BTreeMap;
+
+/// Counts [LogEvent]s in the modem's log between scrapes. One per modem.
+#[derive(Debug, Default)]
+pub struct LogEventCounter {
+    /// the modem returns its whole log every time, so only what's newer than this is counted
+    latest: Option<DateTime<Utc>>,
+    /// since the last [LogEventCounter::take]
+    counts: BTreeMap<LogEvent, u64>,
+    /// by DOCSIS event ID, since startup
+    event_codes: BTreeMap<u32, u64>,
+}
+
+impl LogEventCounter {
+    /// Counts the entries newer than last time. The first batch is whatever the modem had logged
+    /// before we started, so that only marks where to count from.
+    pub fn count(&mut self, logs: &[LogEntry]) {
+        if let Some(previous_latest) = self.latest {
+            for log_entry in logs {
+                if log_entry.timestamp > previous_latest {
+                    *self.counts.entry(log_entry.event()).or_default() += 1;
+                    if let Some(event_id) = log_entry.event_id() {
+                        *self.event_codes.entry(event_id).or_default() += 1;
+                    }
+                }
+            }
+        }
+        let latest = logs.iter().map(|log_entry| log_entry.timestamp).max();
+        // an empty first log is still somewhere to count from
+        self.latest = self.latest.max(latest).or(Some(DateTime::<Utc>::MIN_UTC));
+    }
+
+    /// Counts of every event since the last call, zeros included, or nothing if the log hasn't been
+    /// read yet
+    pub fn take(&mut self) -> BTreeMap<LogEvent, u64> {
+        if self.latest.is_none() {
+            return BTreeMap::new();
+        }
+        let mut counts = std::mem::take(&mut self.counts);
+        for event in LogEvent::ALL {
+            counts.entry(event).or_default();
+        }
+        counts
+    }
+
+    /// Lines counted by DOCSIS event ID since startup, for the ones that had one
+    pub fn event_codes(&self) -> BTreeMap<u32, u64> {
+        self.event_codes.clone()
+    }
+}
@@ -1,91 +1,242 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+use tokio::time::{Instant, Interval, MissedTickBehavior};
 
-use config::Config;
-use log::{error, Level};
-use modem_scraper::construct_loki_streams;
-use modem_scraper_lib::payloads::s33::{
-    GetMultipleHNAPsLogsResponse, GetMultipleHNAPsMetricsResponse,
-};
-use modem_scraper_lib::payloads::Channel;
-use modem_scraper_lib::SOAPClient;
+use clap::Parser;
+use log::{error, info, warn};
+use modem_scraper::alerts::AlertEngine;
+use modem_scraper::bonding::BondingTracker;
+use modem_scraper::breaker::CircuitBreaker;
+use modem_scraper::cli::{Cli, Command, Output, ScrapeFormat};
+use modem_scraper::deltas::CodewordTracker;
+use modem_scraper::firmware::firmware_change;
+use modem_scraper::liveness::{self, Liveness};
+use modem_scraper::log_events::LogEventCounter;
+use modem_scraper::notifiers::discord::DiscordNotifier;
+use modem_scraper::notifiers::ntfy::NtfyNotifier;
+use modem_scraper::notifiers::pushover::PushoverNotifier;
+use modem_scraper::notifiers::slack::SlackNotifier;
+use modem_scraper::notifiers::smtp::SmtpNotifier;
+use modem_scraper::notifiers::webhook::WebhookNotifier;
+use modem_scraper::notifiers::{self, Notifier};
+use modem_scraper::outages::OutageTracker;
+use modem_scraper::probe::Prober;
+use modem_scraper::reboots::RebootDetector;
+use modem_scraper::reload;
+use modem_scraper::report::{self, ReportBuilder};
+use modem_scraper::scraped::{ScrapedMetrics, ScraperStats};
+use modem_scraper::settings::{ModemSettings, Settings};
+use modem_scraper::sinks::csv::CsvSink;
+use modem_scraper::sinks::elasticsearch::ElasticsearchSink;
+use modem_scraper::sinks::graphite::{GraphiteSettings, GraphiteSink};
+use modem_scraper::sinks::influx::InfluxClient;
+use modem_scraper::sinks::loki::{LineTemplate, LokiSettings, LokiSink};
+use modem_scraper::sinks::mqtt::MqttSink;
+use modem_scraper::sinks::otlp::OtlpMetrics;
+use modem_scraper::sinks::prometheus::{self, PrometheusState};
+use modem_scraper::sinks::splunk::SplunkSink;
+use modem_scraper::sinks::sqlite::{self, SqliteSink};
+use modem_scraper::sinks::status::{self, StatusState};
+use modem_scraper::sinks::stdout::{StdoutFormat, StdoutSink};
+use modem_scraper::sinks::telegraf::{TelegrafOptions, TelegrafSink};
+use modem_scraper::sinks::victoriametrics::VictoriaMetricsSink;
+use modem_scraper::sinks::{self, LogSink, MetricSink, SinkResults};
+use modem_scraper::speedtest::Speedtester;
+use modem_scraper::spool::{SpoolSettings, Spooled};
+use modem_scraper::table;
+use modem_scraper::timezone;
+use modem_scraper_lib::payloads::LogEntry;
+use modem_scraper_lib::replay::{replay_file, Replayed};
+use modem_scraper_lib::{ClientOptions, Model, Modem};
 use opentelemetry::sdk::{trace, Resource};
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig;
-use telegraf::{Metric, Point};
-use tracing::instrument;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::{prelude::*, EnvFilter};
 use tracing_unwrap::ResultExt;
 
-/// sends channel metrics to telegraf
-#[instrument(skip(telegraf_client))]
-fn metrics_to_telegraf(
-    metrics: GetMultipleHNAPsMetricsResponse,
-    telegraf_client: &mut telegraf::Client,
-) -> Result<(), telegraf::TelegrafError> {
-    // downstream points
-    let mut points: Vec<telegraf::Point> = metrics
-        .get_customer_status_downstream_channel_info_response
-        .customer_conn_downstream_channel
-        .iter()
-        .map(|p| match p {
-            Channel::Downstream(c) => c.to_point(),
-            Channel::Upstream(c) => c.to_point(),
-        })
-        .collect();
-    // upstream points
-    points.extend(
-        metrics
-            .get_customer_status_upstream_channel_info_response
-            .customer_conn_upstream_channel
-            .iter()
-            .map(|p| match p {
-                Channel::Downstream(c) => c.to_point(),
-                Channel::Upstream(c) => c.to_point(),
-            })
-            .collect::<Vec<Point>>(),
-    );
-    // timestamps?
-    telegraf_client.write_points(&points)
-}
-
-#[instrument]
-async fn logs_to_loki(
-    logs: GetMultipleHNAPsLogsResponse,
-    http_client: &reqwest::Client,
-    loki_url: String,
-) -> Result<reqwest::Response, reqwest::Error> {
-    let labels = HashMap::from([("app".to_owned(), "modem_scraper".to_owned())]);
-    let streams = construct_loki_streams(
-        labels,
-        logs.get_customer_status_log_response
-            .customer_status_log_list
-            .iter()
-            .map(|log_entry| {
-                (
-                    log_entry.level,
-                    u128::try_from(log_entry.timestamp.timestamp_nanos_opt().unwrap())
-                        .unwrap_or_log(),
-                    log_entry.message.to_owned(),
-                )
-            })
-            .collect::<Vec<(Level, u128, String)>>(),
-    );
+/// Reads every certificate out of the PEM bundle at `path`
+fn load_ca_bundle(path: &str) -> Result<Vec<reqwest::Certificate>, String> {
+    let pem = std::fs::read(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    reqwest::Certificate::from_pem_bundle(&pem)
+        .map_err(|e| format!("Unable to load certificates from {}: {}", path, e))
+}
+
+/// Proxies every request through `proxy_url`
+fn build_proxy(
+    proxy_url: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+) -> Result<reqwest::Proxy, reqwest::Error> {
+    let proxy = reqwest::Proxy::all(proxy_url)?;
+    Ok(match username {
+        Some(username) => proxy.basic_auth(username, password.unwrap_or_default()),
+        None => proxy,
+    })
+}
 
-    http_client.post(loki_url).json(&streams).send().await
+/// Everything needed to build the sinks and notifiers for each scrape task
+#[derive(Clone, Default)]
+struct SinkSettings {
+    telegraf_address: Option<String>,
+    telegraf_options: TelegrafOptions,
+    prometheus_state: Option<PrometheusState>,
+    status_state: Option<StatusState>,
+    influx_client: Option<InfluxClient>,
+    victoriametrics_sink: Option<VictoriaMetricsSink>,
+    loki_sink: Option<LokiSink>,
+    splunk_sink: Option<SplunkSink>,
+    elasticsearch_sink: Option<ElasticsearchSink>,
+    mqtt_sink: Option<MqttSink>,
+    otlp_metrics: Option<OtlpMetrics>,
+    csv_sink: Option<CsvSink>,
+    graphite_settings: Option<GraphiteSettings>,
+    sqlite_sink: Option<SqliteSink>,
+    stdout: Option<StdoutFormat>,
+    spool: Option<SpoolSettings>,
+    webhooks: Vec<WebhookNotifier>,
+    discord: Option<DiscordNotifier>,
+    slack: Option<SlackNotifier>,
+    smtp: Option<SmtpNotifier>,
+    ntfy: Option<NtfyNotifier>,
+    pushover: Option<PushoverNotifier>,
+}
+
+impl SinkSettings {
+    /// Sinks that can't be set up are logged and left out
+    fn metric_sinks(&self) -> Vec<Box<dyn MetricSink>> {
+        let mut sinks: Vec<Box<dyn MetricSink>> = Vec::new();
+        if let Some(telegraf_address) = &self.telegraf_address {
+            match TelegrafSink::new(telegraf_address, self.telegraf_options.clone()) {
+                Ok(sink) => sinks.push(Box::new(sink)),
+                Err(e) => error!("Unable to set up telegraf: {}", e),
+            }
+        }
+        if let Some(prometheus_state) = &self.prometheus_state {
+            sinks.push(Box::new(prometheus_state.clone()));
+        }
+        if let Some(status_state) = &self.status_state {
+            sinks.push(Box::new(status_state.clone()));
+        }
+        if let Some(influx_client) = &self.influx_client {
+            sinks.push(Box::new(influx_client.clone()));
+        }
+        if let Some(victoriametrics_sink) = &self.victoriametrics_sink {
+            sinks.push(Box::new(victoriametrics_sink.clone()));
+        }
+        if let Some(mqtt_sink) = &self.mqtt_sink {
+            sinks.push(Box::new(mqtt_sink.clone()));
+        }
+        if let Some(otlp_metrics) = &self.otlp_metrics {
+            sinks.push(Box::new(otlp_metrics.clone()));
+        }
+        if let Some(csv_sink) = &self.csv_sink {
+            sinks.push(Box::new(csv_sink.clone()));
+        }
+        // one connection per modem, like telegraf
+        if let Some(graphite_settings) = &self.graphite_settings {
+            sinks.push(Box::new(GraphiteSink::new(graphite_settings.clone())));
+        }
+        if let Some(sqlite_sink) = &self.sqlite_sink {
+            sinks.push(Box::new(sqlite_sink.clone()));
+        }
+        if let Some(format) = self.stdout {
+            sinks.push(Box::new(StdoutSink { format }));
+        }
+        match &self.spool {
+            Some(spool) => sinks
+                .into_iter()
+                .map(|sink| -> Box<dyn MetricSink> { Box::new(Spooled::new(sink, spool.clone())) })
+                .collect(),
+            None => sinks,
+        }
+    }
+
+    fn log_sinks(&self) -> Vec<Box<dyn LogSink>> {
+        let mut sinks: Vec<Box<dyn LogSink>> = Vec::new();
+        if let Some(loki_sink) = &self.loki_sink {
+            sinks.push(Box::new(loki_sink.clone()));
+        }
+        if let Some(status_state) = &self.status_state {
+            sinks.push(Box::new(status_state.clone()));
+        }
+        if let Some(splunk_sink) = &self.splunk_sink {
+            sinks.push(Box::new(splunk_sink.clone()));
+        }
+        if let Some(elasticsearch_sink) = &self.elasticsearch_sink {
+            sinks.push(Box::new(elasticsearch_sink.clone()));
+        }
+        if let Some(sqlite_sink) = &self.sqlite_sink {
+            sinks.push(Box::new(sqlite_sink.clone()));
+        }
+        if let Some(format) = self.stdout {
+            sinks.push(Box::new(StdoutSink { format }));
+        }
+        match &self.spool {
+            Some(spool) => sinks
+                .into_iter()
+                .map(|sink| -> Box<dyn LogSink> { Box::new(Spooled::new(sink, spool.clone())) })
+                .collect(),
+            None => sinks,
+        }
+    }
+
+    fn notifiers(&self) -> Vec<Box<dyn Notifier>> {
+        let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+        for webhook in &self.webhooks {
+            notifiers.push(Box::new(webhook.clone()));
+        }
+        if let Some(discord) = &self.discord {
+            notifiers.push(Box::new(discord.clone()));
+        }
+        if let Some(slack) = &self.slack {
+            notifiers.push(Box::new(slack.clone()));
+        }
+        if let Some(smtp) = &self.smtp {
+            notifiers.push(Box::new(smtp.clone()));
+        }
+        if let Some(ntfy) = &self.ntfy {
+            notifiers.push(Box::new(ntfy.clone()));
+        }
+        if let Some(pushover) = &self.pushover {
+            notifiers.push(Box::new(pushover.clone()));
+        }
+        notifiers
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    let settings = Config::builder()
-        .add_source(config::File::with_name("config.yml"))
-        .add_source(config::Environment::with_prefix(
-            &env!("CARGO_PKG_NAME").replace('-', "_").to_uppercase(),
-        ))
-        .build()
-        .unwrap();
+    let cli = Cli::parse();
+    let config = cli.load_config().unwrap_or_else(|e| {
+        eprintln!("Unable to load {}: {}", cli.config, e);
+        std::process::exit(1);
+    });
+    let command = cli.command.clone().unwrap_or(Command::Run);
+
+    if let Command::ValidateConfig = command {
+        let problems = Settings::problems(&config);
+        if problems.is_empty() {
+            println!("{} is valid", cli.config);
+            return;
+        }
+        eprintln!("{} has {} problem(s):", cli.config, problems.len());
+        for problem in problems {
+            eprintln!("  {}", problem);
+        }
+        std::process::exit(1);
+    }
+    let settings = Settings::load(&config).unwrap_or_else(|e| {
+        eprintln!("Invalid {}: {}", cli.config, e);
+        eprintln!("`modem-scraper validate-config` lists everything that's wrong with it");
+        std::process::exit(1);
+    });
+    let dump_raw = cli.overrides.dump_raw.clone();
 
-    if settings.get_bool("trace").unwrap_or(false) {
+    if settings.trace {
         let otlp_tracer =
             opentelemetry_otlp::new_pipeline()
                 .tracing()
@@ -104,50 +255,877 @@ async fn main() {
             .unwrap_or_log();
     }
 
-    let http_client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(settings.get_bool("accept_invalid_certs").unwrap())
-        .build()
-        .unwrap();
+    match command {
+        Command::Query { hours } => {
+            let Some(sqlite_settings) = &settings.sqlite else {
+                eprintln!("`sqlite` needs to be configured to query it");
+                std::process::exit(1);
+            };
+            if let Err(e) = sqlite::dump(sqlite_settings, hours) {
+                eprintln!("Unable to query {}: {}", sqlite_settings.path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        Command::Reboot => {
+            let mut failed = false;
+            for modem_settings in &settings.modems {
+                let result = match connect_modem(modem_settings, dump_raw.clone()).await {
+                    Ok((_, mut modem_client)) => modem_client.reboot().await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(_) => println!("Rebooted {}", modem_settings.device_address),
+                    Err(e) => {
+                        eprintln!("Unable to reboot {}: {}", modem_settings.device_address, e);
+                        failed = true;
+                    }
+                }
+            }
+            std::process::exit(i32::from(failed));
+        }
+        Command::LoginTest => {
+            let mut failed = false;
+            for modem_settings in &settings.modems {
+                match connect_modem(modem_settings, dump_raw.clone()).await {
+                    Ok((model, _)) => {
+                        println!("Logged into {} ({})", modem_settings.device_address, model)
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "Unable to log into {}: {}",
+                            modem_settings.device_address, e
+                        );
+                        failed = true;
+                    }
+                }
+            }
+            std::process::exit(i32::from(failed));
+        }
+        // nothing's sent anywhere, the replies are written out while they're fetched
+        Command::DumpRaw { dir } => {
+            let mut failed = false;
+            for modem_settings in &settings.modems {
+                if let Err(e) = scrape_once(modem_settings, Some(dir.clone())).await {
+                    eprintln!("Unable to scrape {}: {}", modem_settings.device_address, e);
+                    failed = true;
+                }
+            }
+            if !failed {
+                println!("Saved replies to {}", dir.display());
+            }
+            std::process::exit(i32::from(failed));
+        }
+        Command::ScrapeOnce { format } => {
+            let mut stdout = StdoutSink {
+                format: StdoutFormat::Json,
+            };
+            let mut failed = false;
+            for modem_settings in &settings.modems {
+                match scrape_once(modem_settings, dump_raw.clone()).await {
+                    Ok((metrics, logs, tags)) => match format {
+                        ScrapeFormat::Table => {
+                            println!("{}", table::render(&metrics, &logs, &tags))
+                        }
+                        ScrapeFormat::Json => {
+                            // printing can't fail
+                            let _ = stdout.write_metrics(&metrics, &tags).await;
+                            let _ = stdout.write_logs(&logs, &tags).await;
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("Unable to scrape {}: {}", modem_settings.device_address, e);
+                        failed = true;
+                    }
+                }
+            }
+            std::process::exit(i32::from(failed));
+        }
+        Command::Replay { dir } => {
+            let prometheus_state = serve_prometheus(&settings, cli.overrides.output);
+            let sink_settings = match output_sinks(
+                &settings,
+                None,
+                cli.overrides.output,
+                prometheus_state,
+                None,
+            ) {
+                Ok(sink_settings) => sink_settings,
+                Err(e) => {
+                    eprintln!("Unable to set up the outputs: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = replay(&dir, &sink_settings).await {
+                eprintln!("Unable to replay {}: {}", dir.display(), e);
+                std::process::exit(1);
+            }
+        }
+        Command::Run => {
+            let output = cli.overrides.output;
+            let prometheus_state = serve_prometheus(&settings, output);
+            let status_state = serve_status(&settings, output);
+            let sink_settings = match output_sinks(
+                &settings,
+                None,
+                output,
+                prometheus_state.clone(),
+                status_state.clone(),
+            ) {
+                Ok(sink_settings) => sink_settings,
+                Err(e) => {
+                    eprintln!("Unable to set up the outputs: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let (running_tx, running_rx) = watch::channel(Running {
+                sink_settings: Arc::new(sink_settings),
+                settings: Arc::new(settings),
+            });
+
+            let liveness = Liveness::default();
+            if let Some(healthcheck_address) = running_rx.borrow().settings.healthcheck_address {
+                tokio::task::spawn(liveness::serve(healthcheck_address, liveness.clone()));
+            }
+
+            let mut tasks = Vec::new();
+            for modem_settings in running_rx.borrow().settings.modems.clone() {
+                liveness.register(&modem_settings.device_address);
+                tasks.push(tokio::task::spawn(scrape_modem(
+                    modem_settings,
+                    running_rx.clone(),
+                    liveness.clone(),
+                    dump_raw.clone(),
+                )));
+            }
 
-    let mut modem_client = SOAPClient::new(
-        settings.get_string("device_address").unwrap(),
-        settings.get_bool("accept_invalid_certs").unwrap_or(false),
-    );
+            match reload::watch(cli.clone()) {
+                Ok(mut reloads) => {
+                    tokio::task::spawn(async move {
+                        while let Some(settings) = reloads.recv().await {
+                            let running = running_tx.borrow().clone();
+                            let previous = running.settings.clone();
+                            if settings.prometheus_address != previous.prometheus_address {
+                                warn!("prometheus_address only changes on restart");
+                            }
+                            if settings.healthcheck_address != previous.healthcheck_address {
+                                warn!("healthcheck_address only changes on restart");
+                            }
+                            if settings.status_address != previous.status_address {
+                                warn!("status_address only changes on restart");
+                            }
+                            if settings.modems.len() != previous.modems.len() {
+                                warn!("Modems are only added or removed on restart");
+                            }
+                            // the old outputs keep going rather than none at all
+                            let sink_settings = match output_sinks(
+                                &settings,
+                                Some(&running),
+                                output,
+                                prometheus_state.clone(),
+                                status_state.clone(),
+                            ) {
+                                Ok(sink_settings) => sink_settings,
+                                Err(e) => {
+                                    error!("Not reloading {}: {}", cli.config, e);
+                                    continue;
+                                }
+                            };
+                            // a replaced exporter would keep exporting the last scrape it saw
+                            if let Some(otlp_metrics) = &running.sink_settings.otlp_metrics {
+                                if !sink_settings
+                                    .otlp_metrics
+                                    .as_ref()
+                                    .is_some_and(|new| new.is(otlp_metrics))
+                                {
+                                    otlp_metrics.stop();
+                                }
+                            }
+                            info!("Reloaded {}", cli.config);
+                            running_tx.send_replace(Running {
+                                sink_settings: Arc::new(sink_settings),
+                                settings: Arc::new(settings),
+                            });
+                        }
+                    });
+                }
+                Err(e) => error!("Unable to watch {} for changes: {}", cli.config, e),
+            }
 
-    let scrape_duration = std::time::Duration::from_secs(
-        u64::try_from(settings.get_int("scrape_interval_seconds").unwrap()).unwrap(),
-    );
+            for task in tasks {
+                task.await.unwrap_or_log();
+            }
+        }
+        Command::ValidateConfig => unreachable!(),
+    }
+}
 
-    let mut telegraf_client =
-        telegraf::Client::new(&settings.get_string("telegraf_address").unwrap()).unwrap();
+/// What the scrape tasks pick up again when the config changes
+#[derive(Clone)]
+struct Running {
+    settings: Arc<Settings>,
+    sink_settings: Arc<SinkSettings>,
+}
 
-    // tick this every 5s
-    let forever = tokio::task::spawn(async move {
-        let mut interval = tokio::time::interval(scrape_duration);
+/// Serves `/metrics` if `prometheus_address` is set. Binding happens once, so changing the address
+/// needs a restart.
+fn serve_prometheus(settings: &Settings, output: Option<Output>) -> Option<PrometheusState> {
+    let prometheus_address = settings.prometheus_address.filter(|_| output.is_none())?;
+    let prometheus_state = PrometheusState::default();
+    tokio::task::spawn(prometheus::serve(
+        prometheus_address,
+        prometheus_state.clone(),
+    ));
+    Some(prometheus_state)
+}
 
-        modem_client
-            .login(
-                &settings.get_string("device_username").unwrap(),
-                &settings.get_string("device_password").unwrap(),
-            )
-            .await;
-
-        loop {
-            let metrics: GetMultipleHNAPsMetricsResponse = modem_client.metrics().await;
-            match metrics_to_telegraf(metrics, &mut telegraf_client) {
-                Ok(_) => (),
-                Err(e) => error!("{}", e),
-            }
-            let logs_response: GetMultipleHNAPsLogsResponse = modem_client.logs().await;
-            logs_to_loki(
-                logs_response,
-                &http_client,
-                settings.get_string("logs_address").unwrap(),
+/// Serves `/api/v1/` if `status_address` is set. Like `/metrics`, changing the address needs a
+/// restart.
+fn serve_status(settings: &Settings, output: Option<Output>) -> Option<StatusState> {
+    let status_address = settings.status_address.filter(|_| output.is_none())?;
+    let status_state = StatusState::default();
+    tokio::task::spawn(status::serve(status_address, status_state.clone()));
+    Some(status_state)
+}
+
+/// The outputs in the config, unless `--output` says otherwise. On a reload, `previous` is what's
+/// running already.
+fn output_sinks(
+    settings: &Settings,
+    previous: Option<&Running>,
+    output: Option<Output>,
+    prometheus_state: Option<PrometheusState>,
+    status_state: Option<StatusState>,
+) -> Result<SinkSettings, String> {
+    match output {
+        None => configured_sinks(settings, previous, prometheus_state, status_state),
+        // just the scrapes as JSON lines, for piping into something else
+        Some(Output::Stdout) => Ok(SinkSettings {
+            stdout: Some(StdoutFormat::Json),
+            ..Default::default()
+        }),
+    }
+}
+
+/// Every output set in the config. Outputs that hold a connection or a running exporter are only
+/// set up again if their settings changed, otherwise the `previous` one carries on.
+fn configured_sinks(
+    settings: &Settings,
+    previous: Option<&Running>,
+    prometheus_state: Option<PrometheusState>,
+    status_state: Option<StatusState>,
+) -> Result<SinkSettings, String> {
+    let mut http_client_builder =
+        reqwest::Client::builder().danger_accept_invalid_certs(settings.accept_invalid_certs);
+    if let Some(ca_bundle) = &settings.ca_bundle {
+        for certificate in load_ca_bundle(ca_bundle)? {
+            http_client_builder = http_client_builder.add_root_certificate(certificate);
+        }
+    }
+    if let Some(logs_proxy_url) = &settings.logs_proxy_url {
+        let proxy = build_proxy(logs_proxy_url, None, None)
+            .map_err(|e| format!("Unable to use logs_proxy_url {}: {}", logs_proxy_url, e))?;
+        http_client_builder = http_client_builder.proxy(proxy);
+    }
+    let http_client = http_client_builder
+        .build()
+        .map_err(|e| format!("Unable to set up the HTTP client: {}", e))?;
+    let line_template = settings
+        .loki_line_template
+        .as_deref()
+        .map(LineTemplate::new)
+        .transpose()
+        .map_err(|e| format!("Invalid loki_line_template: {}", e))?;
+
+    // at least one of these should be set, or the scrapes go nowhere
+    Ok(SinkSettings {
+        telegraf_address: settings.telegraf_address.clone(),
+        telegraf_options: TelegrafOptions {
+            skip_unchanged: settings.telegraf_skip_unchanged,
+            resend_unchanged: Duration::from_secs(settings.telegraf_unchanged_resend_seconds),
+            batch_size: settings.telegraf_batch_size,
+            flush_interval: Duration::from_secs(settings.telegraf_flush_interval_seconds),
+        },
+        prometheus_state,
+        status_state,
+        influx_client: settings
+            .influx
+            .clone()
+            .map(|influx_settings| InfluxClient::new(http_client.clone(), influx_settings)),
+        victoriametrics_sink: settings
+            .victoriametrics
+            .clone()
+            .map(|victoriametrics_settings| {
+                VictoriaMetricsSink::new(http_client.clone(), victoriametrics_settings)
+            }),
+        loki_sink: settings.logs_address.clone().map(|logs_address| {
+            LokiSink::new(
+                http_client.clone(),
+                LokiSettings {
+                    url: logs_address,
+                    encoding: settings.loki_encoding,
+                    log_fields: settings.loki_log_fields,
+                    split: settings.loki_split_streams.clone(),
+                    line_template,
+                    username: settings.loki_username.clone(),
+                    password: settings.loki_password.clone(),
+                    bearer_token: settings.loki_bearer_token.clone(),
+                    tenant_id: settings.loki_tenant_id.clone(),
+                    labels: settings.loki_labels.clone(),
+                    state_directory: settings.loki_state_directory.clone(),
+                    dedup_capacity: settings.loki_dedup_capacity,
+                    dedup_ttl: settings.loki_dedup_ttl_seconds.map(Duration::from_secs),
+                    dedup_tolerance: Duration::from_secs(settings.loki_dedup_tolerance_seconds),
+                    batch_size: settings.loki_batch_size,
+                    batch_timeout: Duration::from_secs(settings.loki_batch_timeout_seconds),
+                },
             )
-            .await
-            .unwrap_or_log();
-            interval.tick().await;
+        }),
+        splunk_sink: settings
+            .splunk
+            .clone()
+            .map(|splunk_settings| SplunkSink::new(http_client.clone(), splunk_settings)),
+        elasticsearch_sink: settings
+            .elasticsearch
+            .clone()
+            .map(|elasticsearch_settings| {
+                ElasticsearchSink::new(http_client.clone(), elasticsearch_settings)
+            }),
+        // one connection shared by every modem
+        mqtt_sink: previous
+            .filter(|previous| previous.settings.mqtt == settings.mqtt)
+            .and_then(|previous| previous.sink_settings.mqtt_sink.clone())
+            .or_else(|| {
+                settings.mqtt.as_ref().and_then(|mqtt_settings| {
+                    match MqttSink::connect(mqtt_settings) {
+                        Ok(mqtt_sink) => Some(mqtt_sink),
+                        Err(e) => {
+                            error!("Unable to set up MQTT: {}", e);
+                            None
+                        }
+                    }
+                })
+            }),
+        // exported once per metrics interval, there's nothing new in between
+        otlp_metrics: previous
+            .filter(|previous| {
+                previous.settings.otlp_metrics_endpoint == settings.otlp_metrics_endpoint
+                    && previous.settings.metrics_interval() == settings.metrics_interval()
+            })
+            .and_then(|previous| previous.sink_settings.otlp_metrics.clone())
+            .filter(|_| settings.otlp_metrics)
+            .or_else(|| {
+                settings
+                    .otlp_metrics
+                    .then(|| {
+                        OtlpMetrics::install(
+                            settings.otlp_metrics_endpoint.clone(),
+                            settings.metrics_interval(),
+                        )
+                    })
+                    .and_then(|otlp_metrics| match otlp_metrics {
+                        Ok(otlp_metrics) => Some(otlp_metrics),
+                        Err(e) => {
+                            error!("Unable to set up OTLP metrics: {}", e);
+                            None
+                        }
+                    })
+            }),
+        csv_sink: settings
+            .csv_directory
+            .clone()
+            .map(|directory| CsvSink { directory }),
+        graphite_settings: settings.graphite.clone(),
+        sqlite_sink: previous
+            .filter(|previous| previous.settings.sqlite == settings.sqlite)
+            .and_then(|previous| previous.sink_settings.sqlite_sink.clone())
+            .or_else(|| {
+                settings.sqlite.as_ref().and_then(|sqlite_settings| {
+                    match SqliteSink::open(sqlite_settings) {
+                        Ok(sqlite_sink) => Some(sqlite_sink),
+                        Err(e) => {
+                            error!("Unable to open SQLite database: {}", e);
+                            None
+                        }
+                    }
+                })
+            }),
+        stdout: settings.stdout.then_some(settings.stdout_format),
+        spool: settings.spool.clone(),
+        webhooks: settings
+            .webhooks
+            .iter()
+            .map(|webhook_settings| {
+                WebhookNotifier::new(http_client.clone(), webhook_settings.clone())
+            })
+            .collect(),
+        discord: settings
+            .discord
+            .clone()
+            .map(|discord_settings| DiscordNotifier::new(http_client.clone(), discord_settings)),
+        slack: settings
+            .slack
+            .clone()
+            .map(|slack_settings| SlackNotifier::new(http_client.clone(), slack_settings)),
+        smtp: settings.smtp.as_ref().and_then(|smtp_settings| {
+            match SmtpNotifier::new(smtp_settings) {
+                Ok(smtp_notifier) => Some(smtp_notifier),
+                Err(e) => {
+                    error!("Unable to set up SMTP: {}", e);
+                    None
+                }
+            }
+        }),
+        ntfy: settings
+            .ntfy
+            .clone()
+            .map(|ntfy_settings| NtfyNotifier::new(http_client.clone(), ntfy_settings)),
+        pushover: settings
+            .pushover
+            .clone()
+            .map(|pushover_settings| PushoverNotifier::new(http_client.clone(), pushover_settings)),
+    })
+}
+
+/// Parses every reply saved in `dir`, oldest first, and writes them out like a scrape would.
+/// Replies that don't parse are reported and skipped.
+async fn replay(dir: &std::path::Path, sink_settings: &SinkSettings) -> std::io::Result<()> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    // file names start with the time they were saved
+    paths.sort();
+
+    let mut metric_sinks = sink_settings.metric_sinks();
+    let mut log_sinks = sink_settings.log_sinks();
+    // per host, picking up device info as it's replayed
+    let mut tags: HashMap<String, HashMap<String, String>> = HashMap::new();
+    for path in paths {
+        let saved = match replay_file(&path) {
+            Ok(saved) => saved,
+            Err(e) => {
+                error!("Unable to replay {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let tags = tags
+            .entry(saved.host.clone())
+            .or_insert_with(|| HashMap::from([("hostname".to_owned(), saved.host.clone())]));
+        match saved.replayed {
+            Replayed::Metrics(metrics) => {
+                sinks::write_metrics(&mut metric_sinks, &ScrapedMetrics::from(*metrics), tags)
+                    .await;
+            }
+            Replayed::Logs(logs) => {
+                sinks::write_logs(&mut log_sinks, &logs, tags).await;
+            }
+            Replayed::DeviceInfo(device_info) => tags.extend(device_info),
+        }
+    }
+    Ok(())
+}
+
+/// Figures out the model (probing the modem if it isn't configured), then connects and logs in
+async fn connect_modem(
+    modem_settings: &ModemSettings,
+    dump_raw: Option<PathBuf>,
+) -> Result<(Model, Box<dyn Modem>), modem_scraper_lib::Error> {
+    let device_address = modem_settings.device_address.clone();
+    let defaults = ClientOptions::default();
+    let client_options = ClientOptions {
+        accept_invalid_certs: modem_settings.accept_invalid_certs,
+        // without it the modem's certificate won't check out, so that's where this shows up
+        root_certificates: match modem_settings.ca_bundle.as_deref().map(load_ca_bundle) {
+            Some(Ok(certificates)) => certificates,
+            Some(Err(e)) => {
+                error!("{}", e);
+                Vec::new()
+            }
+            None => Vec::new(),
+        },
+        pinned_certificate: modem_settings.pinned_certificate.clone(),
+        proxy: modem_settings
+            .proxy_url
+            .as_deref()
+            .map(|proxy_url| {
+                build_proxy(
+                    proxy_url,
+                    modem_settings.proxy_username.as_deref(),
+                    modem_settings.proxy_password.as_deref(),
+                )
+            })
+            .transpose()?,
+        connect_timeout: modem_settings
+            .connect_timeout_seconds
+            .map_or(defaults.connect_timeout, Duration::from_secs),
+        timeout: modem_settings
+            .timeout_seconds
+            .map_or(defaults.timeout, Duration::from_secs),
+        hnap_transport: modem_settings.hnap_transport,
+        retry_policy: modem_settings.retry,
+        login_limits: modem_settings.login_limits.clone(),
+        hnap_timestamp: modem_settings.hnap_timestamp,
+        dump_raw,
+    };
+
+    // `protocol: snmp` works regardless of model, so it wins
+    let model: Model = match (&modem_settings.protocol, modem_settings.model) {
+        #[cfg(feature = "snmp")]
+        (Some(protocol), _) if protocol == "snmp" => Model::Snmp,
+        #[cfg(not(feature = "snmp"))]
+        (Some(protocol), _) if protocol == "snmp" => {
+            return Err(modem_scraper_lib::Error::MissingFeature("snmp"))
         }
+        (_, Some(model)) => model,
+        _ => modem_scraper_lib::detect(&device_address, &client_options)
+            .await?
+            .ok_or(modem_scraper_lib::Error::UnknownModel)?,
+    };
+
+    let mut modem_client = modem_scraper_lib::connect(model, device_address, &client_options)?;
+    modem_client
+        .login(
+            &modem_settings.device_username,
+            &modem_settings.device_password,
+        )
+        .await?;
+
+    Ok((model, modem_client))
+}
+
+/// Attached to every point and stream so modems can be told apart
+fn modem_tags(device_address: &str, model: Model) -> HashMap<String, String> {
+    HashMap::from([
+        (
+            "hostname".to_owned(),
+            reqwest::Url::parse(device_address)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_owned))
+                .unwrap_or_else(|| device_address.to_owned()),
+        ),
+        ("model".to_owned(), model.to_string()),
+    ])
+}
+
+/// Scrapes a single modem once. Unlike [scrape_modem], any failure gives up.
+async fn scrape_once(
+    modem_settings: &ModemSettings,
+    dump_raw: Option<PathBuf>,
+) -> Result<(ScrapedMetrics, Vec<LogEntry>, HashMap<String, String>), modem_scraper_lib::Error> {
+    let (model, mut modem_client) = connect_modem(modem_settings, dump_raw).await?;
+    let mut tags = modem_tags(&modem_settings.device_address, model);
+    tags.extend(modem_client.device_info().await?);
+
+    let mut metrics = modem_client.metrics().await?;
+    let mut logs = modem_client.logs().await?;
+    modem_settings.log_levels.apply(&mut logs);
+    if let Some(timezone) = modem_settings.modem_timezone {
+        timezone::localize_metrics(&mut metrics, timezone);
+        timezone::localize_logs(&mut logs, timezone);
+    }
+    Ok((metrics.into(), logs, tags))
+}
+
+/// How many sinks the last write failed on
+fn sink_failures(results: &SinkResults) -> u64 {
+    results.iter().filter(|(_, result)| result.is_err()).count() as u64
+}
+
+/// Scrapes a single modem forever
+async fn scrape_modem(
+    mut modem_settings: ModemSettings,
+    mut running_rx: watch::Receiver<Running>,
+    liveness: Liveness,
+    dump_raw: Option<PathBuf>,
+) {
+    let device_address = &modem_settings.device_address;
+    let mut breaker = CircuitBreaker::new(modem_settings.circuit_breaker);
+    // doubles with every failure until the breaker opens
+    let mut retry_delay = Duration::from_secs(5);
+    let (model, mut modem_client) = loop {
+        match connect_modem(&modem_settings, dump_raw.clone()).await {
+            Ok(connected) => break connected,
+            // left over from before a restart, and it'll pass
+            Err(modem_scraper_lib::Error::LoginHeldOff(not_before)) => {
+                warn!(
+                    "Not logging into {} until {}, after too many failed logins",
+                    device_address, not_before
+                );
+                let wait = (not_before - chrono::Utc::now())
+                    .to_std()
+                    .unwrap_or_default();
+                tokio::time::sleep(wait).await;
+            }
+            // no point trying again
+            Err(e @ modem_scraper_lib::Error::MissingFeature(_)) => {
+                error!("Unable to scrape {}: {}", device_address, e);
+                liveness.gave_up(device_address, &e.to_string());
+                return;
+            }
+            // most likely the modem's rebooting, or not up yet
+            Err(e) => {
+                error!("Unable to log into {}: {}", device_address, e);
+                liveness.scrape_failed(device_address, &e);
+                breaker.failed(device_address);
+                let wait = breaker.remaining().unwrap_or(retry_delay);
+                retry_delay = (retry_delay * 2).min(Duration::from_secs(300));
+                tokio::time::sleep(wait).await;
+            }
+        }
+    };
+    breaker.succeeded();
+    liveness.logged_in(device_address);
+
+    // the scraper's own metrics, sent along with the modem's
+    let mut stats = ScraperStats::default();
+
+    let mut tags = modem_tags(device_address, model);
+    match modem_client.device_info().await {
+        Ok(device_info) => tags.extend(device_info),
+        Err(e) => {
+            error!("Unable to get device info from {}: {}", device_address, e);
+            stats.parse_failures += u64::from(e.is_parse());
+        }
+    }
+
+    let mut running = running_rx.borrow_and_update().clone();
+    let mut metric_sinks = running.sink_settings.metric_sinks();
+    let mut log_sinks = running.sink_settings.log_sinks();
+    let mut notifiers = running.sink_settings.notifiers();
+    let mut codeword_tracker = CodewordTracker::default();
+    let mut reboot_detector = RebootDetector::default();
+    let mut outage_tracker = OutageTracker::default();
+    let mut bonding_tracker = BondingTracker::default();
+    let mut log_event_counter = LogEventCounter::default();
+    let mut alert_engine = AlertEngine::new(running.settings.alerts.clone());
+    let mut report_builder = ReportBuilder::new(running.settings.report.clone());
+    let speedtester = modem_settings
+        .speedtest
+        .clone()
+        .map(|speedtest| Speedtester::spawn(speedtest, device_address.clone()));
+    let prober = (!modem_settings.probes.is_empty()).then(|| {
+        Prober::spawn(
+            modem_settings.probes.clone(),
+            running.settings.metrics_interval(),
+            device_address,
+        )
     });
-    forever.await.unwrap_or_log();
+    // logged along with the next batch of logs that makes it out
+    let mut pending_log_entries: Vec<LogEntry> = Vec::new();
+
+    let mut metrics_interval = scrape_interval(Instant::now(), running.settings.metrics_interval());
+    let mut logs_interval = scrape_interval(Instant::now(), running.settings.logs_interval());
+
+    loop {
+        let due = tokio::select! {
+            // metrics before logs, so a reboot is noticed before the logs that go with it are sent
+            biased;
+
+            // a reload applies right away, not after whatever's left of the old intervals
+            Ok(()) = running_rx.changed() => {
+                running = running_rx.borrow_and_update().clone();
+                reschedule(&mut metrics_interval, running.settings.metrics_interval());
+                reschedule(&mut logs_interval, running.settings.logs_interval());
+                alert_engine.update_settings(running.settings.alerts.clone());
+                report_builder.update_settings(running.settings.report.clone());
+                let mut new_metric_sinks = running.sink_settings.metric_sinks();
+                sinks::carry_over_metric_sinks(&mut metric_sinks, &mut new_metric_sinks);
+                metric_sinks = new_metric_sinks;
+                let mut new_log_sinks = running.sink_settings.log_sinks();
+                sinks::carry_over_log_sinks(&mut log_sinks, &mut new_log_sinks);
+                log_sinks = new_log_sinks;
+                notifiers = running.sink_settings.notifiers();
+
+                match running
+                    .settings
+                    .modems
+                    .iter()
+                    .find(|new| new.device_address == *device_address)
+                {
+                    Some(new) => {
+                        modem_settings.modem_timezone = new.modem_timezone;
+                        modem_settings.log_levels = new.log_levels.clone();
+                        modem_settings.circuit_breaker = new.circuit_breaker;
+                        breaker.update_settings(new.circuit_breaker);
+                        if *new != modem_settings {
+                            warn!(
+                                "{}'s settings besides modem_timezone, log_levels and \
+                                 circuit_breaker only change on restart",
+                                device_address
+                            );
+                        }
+                    }
+                    None => warn!(
+                        "{} isn't in the config anymore, but it's only removed on restart",
+                        device_address
+                    ),
+                }
+                continue;
+            }
+            _ = metrics_interval.tick() => Due::Metrics,
+            _ = logs_interval.tick() => Due::Logs,
+        };
+
+        stats.circuit_open = breaker.is_open();
+        stats.circuit_trips = breaker.trips();
+        if stats.circuit_open {
+            // the scraper's own metrics still go out, so it's clear why the modem's stopped
+            if let Due::Metrics = due {
+                let scraped = ScrapedMetrics {
+                    scraper: Some(stats.clone()),
+                    ..Default::default()
+                };
+                let results = sinks::write_metrics(&mut metric_sinks, &scraped, &tags).await;
+                stats.sink_errors += sink_failures(&results);
+                liveness.sinks_written(&results);
+            }
+            continue;
+        }
+
+        tokio::time::sleep(jitter(running.settings.scrape_jitter())).await;
+        // the session has most likely expired while the modem was left alone
+        if breaker.cooled_down() {
+            info!("Logging into {} again", device_address);
+            if let Err(e) = modem_client
+                .login(
+                    &modem_settings.device_username,
+                    &modem_settings.device_password,
+                )
+                .await
+            {
+                error!("Unable to log into {}: {}", device_address, e);
+                liveness.scrape_failed(device_address, &e);
+                breaker.failed(device_address);
+                continue;
+            }
+            liveness.logged_in(device_address);
+        }
+
+        match due {
+            Due::Metrics => {
+                // a failed scrape is just skipped, the next tick will try again
+                let started = Instant::now();
+                let scraped = modem_client.metrics().await;
+                stats.scrape_duration = started.elapsed();
+                match scraped {
+                    Ok(mut metrics) => {
+                        if let Some(timezone) = modem_settings.modem_timezone {
+                            timezone::localize_metrics(&mut metrics, timezone);
+                        }
+                        let mut scraped = ScrapedMetrics::from(metrics);
+                        if let Some(log_entry) = reboot_detector.update(&mut scraped) {
+                            pending_log_entries.push(log_entry);
+                            // firmware only changes with a reboot, so that's when to look again
+                            match modem_client.device_info().await {
+                                Ok(device_info) => {
+                                    pending_log_entries.extend(firmware_change(
+                                        &tags,
+                                        &device_info,
+                                        &mut scraped,
+                                    ));
+                                    tags.extend(device_info);
+                                }
+                                Err(e) => {
+                                    error!(
+                                        "Unable to get device info from {}: {}",
+                                        device_address, e
+                                    );
+                                    stats.parse_failures += u64::from(e.is_parse());
+                                }
+                            }
+                        }
+                        pending_log_entries.extend(outage_tracker.update(&mut scraped));
+                        pending_log_entries.extend(bonding_tracker.update(&mut scraped));
+                        codeword_tracker.update(&mut scraped);
+                        let alerts = alert_engine.evaluate(&scraped, &tags);
+                        for alert in &alerts {
+                            warn!("{}", alert);
+                            pending_log_entries.push(alert.log_entry());
+                        }
+                        notifiers::notify(&mut notifiers, &alerts).await;
+                        report_builder.record(&scraped);
+                        if let (Some(settings), Some(report)) =
+                            (&running.settings.report, report_builder.take(&tags))
+                        {
+                            report::deliver(settings, &report, &mut notifiers).await;
+                        }
+                        scraped.log_events = log_event_counter.take();
+                        scraped.event_codes = log_event_counter.event_codes();
+                        scraped.speedtest = speedtester.as_ref().and_then(Speedtester::take);
+                        scraped.probes = prober.as_ref().map(Prober::latest).unwrap_or_default();
+                        let client_stats = modem_client.stats();
+                        stats.logins = client_stats.logins;
+                        stats.requests = client_stats.requests;
+                        stats.dedup_entries = sinks::dedup_entries(&log_sinks);
+                        scraped.scraper = Some(stats.clone());
+                        liveness.scraped(device_address);
+                        breaker.succeeded();
+                        let results =
+                            sinks::write_metrics(&mut metric_sinks, &scraped, &tags).await;
+                        stats.sink_errors += sink_failures(&results);
+                        liveness.sinks_written(&results);
+                    }
+                    Err(e) => {
+                        error!("Unable to get metrics from {}: {}", device_address, e);
+                        stats.parse_failures += u64::from(e.is_parse());
+                        liveness.scrape_failed(device_address, &e);
+                        breaker.failed(device_address);
+                    }
+                }
+            }
+            Due::Logs => match modem_client.logs().await {
+                Ok(mut logs) => {
+                    modem_settings.log_levels.apply(&mut logs);
+                    if let Some(timezone) = modem_settings.modem_timezone {
+                        timezone::localize_logs(&mut logs, timezone);
+                    }
+                    breaker.succeeded();
+                    log_event_counter.count(&logs);
+                    logs.append(&mut pending_log_entries);
+                    let results = sinks::write_logs(&mut log_sinks, &logs, &tags).await;
+                    stats.sink_errors += sink_failures(&results);
+                    liveness.sinks_written(&results);
+                }
+                Err(e) => {
+                    error!("Unable to get logs from {}: {}", device_address, e);
+                    stats.parse_failures += u64::from(e.is_parse());
+                    breaker.failed(device_address);
+                }
+            },
+        }
+    }
+}
+
+/// Which ticker went off
+enum Due {
+    Metrics,
+    Logs,
+}
+
+/// Ticks every `period` from `start`. A scrape that runs long skips the ticks it missed instead of
+/// catching up with a burst of them, so scrapes stay on the same schedule.
+fn scrape_interval(start: Instant, period: Duration) -> Interval {
+    let mut interval = tokio::time::interval_at(start, period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    interval
+}
+
+/// Starts `interval` over a whole `period` from now, if the period changed
+fn reschedule(interval: &mut Interval, period: Duration) {
+    if period != interval.period() {
+        *interval = scrape_interval(Instant::now() + period, period);
+    }
+}
+
+/// Somewhere between nothing and `max`, so modems (and scrapers) started together aren't polled
+/// in lockstep
+fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return max;
+    }
+    // not worth pulling in rand for this
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    max.mul_f64(f64::from(nanos) / 1e9)
 }
@@ -0,0 +1,83 @@
+use crate::alerts::Alert;
+use crate::report::Report;
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use tracing::{error, instrument};
+
+pub mod discord;
+pub mod ntfy;
+pub mod pushover;
+pub mod slack;
+pub mod smtp;
+pub mod webhook;
+
+pub type NotifierError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Somewhere alerts go, for someone to act on
+#[async_trait]
+pub trait Notifier: Send {
+    /// for error messages
+    fn name(&self) -> &'static str;
+    /// Every alert from one scrape, never empty
+    async fn notify(&mut self, alerts: &[Alert]) -> Result<(), NotifierError>;
+
+    /// Sends whatever's been held back, if it's time. Called after every scrape, alerts or not, for
+    /// notifiers that batch alerts up.
+    async fn flush(&mut self) -> Result<(), NotifierError> {
+        Ok(())
+    }
+
+    /// A periodic summary, sent straight away
+    async fn report(&mut self, report: &Report) -> Result<(), NotifierError>;
+}
+
+/// Sends to every notifier. A failing notifier is logged and skipped, same as the sinks.
+#[instrument(skip(notifiers))]
+pub async fn notify(notifiers: &mut [Box<dyn Notifier>], alerts: &[Alert]) {
+    for notifier in notifiers.iter_mut() {
+        let mut result = Ok(());
+        if !alerts.is_empty() {
+            result = notifier.notify(alerts).await;
+        }
+        if result.is_ok() {
+            result = notifier.flush().await;
+        }
+        if let Err(e) = result {
+            error!("Unable to send alerts to {}: {}", notifier.name(), e);
+        }
+    }
+}
+
+/// Sends a report to every notifier. A failing notifier is logged and skipped.
+#[instrument(skip_all)]
+pub async fn report(notifiers: &mut [Box<dyn Notifier>], report: &Report) {
+    for notifier in notifiers.iter_mut() {
+        if let Err(e) = notifier.report(report).await {
+            error!("Unable to send the report to {}: {}", notifier.name(), e);
+        }
+    }
+}
+
+/// `template` with each `{{name}}` replaced by that field, or by nothing if there's no such field.
+/// `escape` is applied to every value first.
+pub fn render(
+    template: &str,
+    fields: &BTreeMap<String, String>,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..start + end].trim();
+        if let Some(value) = fields.get(name) {
+            rendered.push_str(&escape(value));
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
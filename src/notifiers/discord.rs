@@ -0,0 +1,104 @@
+use super::{Notifier, NotifierError};
+use crate::alerts::{Alert, Severity};
+use crate::report::Report;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// `discord:` in the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordSettings {
+    /// from the channel's Integrations settings
+    pub webhook_url: String,
+    /// posts as the webhook's own name if unset
+    pub username: Option<String>,
+}
+
+/// Discord won't take more than this many embeds in one message
+const MAX_EMBEDS: usize = 10;
+/// or more than this many characters in an embed's description
+const MAX_DESCRIPTION: usize = 4096;
+
+/// Posts alerts to a Discord channel as embeds, colored by severity
+#[derive(Debug, Clone)]
+pub struct DiscordNotifier {
+    http_client: reqwest::Client,
+    settings: DiscordSettings,
+}
+
+impl DiscordNotifier {
+    pub fn new(http_client: reqwest::Client, settings: DiscordSettings) -> DiscordNotifier {
+        DiscordNotifier {
+            http_client,
+            settings,
+        }
+    }
+}
+
+fn embed(alert: &Alert) -> Value {
+    let mut fields = vec![json!({
+        "name": "Modem",
+        "value": alert.tags.get("hostname").map(String::as_str).unwrap_or("modem"),
+        "inline": true,
+    })];
+    if let Some(channel) = &alert.channel {
+        fields.push(json!({ "name": "Channel", "value": channel, "inline": true }));
+    }
+    if let Some(value) = alert.value {
+        fields.push(json!({ "name": "Value", "value": format!("{:.1}", value), "inline": true }));
+    }
+    if let Some(threshold) = alert.threshold {
+        fields.push(json!({ "name": "Threshold", "value": threshold.to_string(), "inline": true }));
+    }
+    json!({
+        "title": alert.kind.as_str(),
+        "description": alert.message,
+        "color": match alert.severity {
+            Severity::Warning => 0xf0a020,
+            Severity::Critical => 0xd03030,
+        },
+        "fields": fields,
+        "timestamp": alert.time.to_rfc3339(),
+    })
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    async fn notify(&mut self, alerts: &[Alert]) -> Result<(), NotifierError> {
+        for alerts in alerts.chunks(MAX_EMBEDS) {
+            self.post(alerts.iter().map(embed).collect()).await?;
+        }
+        Ok(())
+    }
+
+    /// One embed with the report as a code block, cut short if there are too many channels
+    async fn report(&mut self, report: &Report) -> Result<(), NotifierError> {
+        let text: String = report.text().chars().take(MAX_DESCRIPTION - 8).collect();
+        self.post(vec![json!({
+            "title": report.title(),
+            "description": format!("```\n{}```", text),
+            "timestamp": report.end.to_rfc3339(),
+        })])
+        .await
+    }
+}
+
+impl DiscordNotifier {
+    async fn post(&self, embeds: Vec<Value>) -> Result<(), NotifierError> {
+        let mut body = json!({ "embeds": embeds });
+        if let Some(username) = &self.settings.username {
+            body["username"] = json!(username);
+        }
+        self.http_client
+            .post(&self.settings.webhook_url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
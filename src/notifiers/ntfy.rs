@@ -0,0 +1,116 @@
+use super::{Notifier, NotifierError};
+use crate::alerts::{Alert, Severity};
+use crate::report::Report;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+/// `ntfy:` in the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct NtfySettings {
+    /// ntfy.sh unless self-hosted
+    #[serde(default = "default_url")]
+    pub url: String,
+    pub topic: String,
+    /// for topics that need an access token
+    pub token: Option<String>,
+    /// 1 (min) to 5 (max)
+    #[serde(default = "default_warning_priority")]
+    pub warning_priority: u8,
+    #[serde(default = "default_critical_priority")]
+    pub critical_priority: u8,
+}
+
+fn default_url() -> String {
+    "https://ntfy.sh".to_owned()
+}
+
+fn default_warning_priority() -> u8 {
+    3
+}
+
+fn default_critical_priority() -> u8 {
+    5
+}
+
+const REPORT_PRIORITY: u8 = 2;
+
+/// Publishes each alert to an ntfy topic
+#[derive(Debug, Clone)]
+pub struct NtfyNotifier {
+    http_client: reqwest::Client,
+    settings: NtfySettings,
+}
+
+impl NtfyNotifier {
+    pub fn new(http_client: reqwest::Client, settings: NtfySettings) -> NtfyNotifier {
+        NtfyNotifier {
+            http_client,
+            settings,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for NtfyNotifier {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    async fn notify(&mut self, alerts: &[Alert]) -> Result<(), NotifierError> {
+        for alert in alerts {
+            let priority = match alert.severity {
+                Severity::Warning => self.settings.warning_priority,
+                Severity::Critical => self.settings.critical_priority,
+            };
+            let hostname = alert.tags.get("hostname").map(String::as_str);
+            self.publish(
+                hostname.unwrap_or("modem"),
+                priority,
+                &format!("{},{}", alert.severity.as_str(), alert.kind.as_str()),
+                alert.message.clone(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// At low priority, since nothing's wrong yet. Long reports turn into an attachment, which is
+    /// fine.
+    async fn report(&mut self, report: &Report) -> Result<(), NotifierError> {
+        self.publish(
+            &report.title(),
+            REPORT_PRIORITY,
+            &format!("report,{}", report.period.as_str()),
+            report.text(),
+        )
+        .await
+    }
+}
+
+impl NtfyNotifier {
+    async fn publish(
+        &self,
+        title: &str,
+        priority: u8,
+        tags: &str,
+        message: String,
+    ) -> Result<(), NotifierError> {
+        let url = format!(
+            "{}/{}",
+            self.settings.url.trim_end_matches('/'),
+            self.settings.topic
+        );
+        let mut request = self
+            .http_client
+            .post(&url)
+            .header("Title", title)
+            .header("Priority", priority.to_string())
+            .header("Tags", tags)
+            .body(message);
+        if let Some(token) = &self.settings.token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
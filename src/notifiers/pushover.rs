@@ -0,0 +1,115 @@
+use super::{Notifier, NotifierError};
+use crate::alerts::{Alert, Severity};
+use crate::report::Report;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+const MESSAGES_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// `pushover:` in the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct PushoverSettings {
+    /// the application's API token
+    pub token: String,
+    /// the user or group key to send to
+    pub user: String,
+    /// -2 (silent) to 2 (emergency, repeated until it's acknowledged)
+    #[serde(default)]
+    pub warning_priority: i8,
+    #[serde(default = "default_critical_priority")]
+    pub critical_priority: i8,
+}
+
+fn default_critical_priority() -> i8 {
+    1
+}
+
+/// Emergency alerts repeat this often until they're acknowledged...
+const EMERGENCY_RETRY_SECONDS: u32 = 60;
+/// ...for up to this long
+const EMERGENCY_EXPIRE_SECONDS: u32 = 3600;
+/// quiet, since nothing's wrong yet
+const REPORT_PRIORITY: i8 = -1;
+
+/// Pushes each alert through Pushover
+#[derive(Debug, Clone)]
+pub struct PushoverNotifier {
+    http_client: reqwest::Client,
+    settings: PushoverSettings,
+}
+
+impl PushoverNotifier {
+    pub fn new(http_client: reqwest::Client, settings: PushoverSettings) -> PushoverNotifier {
+        PushoverNotifier {
+            http_client,
+            settings,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for PushoverNotifier {
+    fn name(&self) -> &'static str {
+        "pushover"
+    }
+
+    async fn notify(&mut self, alerts: &[Alert]) -> Result<(), NotifierError> {
+        for alert in alerts {
+            let priority = match alert.severity {
+                Severity::Warning => self.settings.warning_priority,
+                Severity::Critical => self.settings.critical_priority,
+            };
+            let hostname = alert.tags.get("hostname").map(String::as_str);
+            self.send(
+                hostname.unwrap_or("modem"),
+                &alert.message,
+                priority,
+                alert.time.timestamp(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Only the summary, without the channels, which wouldn't fit
+    async fn report(&mut self, report: &Report) -> Result<(), NotifierError> {
+        self.send(
+            &report.title(),
+            &report.summary(),
+            REPORT_PRIORITY,
+            report.end.timestamp(),
+        )
+        .await
+    }
+}
+
+impl PushoverNotifier {
+    async fn send(
+        &self,
+        title: &str,
+        message: &str,
+        priority: i8,
+        timestamp: i64,
+    ) -> Result<(), NotifierError> {
+        let mut form = vec![
+            ("token", self.settings.token.clone()),
+            ("user", self.settings.user.clone()),
+            ("title", title.to_owned()),
+            ("message", message.to_owned()),
+            ("priority", priority.to_string()),
+            ("timestamp", timestamp.to_string()),
+        ];
+        // Pushover refuses emergency messages without these
+        if priority >= 2 {
+            form.push(("retry", EMERGENCY_RETRY_SECONDS.to_string()));
+            form.push(("expire", EMERGENCY_EXPIRE_SECONDS.to_string()));
+        }
+        self.http_client
+            .post(MESSAGES_URL)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
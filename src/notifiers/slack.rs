@@ -0,0 +1,128 @@
+use super::{Notifier, NotifierError};
+use crate::alerts::{Alert, Severity};
+use crate::report::Report;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// `slack:` in the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlackSettings {
+    /// an incoming webhook, `https://hooks.slack.com/services/...`
+    pub webhook_url: String,
+}
+
+/// Slack won't take more than 50 blocks in one message, and each alert is two
+const ALERTS_PER_MESSAGE: usize = 25;
+/// or more than this many characters in a section
+const MAX_SECTION: usize = 3000;
+
+/// Posts alerts to a Slack channel as Block Kit messages
+#[derive(Debug, Clone)]
+pub struct SlackNotifier {
+    http_client: reqwest::Client,
+    settings: SlackSettings,
+}
+
+impl SlackNotifier {
+    pub fn new(http_client: reqwest::Client, settings: SlackSettings) -> SlackNotifier {
+        SlackNotifier {
+            http_client,
+            settings,
+        }
+    }
+}
+
+/// `&`, `<` and `>` are the only characters Slack wants escaped
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn blocks(alert: &Alert) -> [Value; 2] {
+    let icon = match alert.severity {
+        Severity::Warning => ":warning:",
+        Severity::Critical => ":rotating_light:",
+    };
+    let hostname = alert.tags.get("hostname").map(String::as_str);
+    let mut context = vec![alert.kind.as_str().to_owned(), alert.time.to_rfc3339()];
+    if let Some(serial_number) = alert.tags.get("serial_number") {
+        context.push(format!("serial {}", serial_number));
+    }
+    [
+        json!({
+            "type": "section",
+            "text": {
+                "type": "mrkdwn",
+                "text": format!(
+                    "{} *{}*: {}",
+                    icon,
+                    escape(hostname.unwrap_or("modem")),
+                    escape(&alert.message)
+                ),
+            },
+        }),
+        json!({
+            "type": "context",
+            "elements": [{ "type": "mrkdwn", "text": escape(&context.join(" | ")) }],
+        }),
+    ]
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn notify(&mut self, alerts: &[Alert]) -> Result<(), NotifierError> {
+        for alerts in alerts.chunks(ALERTS_PER_MESSAGE) {
+            self.post(json!({
+                // for notifications, which don't show blocks
+                "text": alerts
+                    .iter()
+                    .map(|alert| escape(&alert.to_string()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                "blocks": alerts.iter().flat_map(blocks).collect::<Vec<_>>(),
+            }))
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// A header, then the report as a code block, cut short if there are too many channels
+    async fn report(&mut self, report: &Report) -> Result<(), NotifierError> {
+        let text: String = escape(&report.text())
+            .chars()
+            .take(MAX_SECTION - 6)
+            .collect();
+        self.post(json!({
+            "text": escape(&report.title()),
+            "blocks": [
+                {
+                    "type": "header",
+                    "text": { "type": "plain_text", "text": report.title() },
+                },
+                {
+                    "type": "section",
+                    "text": { "type": "mrkdwn", "text": format!("```{}```", text) },
+                },
+            ],
+        }))
+        .await
+    }
+}
+
+impl SlackNotifier {
+    async fn post(&self, body: Value) -> Result<(), NotifierError> {
+        self.http_client
+            .post(&self.settings.webhook_url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
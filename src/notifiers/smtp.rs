@@ -0,0 +1,157 @@
+use super::{Notifier, NotifierError};
+use crate::alerts::Alert;
+use crate::report::Report;
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::{Mailbox, MessageBuilder, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// upgrade a plain connection, usually on 587
+    #[default]
+    Starttls,
+    /// TLS from the start, usually on 465
+    Tls,
+    /// nothing, for a relay on localhost
+    None,
+}
+
+/// `smtp:` in the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    /// whatever's usual for `tls` if unset
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// `modem-scraper <scraper@example.com>` or just the address
+    pub from: String,
+    pub to: Vec<String>,
+    /// collect alerts for this long and send them as one email, so a flapping modem doesn't send
+    /// dozens. 0 sends each scrape's alerts straight away.
+    #[serde(default)]
+    pub digest_seconds: u64,
+}
+
+/// Emails alerts, either as they happen or as a digest
+#[derive(Clone)]
+pub struct SmtpNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    digest: Duration,
+    /// alerts waiting for the digest to go out, and when the first of them came in
+    pending: Vec<Alert>,
+    pending_since: Option<Instant>,
+}
+
+impl SmtpNotifier {
+    pub fn new(settings: &SmtpSettings) -> Result<SmtpNotifier, NotifierError> {
+        let mut builder = match settings.tls {
+            SmtpTls::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)?
+            }
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)?,
+            SmtpTls::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host)
+            }
+        };
+        if let Some(port) = settings.port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &settings.username {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                settings.password.clone().unwrap_or_default(),
+            ));
+        }
+        Ok(SmtpNotifier {
+            transport: builder.build(),
+            from: settings.from.parse()?,
+            to: settings
+                .to
+                .iter()
+                .map(|to| to.parse())
+                .collect::<Result<_, _>>()?,
+            digest: Duration::from_secs(settings.digest_seconds),
+            pending: Vec::new(),
+            pending_since: None,
+        })
+    }
+
+    fn message(&self, subject: String) -> MessageBuilder {
+        let mut message = Message::builder().from(self.from.clone()).subject(subject);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        message
+    }
+
+    async fn send(&self, alerts: &[Alert]) -> Result<(), NotifierError> {
+        let subject = match alerts {
+            [alert] => alert.to_string(),
+            alerts => format!("{} modem alerts", alerts.len()),
+        };
+        let body: Vec<String> = alerts
+            .iter()
+            .map(|alert| format!("{} {}", alert.time.to_rfc3339(), alert))
+            .collect();
+        let message = self
+            .message(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body.join("\n"))?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn notify(&mut self, alerts: &[Alert]) -> Result<(), NotifierError> {
+        if self.digest.is_zero() {
+            return self.send(alerts).await;
+        }
+        self.pending.extend_from_slice(alerts);
+        self.pending_since.get_or_insert_with(Instant::now);
+        Ok(())
+    }
+
+    /// Sends the digest once it's been collecting long enough. If that fails it's kept to try
+    /// again next time.
+    async fn flush(&mut self) -> Result<(), NotifierError> {
+        match self.pending_since {
+            Some(pending_since) if pending_since.elapsed() >= self.digest => {
+                self.send(&self.pending).await?;
+                self.pending.clear();
+                self.pending_since = None;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// As HTML, with the text version for mail clients that don't show HTML. Never held back for
+    /// the digest.
+    async fn report(&mut self, report: &Report) -> Result<(), NotifierError> {
+        let message = self
+            .message(report.title())
+            .multipart(MultiPart::alternative_plain_html(
+                report.text(),
+                report.html(),
+            ))?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use super::{render, Notifier, NotifierError};
+use crate::alerts::Alert;
+use crate::report::Report;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use modem_scraper_lib::RetryPolicy;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// One entry of `webhooks:`
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookSettings {
+    pub url: String,
+    /// JSON body with `{{field}}`s from [Alert::fields], which are escaped to go inside a JSON
+    /// string. The whole alert if unset.
+    pub template: Option<String>,
+    /// signs the body with HMAC-SHA256, sent as `X-Signature-256: sha256=<hex>`
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+/// POSTs each alert as JSON
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    http_client: reqwest::Client,
+    settings: WebhookSettings,
+}
+
+impl WebhookNotifier {
+    pub fn new(http_client: reqwest::Client, settings: WebhookSettings) -> WebhookNotifier {
+        WebhookNotifier {
+            http_client,
+            settings,
+        }
+    }
+
+    fn body(&self, alert: &Alert) -> String {
+        match &self.settings.template {
+            Some(template) => render(template, &alert.fields(), |value| {
+                // a JSON string, minus its quotes
+                let quoted = json!(value).to_string();
+                quoted[1..quoted.len() - 1].to_owned()
+            }),
+            None => json!({
+                "kind": alert.kind,
+                "severity": alert.severity,
+                "time": alert.time,
+                "hostname": alert.tags.get("hostname"),
+                "serial_number": alert.tags.get("serial_number"),
+                "channel": alert.channel,
+                "value": alert.value,
+                "threshold": alert.threshold,
+                "message": alert.message,
+                "tags": alert.tags,
+            })
+            .to_string(),
+        }
+    }
+
+    async fn post(&self, body: &str) -> Result<(), NotifierError> {
+        let mut request = self
+            .http_client
+            .post(&self.settings.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_owned());
+        for (name, value) in &self.settings.headers {
+            request = request.header(name, value);
+        }
+        if let Some(secret) = &self.settings.secret {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+            mac.update(body.as_bytes());
+            let signature = hex::encode(mac.finalize().into_bytes());
+            request = request.header("X-Signature-256", format!("sha256={}", signature));
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Retried per `retry`
+    async fn post_retrying(&self, body: &str) -> Result<(), NotifierError> {
+        let mut attempt = 1;
+        loop {
+            match self.post(body).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < self.settings.retry.max_attempts => {
+                    warn!("Webhook to {} failed, retrying: {}", self.settings.url, e);
+                    tokio::time::sleep(self.settings.retry.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    /// Each alert is retried per `retry`, and the first one that still fails gives up on the rest
+    async fn notify(&mut self, alerts: &[Alert]) -> Result<(), NotifierError> {
+        for alert in alerts {
+            self.post_retrying(&self.body(alert)).await?;
+        }
+        Ok(())
+    }
+
+    /// The whole report as JSON, under `report` so it can be told apart from an alert. `template`
+    /// is only for alerts.
+    async fn report(&mut self, report: &Report) -> Result<(), NotifierError> {
+        let body = json!({ "report": report, "text": report.text() }).to_string();
+        self.post_retrying(&body).await
+    }
+}
@@ -0,0 +1,88 @@
+use crate::scraped::ScrapedMetrics;
+use chrono::{DateTime, Utc};
+use log::Level;
+use modem_scraper_lib::payloads::LogEntry;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Internet outages since the scraper started, going by what the modem says about its connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutageStats {
+    /// as of this scrape
+    pub down: bool,
+    /// including one that's still going
+    pub outages: u64,
+    /// across every outage, including one that's still going
+    pub downtime: Duration,
+}
+
+/// Notices the modem losing its internet connection, and keeps count of how often and for how
+/// long. One per modem.
+#[derive(Debug, Default)]
+pub struct OutageTracker {
+    /// when the current outage started
+    down_since: Option<DateTime<Utc>>,
+    outages: u64,
+    /// across the outages that are over
+    downtime: Duration,
+}
+
+impl OutageTracker {
+    /// Sets `scraped.outages`, and returns a log entry when an outage starts or ends. The
+    /// internet's down when the modem says it isn't connected or doesn't have network access. A
+    /// scrape that fails doesn't count, since the modem itself could be what's down. Models that
+    /// report neither never have outages.
+    pub fn update(&mut self, scraped: &mut ScrapedMetrics) -> Option<LogEntry> {
+        let status = &scraped.metrics.status;
+        if status.internet_connected.is_none() && status.network_access.is_none() {
+            return None;
+        }
+        let down = status.internet_connected == Some(false) || status.network_access == Some(false);
+
+        let now = Utc::now();
+        let log_entry = match (self.down_since, down) {
+            (None, true) => {
+                self.down_since = Some(now);
+                self.outages += 1;
+                let message = "Internet connection lost".to_owned();
+                warn!("{}", message);
+                Some(LogEntry {
+                    timestamp: now,
+                    level: Level::Warn,
+                    message,
+                    priority: None,
+                })
+            }
+            (Some(down_since), false) => {
+                self.down_since = None;
+                let outage = (now - down_since).to_std().unwrap_or_default();
+                self.downtime += outage;
+                let message = format!(
+                    "Internet connection back after {}s, down since {}",
+                    outage.as_secs(),
+                    down_since.to_rfc3339()
+                );
+                info!("{}", message);
+                Some(LogEntry {
+                    timestamp: now,
+                    level: Level::Info,
+                    message,
+                    priority: None,
+                })
+            }
+            _ => None,
+        };
+
+        let current = self
+            .down_since
+            .and_then(|down_since| (now - down_since).to_std().ok())
+            .unwrap_or_default();
+        scraped.outages = Some(OutageStats {
+            down,
+            outages: self.outages,
+            downtime: self.downtime + current,
+        });
+        log_entry
+    }
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{instrument, warn};
+
+/// One round of pings to a target past the modem
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProbeResult {
+    pub target: String,
+    /// `icmp` or `tcp`
+    pub method: String,
+    pub sent: u32,
+    pub received: u32,
+    /// round trip times of the replies, `None` without any
+    pub rtt_min: Option<Duration>,
+    pub rtt_avg: Option<Duration>,
+    pub rtt_max: Option<Duration>,
+}
+
+impl ProbeResult {
+    /// percent of the pings that went unanswered
+    pub fn loss(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        f64::from(self.sent - self.received) / f64::from(self.sent) * 100.0
+    }
+}
+
+pub type ProbeError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeMethod {
+    /// through the system's `ping`, which can send ICMP without root
+    #[default]
+    Icmp,
+    /// times how long a TCP connection takes to open, for networks that drop ICMP
+    Tcp,
+}
+
+impl ProbeMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProbeMethod::Icmp => "icmp",
+            ProbeMethod::Tcp => "tcp",
+        }
+    }
+}
+
+/// One entry of `probes:` under a modem
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ProbeSettings {
+    /// a hostname or IP, e.g. the ISP's gateway or `1.1.1.1`
+    pub target: String,
+    #[serde(default)]
+    pub method: ProbeMethod,
+    /// for `tcp`
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// pings per round
+    #[serde(default = "default_count")]
+    pub count: u32,
+    /// before a ping counts as lost
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_port() -> u16 {
+    443
+}
+
+fn default_count() -> u32 {
+    5
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+/// `time=12.3 ms` out of a line of `ping`'s output, which is the same on Linux, the BSDs and macOS
+fn reply_rtt(line: &str) -> Option<Duration> {
+    let (_, rest) = line.split_once("time=")?;
+    let ms: f64 = rest
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .next()?
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs_f64(ms / 1000.0))
+}
+
+async fn icmp(settings: &ProbeSettings) -> Result<Vec<Duration>, ProbeError> {
+    let output = tokio::process::Command::new("ping")
+        .args(["-n", "-c", &settings.count.to_string(), &settings.target])
+        .kill_on_drop(true)
+        .output();
+    // `ping` sends one a second, and its own timeout flag differs between platforms
+    let deadline =
+        Duration::from_secs(u64::from(settings.count)) + Duration::from_millis(settings.timeout_ms);
+    let output = tokio::time::timeout(deadline, output)
+        .await
+        .map_err(|_| format!("ping {} took longer than {:?}", settings.target, deadline))??;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let rtts: Vec<Duration> = stdout.lines().filter_map(reply_rtt).collect();
+    // exits 1 when nothing answered, which is 100% loss rather than an error
+    if rtts.is_empty() && !output.status.success() && output.status.code() != Some(1) {
+        return Err(format!(
+            "ping {} exited with {}: {}",
+            settings.target,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(rtts)
+}
+
+async fn tcp(settings: &ProbeSettings) -> Result<Vec<Duration>, ProbeError> {
+    // resolved once, so DNS isn't in the round trip
+    let address: SocketAddr = tokio::net::lookup_host((settings.target.as_str(), settings.port))
+        .await?
+        .next()
+        .ok_or_else(|| format!("{} didn't resolve", settings.target))?;
+    let timeout = Duration::from_millis(settings.timeout_ms);
+    let mut rtts = Vec::new();
+    for _ in 0..settings.count {
+        let started = Instant::now();
+        // refused still means it got there and back
+        let connected = tokio::time::timeout(timeout, TcpStream::connect(address)).await;
+        match connected {
+            Ok(Ok(_)) => rtts.push(started.elapsed()),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                rtts.push(started.elapsed())
+            }
+            Ok(Err(_)) | Err(_) => {}
+        }
+    }
+    Ok(rtts)
+}
+
+/// One round of `count` pings
+#[instrument(skip(settings), fields(target = %settings.target, method = settings.method.as_str()))]
+pub async fn probe(settings: &ProbeSettings) -> Result<ProbeResult, ProbeError> {
+    let rtts = match settings.method {
+        ProbeMethod::Icmp => icmp(settings).await?,
+        ProbeMethod::Tcp => tcp(settings).await?,
+    };
+    let received = rtts.len() as u32;
+    Ok(ProbeResult {
+        target: settings.target.clone(),
+        method: settings.method.as_str().to_owned(),
+        sent: settings.count.max(received),
+        received,
+        rtt_min: rtts.iter().min().copied(),
+        rtt_avg: (received > 0).then(|| rtts.iter().sum::<Duration>() / received),
+        rtt_max: rtts.iter().max().copied(),
+    })
+}
+
+/// Probes every target once an interval in the background, and keeps each one's latest round for
+/// the scrapes. One per modem that has `probes:`.
+#[derive(Debug)]
+pub struct Prober {
+    /// in the same order as the settings, `None` until the first round's done
+    latest: Arc<Mutex<Vec<Option<ProbeResult>>>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Prober {
+    pub fn spawn(probes: Vec<ProbeSettings>, every: Duration, device_address: &str) -> Prober {
+        let latest = Arc::new(Mutex::new(vec![None; probes.len()]));
+        let tasks = probes
+            .into_iter()
+            .enumerate()
+            .map(|(i, settings)| {
+                let latest = latest.clone();
+                let device_address = device_address.to_owned();
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(every);
+                    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                    loop {
+                        interval.tick().await;
+                        let result = probe(&settings).await;
+                        if let Err(e) = &result {
+                            warn!(
+                                "Unable to probe {} for {}: {}",
+                                settings.target, device_address, e
+                            );
+                        }
+                        // a stale round would look like everything's fine
+                        latest.lock().unwrap()[i] = result.ok();
+                    }
+                })
+            })
+            .collect();
+        Prober { latest, tasks }
+    }
+
+    /// The latest round of each probe that's had one
+    pub fn latest(&self) -> Vec<ProbeResult> {
+        self.latest
+            .lock()
+            .unwrap()
+            .iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+impl Drop for Prober {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::scraped::ScrapedMetrics;
+use chrono::Utc;
+use log::Level;
+use modem_scraper_lib::payloads::LogEntry;
+use std::time::Duration;
+use tracing::warn;
+
+/// Notices the modem's uptime going down between scrapes. One per modem.
+#[derive(Debug, Default)]
+pub struct RebootDetector {
+    previous_uptime: Option<Duration>,
+}
+
+impl RebootDetector {
+    /// Sets `scraped.rebooted_after` if the modem rebooted since the last scrape, and returns a log
+    /// entry saying so. Models that don't report uptime never reboot as far as this is concerned.
+    pub fn update(&mut self, scraped: &mut ScrapedMetrics) -> Option<LogEntry> {
+        let uptime = scraped.metrics.uptime?;
+        let previous_uptime = self.previous_uptime.replace(uptime)?;
+        if uptime >= previous_uptime {
+            return None;
+        }
+
+        let message = format!(
+            "Modem rebooted after {}s of uptime",
+            previous_uptime.as_secs()
+        );
+        warn!("{}", message);
+        scraped.rebooted_after = Some(previous_uptime);
+        Some(LogEntry {
+            timestamp: scraped.metrics.time.unwrap_or_else(Utc::now),
+            level: Level::Warn,
+            message,
+            priority: None,
+        })
+    }
+}
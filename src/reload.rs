@@ -0,0 +1,78 @@
+//! Watches the config file, so tuning it doesn't need a restart
+
+use crate::cli::Cli;
+use crate::settings::Settings;
+use config::Config;
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::error;
+
+/// Editors tend to write a file in a few steps, so wait for them to finish before reading it
+const SETTLE: Duration = Duration::from_millis(500);
+
+fn top_level(config: &Config) -> Option<HashMap<String, config::Value>> {
+    config.clone().try_deserialize().ok()
+}
+
+/// Sends the new settings every time the config file changes to something valid. Changes that
+/// don't load are logged and skipped, so a typo doesn't take the scraper down.
+pub fn watch(cli: Cli) -> notify::Result<mpsc::Receiver<Settings>> {
+    let path = PathBuf::from(&cli.config);
+    // watch the directory rather than the file, since editors replace it instead of writing to it
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+        _ => Path::new(".").to_owned(),
+    };
+    let file_name = path.file_name().map(ToOwned::to_owned);
+
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if event
+                .paths
+                .iter()
+                .any(|path| path.file_name() == file_name.as_deref())
+            {
+                let _ = event_tx.send(());
+            }
+        }
+    })?;
+    watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+
+    let (settings_tx, settings_rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        // events stop when this is dropped
+        let _watcher = watcher;
+        let mut previous = cli.load_config().ok().as_ref().and_then(top_level);
+        while event_rx.recv().await.is_some() {
+            tokio::time::sleep(SETTLE).await;
+            while event_rx.try_recv().is_ok() {}
+
+            let config = match cli.load_config() {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("Unable to reload {}: {}", cli.config, e);
+                    continue;
+                }
+            };
+            // saving without changing anything shouldn't set everything up again
+            let current = top_level(&config);
+            if current == previous {
+                continue;
+            }
+            previous = current;
+            match Settings::load(&config) {
+                Ok(settings) => {
+                    if settings_tx.send(settings).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => error!("Not reloading {}: {}", cli.config, e),
+            }
+        }
+    });
+    Ok(settings_rx)
+}
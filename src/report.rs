@@ -0,0 +1,421 @@
+use crate::notifiers::{self, Notifier};
+use crate::outages::OutageStats;
+use crate::scraped::ScrapedMetrics;
+use chrono::{DateTime, Datelike, Days, Local, NaiveTime, Utc};
+use modem_scraper_lib::payloads::Channel;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{error, info};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    /// at midnight
+    #[default]
+    Daily,
+    /// at midnight going into Monday
+    Weekly,
+}
+
+impl ReportPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportPeriod::Daily => "daily",
+            ReportPeriod::Weekly => "weekly",
+        }
+    }
+
+    /// The first midnight (local to the scraper) after `time` that a report is due
+    fn next_due(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let today = time.with_timezone(&Local).date_naive();
+        let days = match self {
+            ReportPeriod::Daily => 1,
+            ReportPeriod::Weekly => 7 - u64::from(today.weekday().num_days_from_monday()),
+        };
+        let midnight = (today + Days::new(days)).and_time(NaiveTime::MIN);
+        // an hour late if DST skipped midnight, which is close enough
+        [midnight, midnight + chrono::Duration::hours(1)]
+            .into_iter()
+            .find_map(|midnight| midnight.and_local_timezone(Local).earliest())
+            .map(|midnight| midnight.with_timezone(&Utc))
+            .unwrap_or_else(|| time + chrono::Duration::days(days as i64))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Html,
+}
+
+/// `report:` in the config
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ReportSettings {
+    #[serde(default)]
+    pub period: ReportPeriod,
+    /// of the files in `directory`. Emails have both, chat services get text.
+    #[serde(default)]
+    pub format: ReportFormat,
+    /// writes each report here, as `<hostname>-<date>.txt` or `.html`
+    pub directory: Option<PathBuf>,
+    /// through every notifier as well
+    #[serde(default = "default_notify")]
+    pub notify: bool,
+}
+
+fn default_notify() -> bool {
+    true
+}
+
+/// Lowest, average and highest over the period
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Spread {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Accumulator {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+}
+
+impl Accumulator {
+    fn new(value: f64) -> Accumulator {
+        Accumulator {
+            min: value,
+            max: value,
+            sum: value,
+            count: 1,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn spread(&self) -> Spread {
+        Spread {
+            min: self.min,
+            avg: self.sum / self.count as f64,
+            max: self.max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChannelSummary {
+    /// e.g. `downstream 12`
+    pub channel: String,
+    /// dBmV
+    pub power: Spread,
+    /// dB, downstream only
+    pub snr: Option<Spread>,
+}
+
+/// How the line did over one period, for one modem
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub period: ReportPeriod,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub tags: HashMap<String, String>,
+    pub scrapes: u64,
+    /// locked channels only, downstream first
+    pub channels: Vec<ChannelSummary>,
+    pub corrected: u64,
+    pub uncorrectables: u64,
+    pub reboots: u64,
+    /// that started during the period
+    pub outages: u64,
+    pub downtime: Duration,
+}
+
+/// `1h 2m 3s`
+fn duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match (seconds / 3600, seconds % 3600 / 60, seconds % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, s) => format!("{}h {}m {}s", h, m, s),
+    }
+}
+
+fn local(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+fn spread(spread: Option<Spread>) -> String {
+    match spread {
+        Some(Spread { min, avg, max }) => format!("{:.1} / {:.1} / {:.1}", min, avg, max),
+        None => String::new(),
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl Report {
+    pub fn hostname(&self) -> &str {
+        self.tags
+            .get("hostname")
+            .map(String::as_str)
+            .unwrap_or("modem")
+    }
+
+    /// e.g. `Daily line-quality report for 192.168.100.1`
+    pub fn title(&self) -> String {
+        let period = match self.period {
+            ReportPeriod::Daily => "Daily",
+            ReportPeriod::Weekly => "Weekly",
+        };
+        format!("{} line-quality report for {}", period, self.hostname())
+    }
+
+    /// The period, outages, reboots and codewords, without the channels. A few short lines.
+    pub fn summary(&self) -> String {
+        format!(
+            concat!(
+                "{} to {}, {} scrapes\n",
+                "Outages: {}, {} down\n",
+                "Reboots: {}\n",
+                "Codewords: {} corrected, {} uncorrectable"
+            ),
+            local(self.start),
+            local(self.end),
+            self.scrapes,
+            self.outages,
+            duration(self.downtime),
+            self.reboots,
+            self.corrected,
+            self.uncorrectables,
+        )
+    }
+
+    /// [Report::summary], then a table of the channels. Meant for a monospaced font.
+    pub fn text(&self) -> String {
+        let row = |channel: &str, power: &str, snr: &str| {
+            format!("{:<20} {:<28} {}\n", channel, power, snr)
+        };
+        let mut text = format!("{}\n\n", self.summary());
+        text.push_str(&row(
+            "Channel",
+            "Power min/avg/max (dBmV)",
+            "SNR min/avg/max (dB)",
+        ));
+        for channel in &self.channels {
+            text.push_str(&row(
+                &channel.channel,
+                &spread(Some(channel.power)),
+                &spread(channel.snr),
+            ));
+        }
+        text
+    }
+
+    pub fn html(&self) -> String {
+        let mut html = format!(
+            concat!(
+                "<!DOCTYPE html>\n",
+                "<html><head><meta charset=\"utf-8\"><title>{0}</title></head><body>\n",
+                "<h1>{0}</h1>\n<p>{1}</p>\n<table>\n",
+                "<tr><th>Channel</th><th>Power min/avg/max (dBmV)</th>",
+                "<th>SNR min/avg/max (dB)</th></tr>\n"
+            ),
+            escape_html(&self.title()),
+            escape_html(&self.summary()).replace('\n', "<br>\n")
+        );
+        for channel in &self.channels {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(&channel.channel),
+                spread(Some(channel.power)),
+                spread(channel.snr)
+            );
+        }
+        html.push_str("</table>\n</body></html>\n");
+        html
+    }
+
+    /// As `<hostname>-<date the period started>.txt` or `.html`
+    pub fn write(&self, directory: &Path, format: ReportFormat) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(directory)?;
+        let (extension, contents) = match format {
+            ReportFormat::Text => ("txt", self.text()),
+            ReportFormat::Html => ("html", self.html()),
+        };
+        let path = directory.join(format!(
+            "{}-{}.{}",
+            self.hostname().replace(['/', '\\', ':'], "_"),
+            self.start.with_timezone(&Local).format("%Y-%m-%d"),
+            extension
+        ));
+        std::fs::write(&path, contents)?;
+        Ok(path)
+    }
+}
+
+/// Collects every scrape into a [Report] that's due once the period's over. One per modem.
+#[derive(Debug)]
+pub struct ReportBuilder {
+    /// `None` when there's no `report:`, so nothing's collected
+    settings: Option<ReportSettings>,
+    start: DateTime<Utc>,
+    due: DateTime<Utc>,
+    scrapes: u64,
+    /// by type and channel id, so downstream comes first and channels are in order
+    channels: BTreeMap<(&'static str, u8), (Accumulator, Option<Accumulator>)>,
+    corrected: u64,
+    uncorrectables: u64,
+    reboots: u64,
+    /// as of the end of the previous period, to count from
+    outages_before: OutageStats,
+    outages_latest: OutageStats,
+}
+
+impl ReportBuilder {
+    pub fn new(settings: Option<ReportSettings>) -> ReportBuilder {
+        let now = Utc::now();
+        let period = settings.as_ref().map(|s| s.period).unwrap_or_default();
+        ReportBuilder {
+            settings,
+            start: now,
+            due: period.next_due(now),
+            scrapes: 0,
+            channels: BTreeMap::new(),
+            corrected: 0,
+            uncorrectables: 0,
+            reboots: 0,
+            outages_before: OutageStats::default(),
+            outages_latest: OutageStats::default(),
+        }
+    }
+
+    /// After a reload. A new period only changes when the current report's due.
+    pub fn update_settings(&mut self, settings: Option<ReportSettings>) {
+        if let Some(settings) = &settings {
+            self.due = settings.period.next_due(self.start);
+        }
+        self.settings = settings;
+    }
+
+    /// Needs the codeword deltas, reboot and outages filled in
+    pub fn record(&mut self, scraped: &ScrapedMetrics) {
+        if self.settings.is_none() {
+            return;
+        }
+        self.scrapes += 1;
+        for channel in &scraped.metrics.channels {
+            let (key, lock_status, power, snr) = match channel {
+                Channel::Downstream(c) => (
+                    ("downstream", c.channel_id),
+                    c.lock_status,
+                    c.power,
+                    Some(c.snr),
+                ),
+                Channel::Upstream(c) => (("upstream", c.channel_id), c.lock_status, c.power, None),
+                Channel::OfdmaUpstream(c) => (
+                    ("ofdma upstream", c.channel_id),
+                    c.lock_status,
+                    c.power,
+                    None,
+                ),
+            };
+            // whatever an unlocked channel reports is meaningless
+            if !lock_status {
+                continue;
+            }
+            self.channels
+                .entry(key)
+                .and_modify(|(power_spread, snr_spread)| {
+                    power_spread.add(power);
+                    if let (Some(snr_spread), Some(snr)) = (snr_spread, snr) {
+                        snr_spread.add(snr);
+                    }
+                })
+                .or_insert_with(|| (Accumulator::new(power), snr.map(Accumulator::new)));
+        }
+        for delta in scraped.codeword_deltas.values() {
+            self.corrected += delta.corrected;
+            self.uncorrectables += delta.uncorrectables;
+        }
+        self.reboots += u64::from(scraped.rebooted_after.is_some());
+        if let Some(outages) = scraped.outages {
+            self.outages_latest = outages;
+        }
+    }
+
+    /// The report, once the period's over, and starts the next one
+    pub fn take(&mut self, tags: &HashMap<String, String>) -> Option<Report> {
+        let settings = self.settings.as_ref()?;
+        let now = Utc::now();
+        if now < self.due {
+            return None;
+        }
+        let report = Report {
+            period: settings.period,
+            start: self.start,
+            end: now,
+            tags: tags.clone(),
+            scrapes: self.scrapes,
+            channels: self
+                .channels
+                .iter()
+                .map(|((kind, id), (power, snr))| ChannelSummary {
+                    channel: format!("{} {}", kind, id),
+                    power: power.spread(),
+                    snr: snr.as_ref().map(Accumulator::spread),
+                })
+                .collect(),
+            corrected: self.corrected,
+            uncorrectables: self.uncorrectables,
+            reboots: self.reboots,
+            outages: self.outages_latest.outages - self.outages_before.outages,
+            downtime: self
+                .outages_latest
+                .downtime
+                .saturating_sub(self.outages_before.downtime),
+        };
+        *self = ReportBuilder {
+            outages_before: self.outages_latest,
+            outages_latest: self.outages_latest,
+            ..ReportBuilder::new(self.settings.take())
+        };
+        Some(report)
+    }
+}
+
+/// Writes `report` to the directory and sends it through the notifiers, whichever are configured.
+/// Failures are logged and skipped.
+pub async fn deliver(
+    settings: &ReportSettings,
+    report: &Report,
+    notifiers: &mut [Box<dyn Notifier>],
+) {
+    if let Some(directory) = &settings.directory {
+        match report.write(directory, settings.format) {
+            Ok(path) => info!("Wrote {} to {}", report.title(), path.display()),
+            Err(e) => error!("Unable to write {}: {}", report.title(), e),
+        }
+    }
+    if settings.notify {
+        notifiers::report(notifiers, report).await;
+    }
+}
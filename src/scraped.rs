@@ -0,0 +1,349 @@
+use crate::bonding::BondingChanges;
+use crate::outages::OutageStats;
+use crate::probe::ProbeResult;
+use crate::speedtest::SpeedtestResult;
+use modem_scraper_lib::events;
+use modem_scraper_lib::payloads::{Channel, CodewordDelta, LogEvent, Metrics, RequestStats};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use telegraf::{IntoFieldData, Point};
+
+/// The scraper's own numbers, so whoever watches the modem can watch the scraper too. Counts are
+/// since startup.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScraperStats {
+    /// how long the latest metrics request took, relogins and retries included
+    pub scrape_duration: Duration,
+    /// by action. HNAP models only.
+    pub requests: BTreeMap<String, RequestStats>,
+    /// HNAP models only
+    pub logins: u64,
+    /// replies that arrived but couldn't be parsed
+    pub parse_failures: u64,
+    /// failed writes, across every output
+    pub sink_errors: u64,
+    /// log entries remembered by outputs that skip ones they've already sent
+    pub dedup_entries: Option<u64>,
+    /// whether the modem is being left alone after failing too many times in a row
+    pub circuit_open: bool,
+    /// how many times that's happened
+    pub circuit_trips: u64,
+}
+
+/// A scrape's [Metrics], plus what the scraper worked out around it: changes since the previous
+/// scrape, counts from the logs, speedtests and probes, and its own stats. Serializes so it can be
+/// kept on disk until it's sent.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScrapedMetrics {
+    /// as the modem client returned them
+    pub metrics: Metrics,
+    /// by downstream channel id
+    pub codeword_deltas: HashMap<u8, CodewordDelta>,
+    /// uptime before a reboot, when the uptime went down since the previous scrape
+    pub rebooted_after: Option<Duration>,
+    /// how many of each [LogEvent] were logged since the previous scrape
+    pub log_events: BTreeMap<LogEvent, u64>,
+    /// how many lines with each DOCSIS event ID were logged since the scraper started
+    pub event_codes: BTreeMap<u32, u64>,
+    /// firmware version before an update the scraper noticed since the previous scrape
+    pub firmware_changed_from: Option<String>,
+    pub scraper: Option<ScraperStats>,
+    /// for models that report their connection state
+    pub outages: Option<OutageStats>,
+    /// by direction, `downstream` or `upstream`, from the second scrape on
+    pub bonding: BTreeMap<String, BondingChanges>,
+    /// when a speedtest finished since the previous scrape
+    pub speedtest: Option<SpeedtestResult>,
+    /// the latest round of each latency probe
+    pub probes: Vec<ProbeResult>,
+}
+
+impl From<Metrics> for ScrapedMetrics {
+    fn from(metrics: Metrics) -> Self {
+        ScrapedMetrics {
+            metrics,
+            ..Default::default()
+        }
+    }
+}
+
+impl ScrapedMetrics {
+    /// [Channel::health], going by the channel's codewords since the previous scrape
+    pub fn channel_health(&self, channel: &Channel) -> f64 {
+        channel.health(channel.codeword_delta(&self.codeword_deltas))
+    }
+
+    /// [Metrics::health], going by the codewords since the previous scrape
+    pub fn health(&self) -> Option<f64> {
+        self.metrics.health(&self.codeword_deltas)
+    }
+
+    /// [ScrapedMetrics::log_events] as a `modem_log_events` point, or nothing if they aren't being counted
+    pub fn log_events_point(&self) -> Option<Point> {
+        if self.log_events.is_empty() {
+            return None;
+        }
+        let fields = self
+            .log_events
+            .iter()
+            .map(|(event, count)| {
+                (
+                    event.as_str().to_owned(),
+                    Box::new(*count) as Box<dyn IntoFieldData>,
+                )
+            })
+            .collect();
+        Some(Point::new(
+            "modem_log_events".to_owned(),
+            Vec::new(),
+            fields,
+            None,
+        ))
+    }
+
+    /// A `modem_log_event_code` point per [ScrapedMetrics::event_codes], tagged with the `event_id`. The
+    /// catalog's `description` goes along with the `count` when there is one.
+    pub fn event_code_points(&self) -> Vec<Point> {
+        self.event_codes
+            .iter()
+            .map(|(event_id, count)| {
+                let mut fields = vec![(
+                    "count".to_owned(),
+                    Box::new(*count) as Box<dyn IntoFieldData>,
+                )];
+                if let Some(event_code) = events::by_id(*event_id) {
+                    fields.push((
+                        "description".to_owned(),
+                        Box::new(event_code.description.to_owned()),
+                    ));
+                }
+                Point::new(
+                    "modem_log_event_code".to_owned(),
+                    vec![("event_id".to_owned(), event_id.to_string())],
+                    fields,
+                    None,
+                )
+            })
+            .collect()
+    }
+
+    /// A `modem_firmware_change` point if [ScrapedMetrics::firmware_changed_from] is set. The new version
+    /// is in the `firmware_version` tag.
+    pub fn firmware_change_point(&self) -> Option<Point> {
+        let previous_version = self.firmware_changed_from.clone()?;
+        Some(Point::new(
+            "modem_firmware_change".to_owned(),
+            Vec::new(),
+            vec![(
+                "previous_version".to_owned(),
+                Box::new(previous_version) as Box<dyn IntoFieldData>,
+            )],
+            None,
+        ))
+    }
+
+    /// A `modem_scraper` point from [ScrapedMetrics::scraper], plus a `modem_scraper_request` point tagged
+    /// with the `action` for each request latency
+    pub fn scraper_points(&self) -> Vec<Point> {
+        let Some(stats) = &self.scraper else {
+            return Vec::new();
+        };
+        let mut fields: Vec<(String, Box<dyn IntoFieldData>)> = vec![
+            (
+                "scrape_duration_seconds".to_owned(),
+                Box::new(stats.scrape_duration.as_secs_f64()),
+            ),
+            ("logins".to_owned(), Box::new(stats.logins)),
+            ("parse_failures".to_owned(), Box::new(stats.parse_failures)),
+            ("sink_errors".to_owned(), Box::new(stats.sink_errors)),
+            ("circuit_open".to_owned(), Box::new(stats.circuit_open)),
+            ("circuit_trips".to_owned(), Box::new(stats.circuit_trips)),
+        ];
+        if let Some(dedup_entries) = stats.dedup_entries {
+            fields.push(("dedup_entries".to_owned(), Box::new(dedup_entries)));
+        }
+        let mut points = vec![Point::new(
+            "modem_scraper".to_owned(),
+            Vec::new(),
+            fields,
+            None,
+        )];
+        for (action, request) in &stats.requests {
+            points.push(Point::new(
+                "modem_scraper_request".to_owned(),
+                vec![("action".to_owned(), action.clone())],
+                vec![
+                    (
+                        "latency_seconds".to_owned(),
+                        Box::new(request.latency.as_secs_f64()) as Box<dyn IntoFieldData>,
+                    ),
+                    (
+                        "response_bytes".to_owned(),
+                        Box::new(request.response_bytes),
+                    ),
+                    ("result".to_owned(), Box::new(request.result.clone())),
+                ],
+                None,
+            ));
+            points.extend(request.results.iter().map(|(result, count)| {
+                Point::new(
+                    "modem_scraper_request_results".to_owned(),
+                    vec![
+                        ("action".to_owned(), action.clone()),
+                        ("result".to_owned(), result.clone()),
+                    ],
+                    vec![(
+                        "count".to_owned(),
+                        Box::new(*count) as Box<dyn IntoFieldData>,
+                    )],
+                    None,
+                )
+            }));
+        }
+        points
+    }
+
+    /// A `modem_outages` point from [ScrapedMetrics::outages]
+    pub fn outage_point(&self) -> Option<Point> {
+        let outages = self.outages?;
+        Some(Point::new(
+            "modem_outages".to_owned(),
+            Vec::new(),
+            vec![
+                (
+                    "down".to_owned(),
+                    Box::new(outages.down) as Box<dyn IntoFieldData>,
+                ),
+                ("outages".to_owned(), Box::new(outages.outages)),
+                (
+                    "downtime_seconds".to_owned(),
+                    Box::new(outages.downtime.as_secs()),
+                ),
+            ],
+            None,
+        ))
+    }
+
+    /// A `modem_bonding` point per direction in [ScrapedMetrics::bonding], tagged with the `direction`.
+    /// The channel IDs that changed are only there when some did.
+    pub fn bonding_points(&self) -> Vec<Point> {
+        self.bonding
+            .iter()
+            .map(|(direction, changes)| {
+                let mut fields: Vec<(String, Box<dyn IntoFieldData>)> = vec![
+                    (
+                        "channels_added".to_owned(),
+                        Box::new(changes.added.len() as u64),
+                    ),
+                    (
+                        "channels_removed".to_owned(),
+                        Box::new(changes.removed.len() as u64),
+                    ),
+                    (
+                        "channels_added_total".to_owned(),
+                        Box::new(changes.added_total),
+                    ),
+                    (
+                        "channels_removed_total".to_owned(),
+                        Box::new(changes.removed_total),
+                    ),
+                ];
+                for (name, channel_ids) in [
+                    ("added_channel_ids", &changes.added),
+                    ("removed_channel_ids", &changes.removed),
+                ] {
+                    if !channel_ids.is_empty() {
+                        let channel_ids: Vec<String> =
+                            channel_ids.iter().map(u8::to_string).collect();
+                        fields.push((name.to_owned(), Box::new(channel_ids.join(","))));
+                    }
+                }
+                Point::new(
+                    "modem_bonding".to_owned(),
+                    vec![("direction".to_owned(), direction.clone())],
+                    fields,
+                    None,
+                )
+            })
+            .collect()
+    }
+
+    /// A `modem_speedtest` point from [ScrapedMetrics::speedtest]
+    pub fn speedtest_point(&self) -> Option<Point> {
+        let speedtest = self.speedtest.as_ref()?;
+        let mut fields: Vec<(String, Box<dyn IntoFieldData>)> = vec![
+            ("download_bps".to_owned(), Box::new(speedtest.download)),
+            ("upload_bps".to_owned(), Box::new(speedtest.upload)),
+            (
+                "latency_ms".to_owned(),
+                Box::new(speedtest.latency.as_secs_f64() * 1000.0),
+            ),
+        ];
+        if let Some(jitter) = speedtest.jitter {
+            fields.push((
+                "jitter_ms".to_owned(),
+                Box::new(jitter.as_secs_f64() * 1000.0),
+            ));
+        }
+        if let Some(packet_loss) = speedtest.packet_loss {
+            fields.push(("packet_loss_percent".to_owned(), Box::new(packet_loss)));
+        }
+        if let Some(server) = &speedtest.server {
+            fields.push(("server".to_owned(), Box::new(server.clone())));
+        }
+        Some(Point::new(
+            "modem_speedtest".to_owned(),
+            Vec::new(),
+            fields,
+            None,
+        ))
+    }
+
+    /// A `modem_probe` point per [ScrapedMetrics::probes], tagged with the `target` and `method`
+    pub fn probe_points(&self) -> Vec<Point> {
+        let ms = |rtt: Duration| rtt.as_secs_f64() * 1000.0;
+        self.probes
+            .iter()
+            .map(|probe| {
+                let mut fields: Vec<(String, Box<dyn IntoFieldData>)> = vec![
+                    ("sent".to_owned(), Box::new(probe.sent)),
+                    ("received".to_owned(), Box::new(probe.received)),
+                    ("loss_percent".to_owned(), Box::new(probe.loss())),
+                ];
+                for (name, rtt) in [
+                    ("rtt_min_ms", probe.rtt_min),
+                    ("rtt_avg_ms", probe.rtt_avg),
+                    ("rtt_max_ms", probe.rtt_max),
+                ] {
+                    if let Some(rtt) = rtt {
+                        fields.push((name.to_owned(), Box::new(ms(rtt))));
+                    }
+                }
+                Point::new(
+                    "modem_probe".to_owned(),
+                    vec![
+                        ("target".to_owned(), probe.target.clone()),
+                        ("method".to_owned(), probe.method.clone()),
+                    ],
+                    fields,
+                    None,
+                )
+            })
+            .collect()
+    }
+
+    /// A `modem_reboot` point if [ScrapedMetrics::rebooted_after] is set
+    pub fn reboot_point(&self) -> Option<Point> {
+        let rebooted_after = self.rebooted_after?;
+        Some(Point::new(
+            "modem_reboot".to_owned(),
+            Vec::new(),
+            vec![(
+                "previous_uptime_seconds".to_owned(),
+                Box::new(rebooted_after.as_secs()) as Box<dyn IntoFieldData>,
+            )],
+            None,
+        ))
+    }
+}
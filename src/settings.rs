@@ -0,0 +1,450 @@
+//! `config.yml`, typed. Every key is optional except `scrape_interval_seconds` and the modem's
+//! `device_address`.
+
+use crate::alerts::AlertSettings;
+use crate::breaker::CircuitBreakerSettings;
+use crate::notifiers::discord::DiscordSettings;
+use crate::notifiers::ntfy::NtfySettings;
+use crate::notifiers::pushover::PushoverSettings;
+use crate::notifiers::slack::SlackSettings;
+use crate::notifiers::smtp::SmtpSettings;
+use crate::notifiers::webhook::WebhookSettings;
+use crate::probe::ProbeSettings;
+use crate::report::ReportSettings;
+use crate::sinks::elasticsearch::ElasticsearchSettings;
+use crate::sinks::graphite::GraphiteSettings;
+use crate::sinks::influx::InfluxSettings;
+use crate::sinks::loki::{LineTemplate, LokiEncoding, LokiLogFields, LokiSplit};
+use crate::sinks::mqtt::MqttSettings;
+use crate::sinks::splunk::SplunkSettings;
+use crate::sinks::sqlite::SqliteSettings;
+use crate::sinks::stdout::StdoutFormat;
+use crate::sinks::victoriametrics::VictoriaMetricsSettings;
+use crate::speedtest::SpeedtestSettings;
+use crate::spool::SpoolSettings;
+use chrono_tz::Tz;
+use config::{Config, ConfigError};
+use modem_scraper_lib::payloads::LogLevels;
+use modem_scraper_lib::{HnapTimestamp, HnapTransport, LoginLimits, Model, RetryPolicy};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// One entry of `modems:`. Without that list, these keys are read from the top level instead.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ModemSettings {
+    pub device_address: String,
+    #[serde(default)]
+    pub device_username: String,
+    #[serde(default)]
+    pub device_password: String,
+    pub model: Option<Model>,
+    pub protocol: Option<String>,
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// PEM bundle of extra CAs to trust
+    pub ca_bundle: Option<String>,
+    /// hex SHA-256 of the modem's certificate
+    pub pinned_certificate: Option<String>,
+    /// `http://`, `https://`, `socks5://` or `socks5h://`, optionally with credentials
+    pub proxy_url: Option<String>,
+    /// for proxies that want basic auth, if they're not in `proxy_url`
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    pub connect_timeout_seconds: Option<u64>,
+    pub timeout_seconds: Option<u64>,
+    /// `json` or `xml`, for HNAP modems
+    #[serde(default)]
+    pub hnap_transport: HnapTransport,
+    /// `auto`, `millis` or `seconds`, for HNAP modems
+    #[serde(default)]
+    pub hnap_timestamp: HnapTimestamp,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// e.g. `America/Los_Angeles`, for modems that report local time rather than UTC
+    pub modem_timezone: Option<Tz>,
+    /// priority → level, for firmware that numbers its log levels differently
+    #[serde(default)]
+    pub log_levels: LogLevels,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerSettings,
+    /// how long to hold off logging in after failures, HNAP models only
+    #[serde(default)]
+    pub login_limits: LoginLimits,
+    /// for the modem that's the internet connection the scraper's on
+    pub speedtest: Option<SpeedtestSettings>,
+    /// latency to targets past the modem, every metrics scrape
+    #[serde(default)]
+    pub probes: Vec<ProbeSettings>,
+}
+
+/// Everything else in the config. No Debug, there are credentials in here.
+#[derive(Clone, Deserialize)]
+pub struct Settings {
+    pub scrape_interval_seconds: u64,
+    /// how often to scrape channel metrics, `scrape_interval_seconds` if unset
+    pub metrics_interval_seconds: Option<u64>,
+    /// how often to scrape the event log, `scrape_interval_seconds` if unset
+    pub logs_interval_seconds: Option<u64>,
+    /// each scrape waits a random amount up to this after its tick
+    #[serde(default)]
+    pub scrape_jitter_ms: u64,
+    /// export traces to `OTEL_EXPORTER_OTLP_ENDPOINT`
+    #[serde(default)]
+    pub trace: bool,
+    /// for the outputs' HTTP client, same as the modem's
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    pub ca_bundle: Option<String>,
+    pub logs_proxy_url: Option<String>,
+
+    pub telegraf_address: Option<String>,
+    /// don't resend channel samples that haven't changed
+    #[serde(default)]
+    pub telegraf_skip_unchanged: bool,
+    #[serde(default = "default_telegraf_unchanged_resend_seconds")]
+    pub telegraf_unchanged_resend_seconds: u64,
+    pub telegraf_batch_size: Option<usize>,
+    #[serde(default = "default_telegraf_flush_interval_seconds")]
+    pub telegraf_flush_interval_seconds: u64,
+    pub prometheus_address: Option<SocketAddr>,
+    /// serves `/healthz` and `/readyz`
+    pub healthcheck_address: Option<SocketAddr>,
+    /// serves the latest scrape as JSON under `/api/v1/`
+    pub status_address: Option<SocketAddr>,
+    pub influx: Option<InfluxSettings>,
+    pub victoriametrics: Option<VictoriaMetricsSettings>,
+    pub mqtt: Option<MqttSettings>,
+    pub graphite: Option<GraphiteSettings>,
+    #[serde(default)]
+    pub otlp_metrics: bool,
+    pub otlp_metrics_endpoint: Option<String>,
+    pub csv_directory: Option<PathBuf>,
+    pub sqlite: Option<SqliteSettings>,
+    #[serde(default)]
+    pub stdout: bool,
+    #[serde(default)]
+    pub stdout_format: StdoutFormat,
+
+    /// Loki's push endpoint
+    pub logs_address: Option<String>,
+    #[serde(default)]
+    pub loki_encoding: LokiEncoding,
+    #[serde(default)]
+    pub loki_log_fields: LokiLogFields,
+    #[serde(default)]
+    pub loki_split_streams: Vec<LokiSplit>,
+    pub loki_line_template: Option<String>,
+    pub loki_username: Option<String>,
+    pub loki_password: Option<String>,
+    pub loki_bearer_token: Option<String>,
+    pub loki_tenant_id: Option<String>,
+    #[serde(default = "default_loki_labels")]
+    pub loki_labels: HashMap<String, String>,
+    pub loki_state_directory: Option<PathBuf>,
+    pub loki_dedup_capacity: Option<usize>,
+    pub loki_dedup_ttl_seconds: Option<u64>,
+    #[serde(default)]
+    pub loki_dedup_tolerance_seconds: u64,
+    pub loki_batch_size: Option<usize>,
+    #[serde(default = "default_loki_batch_timeout_seconds")]
+    pub loki_batch_timeout_seconds: u64,
+    pub splunk: Option<SplunkSettings>,
+    pub elasticsearch: Option<ElasticsearchSettings>,
+    /// writes that fail are kept here and sent again once the output's back
+    pub spool: Option<SpoolSettings>,
+    pub alerts: Option<AlertSettings>,
+    /// where alerts are POSTed
+    #[serde(default)]
+    pub webhooks: Vec<WebhookSettings>,
+    pub discord: Option<DiscordSettings>,
+    pub slack: Option<SlackSettings>,
+    pub smtp: Option<SmtpSettings>,
+    pub ntfy: Option<NtfySettings>,
+    pub pushover: Option<PushoverSettings>,
+    /// a periodic line-quality summary
+    pub report: Option<ReportSettings>,
+
+    /// `modems:`, or the top level as a single modem
+    #[serde(skip)]
+    pub modems: Vec<ModemSettings>,
+}
+
+fn default_loki_batch_timeout_seconds() -> u64 {
+    60
+}
+
+fn default_telegraf_unchanged_resend_seconds() -> u64 {
+    300
+}
+
+fn default_telegraf_flush_interval_seconds() -> u64 {
+    60
+}
+
+fn default_loki_labels() -> HashMap<String, String> {
+    HashMap::from([("app".to_owned(), "modem_scraper".to_owned())])
+}
+
+impl Settings {
+    pub fn metrics_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.metrics_interval_seconds
+                .unwrap_or(self.scrape_interval_seconds),
+        )
+    }
+
+    pub fn logs_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.logs_interval_seconds
+                .unwrap_or(self.scrape_interval_seconds),
+        )
+    }
+
+    pub fn scrape_jitter(&self) -> Duration {
+        Duration::from_millis(self.scrape_jitter_ms)
+    }
+
+    pub fn load(config: &Config) -> Result<Settings, ConfigError> {
+        let mut settings: Settings = config.clone().try_deserialize()?;
+        settings.modems = match config.get::<Vec<ModemSettings>>("modems") {
+            Ok(modems) => modems,
+            Err(ConfigError::NotFound(_)) => vec![config.clone().try_deserialize()?],
+            Err(e) => return Err(e),
+        };
+        Ok(settings)
+    }
+
+    /// Everything wrong with `config`, rather than just the first thing [Settings::load] trips
+    /// over. Each problem says what the key defaults to, if anything.
+    pub fn problems(config: &Config) -> Vec<String> {
+        let mut checker = Checker {
+            config,
+            problems: Vec::new(),
+            known: HashSet::from(["modems".to_owned()]),
+        };
+        checker.required::<u64>("scrape_interval_seconds", "e.g. 60");
+        checker.optional::<u64>("metrics_interval_seconds", "scrape_interval_seconds");
+        checker.optional::<u64>("logs_interval_seconds", "scrape_interval_seconds");
+        checker.optional::<u64>("scrape_jitter_ms", "0");
+        checker.optional::<bool>("trace", "false");
+        checker.optional::<bool>("accept_invalid_certs", "false");
+        checker.optional::<String>("ca_bundle", "the system's CAs");
+        checker.optional::<String>("logs_proxy_url", "no proxy");
+        checker.optional::<String>("telegraf_address", "not sent to telegraf");
+        checker.optional::<bool>("telegraf_skip_unchanged", "false");
+        checker.optional::<u64>("telegraf_unchanged_resend_seconds", "300");
+        checker.optional::<usize>("telegraf_batch_size", "written every scrape");
+        checker.optional::<u64>("telegraf_flush_interval_seconds", "60");
+        checker.optional::<SocketAddr>("prometheus_address", "/metrics isn't served");
+        checker.optional::<SocketAddr>("healthcheck_address", "no health checks");
+        checker.optional::<SocketAddr>("status_address", "no status API");
+        checker.optional::<InfluxSettings>("influx", "not sent to InfluxDB");
+        checker.optional::<VictoriaMetricsSettings>("victoriametrics", "not sent");
+        checker.optional::<MqttSettings>("mqtt", "not published");
+        checker.optional::<GraphiteSettings>("graphite", "not sent to graphite");
+        checker.optional::<bool>("otlp_metrics", "false");
+        checker.optional::<String>("otlp_metrics_endpoint", "OTEL_EXPORTER_OTLP_ENDPOINT");
+        checker.optional::<PathBuf>("csv_directory", "no CSV files");
+        checker.optional::<SqliteSettings>("sqlite", "no history");
+        checker.optional::<bool>("stdout", "false");
+        checker.optional::<StdoutFormat>("stdout_format", "text");
+        checker.optional::<String>("logs_address", "logs aren't sent to Loki");
+        checker.optional::<LokiEncoding>("loki_encoding", "json");
+        checker.optional::<LokiLogFields>("loki_log_fields", "none");
+        checker.optional::<Vec<LokiSplit>>("loki_split_streams", "by level only");
+        checker.optional::<String>("loki_line_template", "the modem's message");
+        if let Ok(template) = config.get::<String>("loki_line_template") {
+            if let Err(e) = LineTemplate::new(&template) {
+                checker.problems.push(format!("loki_line_template: {}", e));
+            }
+        }
+        checker.optional::<String>("loki_username", "no basic auth");
+        checker.optional::<String>("loki_password", "no basic auth");
+        checker.optional::<String>("loki_bearer_token", "no bearer token");
+        checker.optional::<String>("loki_tenant_id", "no X-Scope-OrgID");
+        checker.optional::<HashMap<String, String>>("loki_labels", "app: modem_scraper");
+        checker.optional::<PathBuf>("loki_state_directory", "pushed again after a restart");
+        checker.optional::<usize>("loki_dedup_capacity", "sized from the modem's log");
+        checker.optional::<u64>("loki_dedup_ttl_seconds", "remembered by count");
+        checker.optional::<u64>("loki_dedup_tolerance_seconds", "0");
+        checker.optional::<usize>("loki_batch_size", "pushed every time the log's read");
+        checker.optional::<u64>("loki_batch_timeout_seconds", "60");
+        checker.optional::<SplunkSettings>("splunk", "not sent to Splunk");
+        checker.optional::<ElasticsearchSettings>("elasticsearch", "not sent to Elasticsearch");
+        checker.optional::<SpoolSettings>("spool", "failed writes are dropped");
+        checker.optional::<AlertSettings>("alerts", "no alerts");
+        checker.optional::<Vec<WebhookSettings>>("webhooks", "none");
+        checker.optional::<DiscordSettings>("discord", "not sent to Discord");
+        checker.optional::<SlackSettings>("slack", "not sent to Slack");
+        checker.optional::<SmtpSettings>("smtp", "not emailed");
+        checker.optional::<NtfySettings>("ntfy", "not sent to ntfy");
+        checker.optional::<PushoverSettings>("pushover", "not sent to Pushover");
+        checker.optional::<ReportSettings>("report", "no reports");
+
+        match config.get::<Vec<config::Value>>("modems") {
+            Ok(modems) => {
+                for (i, modem) in modems.into_iter().enumerate() {
+                    let prefix = format!("modems[{}].", i);
+                    checker.modem(&prefix);
+                    if let Ok(keys) = modem.into_table() {
+                        checker.unknown(&prefix, keys.keys());
+                    }
+                }
+            }
+            Err(ConfigError::NotFound(_)) => checker.modem(""),
+            Err(e) => checker.problems.push(format!("modems: {}", e)),
+        }
+
+        if let Ok(keys) = config
+            .clone()
+            .try_deserialize::<HashMap<String, config::Value>>()
+        {
+            checker.unknown("", keys.keys());
+        }
+        checker.problems
+    }
+}
+
+struct Checker<'a> {
+    config: &'a Config,
+    problems: Vec<String>,
+    /// every key that was checked, with its `modems[i].` prefix if it has one
+    known: HashSet<String>,
+}
+
+impl Checker<'_> {
+    fn required<T: DeserializeOwned>(&mut self, key: &str, example: &str) {
+        self.known.insert(key.to_owned());
+        match self.config.get::<T>(key) {
+            Ok(_) => {}
+            Err(ConfigError::NotFound(_)) => self
+                .problems
+                .push(format!("{}: missing, it's required ({})", key, example)),
+            Err(e) => self.problems.push(format!("{}: {} ({})", key, e, example)),
+        }
+    }
+
+    fn optional<T: DeserializeOwned>(&mut self, key: &str, default: &str) {
+        self.known.insert(key.to_owned());
+        match self.config.get::<T>(key) {
+            Ok(_) | Err(ConfigError::NotFound(_)) => {}
+            Err(e) => self
+                .problems
+                .push(format!("{}: {} (default: {})", key, e, default)),
+        }
+    }
+
+    /// Keys under `prefix` that weren't checked, most likely typos, which would otherwise be
+    /// silently ignored
+    fn unknown<'k>(&mut self, prefix: &str, keys: impl Iterator<Item = &'k String>) {
+        let mut unknown: Vec<String> = keys
+            .map(|key| format!("{}{}", prefix, key))
+            .filter(|key| !self.known.contains(key))
+            .collect();
+        unknown.sort();
+        for key in unknown {
+            self.problems.push(format!("{}: unknown key", key));
+        }
+    }
+
+    /// `prefix` is `modems[i].`, or empty for a modem set at the top level
+    fn modem(&mut self, prefix: &str) {
+        let key = |name: &str| format!("{}{}", prefix, name);
+        self.required::<String>(&key("device_address"), "e.g. https://192.168.100.1/HNAP1/");
+        self.optional::<String>(&key("device_username"), "empty");
+        self.optional::<String>(&key("device_password"), "empty");
+        self.optional::<Model>(&key("model"), "detected");
+        self.optional::<String>(&key("protocol"), "the model's");
+        if !cfg!(feature = "snmp")
+            && self
+                .config
+                .get::<String>(&key("protocol"))
+                .is_ok_and(|protocol| protocol == "snmp")
+        {
+            self.problems.push(format!(
+                "{}: snmp needs a build with `--features snmp`",
+                key("protocol")
+            ));
+        }
+        self.optional::<bool>(&key("accept_invalid_certs"), "false");
+        self.optional::<String>(&key("ca_bundle"), "the system's CAs");
+        self.optional::<String>(&key("pinned_certificate"), "not pinned");
+        self.optional::<String>(&key("proxy_url"), "no proxy");
+        self.optional::<String>(&key("proxy_username"), "none");
+        self.optional::<String>(&key("proxy_password"), "none");
+        self.optional::<u64>(&key("connect_timeout_seconds"), "5");
+        self.optional::<u64>(&key("timeout_seconds"), "30");
+        self.optional::<HnapTransport>(&key("hnap_transport"), "json");
+        self.optional::<HnapTimestamp>(&key("hnap_timestamp"), "auto");
+        self.optional::<RetryPolicy>(
+            &key("retry"),
+            "3 attempts, backing off from 500ms to 10s with jitter",
+        );
+        self.optional::<Tz>(&key("modem_timezone"), "UTC");
+        self.optional::<LogLevels>(
+            &key("log_levels"),
+            "3 error, 4 warn, 5 info, 6 debug, anything else error",
+        );
+        self.optional::<CircuitBreakerSettings>(
+            &key("circuit_breaker"),
+            "left alone for 300s after 5 failures in a row",
+        );
+        self.optional::<LoginLimits>(
+            &key("login_limits"),
+            "60s after a failed login, doubling up to an hour, and an hour after a lockout",
+        );
+        self.optional::<SpeedtestSettings>(&key("speedtest"), "no speedtests");
+        self.optional::<Vec<ProbeSettings>>(&key("probes"), "none");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::{File, FileFormat};
+
+    fn problems(yaml: &str) -> Vec<String> {
+        let config = Config::builder()
+            .add_source(File::from_str(yaml, FileFormat::Yaml))
+            .build()
+            .unwrap();
+        Settings::problems(&config)
+    }
+
+    #[test]
+    fn unknown_keys_everywhere_at_once() {
+        let problems = problems(
+            "scrape_interval_seconds: 60
+scrape_intreval_seconds: 30
+modems:
+  - device_address: https://192.168.100.1/HNAP1/
+    device_pasword: hunter2
+  - device_address: https://192.168.0.1/HNAP1/
+    modle: s33
+",
+        );
+        assert_eq!(
+            problems,
+            vec![
+                "modems[0].device_pasword: unknown key",
+                "modems[1].modle: unknown key",
+                "scrape_intreval_seconds: unknown key",
+            ]
+        );
+    }
+
+    #[test]
+    fn top_level_modem_keys_are_known() {
+        assert!(problems(
+            "scrape_interval_seconds: 60
+device_address: https://192.168.100.1/HNAP1/
+device_password: hunter2
+"
+        )
+        .is_empty());
+    }
+}
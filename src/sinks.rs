@@ -0,0 +1,229 @@
+use ::telegraf::protocol::{Field, FieldData, Timestamp};
+use ::telegraf::Point;
+use async_trait::async_trait;
+use chrono::Utc;
+use modem_scraper_lib::payloads::{CodewordDelta, LogEntry};
+use std::any::Any;
+use std::collections::HashMap;
+use tracing::{error, instrument};
+
+use crate::add_tags;
+use crate::scraped::ScrapedMetrics;
+
+pub mod csv;
+pub mod elasticsearch;
+pub mod graphite;
+pub mod homeassistant;
+pub mod influx;
+pub mod loki;
+pub mod mqtt;
+pub mod otlp;
+pub mod prometheus;
+pub mod splunk;
+pub mod sqlite;
+pub mod status;
+pub mod stdout;
+pub mod telegraf;
+pub mod victoriametrics;
+
+pub type SinkError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Whatever a sink is holding on to between writes, handed to the sink that replaces it when the
+/// config is reloaded
+pub type SinkState = Box<dyn Any + Send>;
+
+/// Somewhere channel metrics go after every scrape
+#[async_trait]
+pub trait MetricSink: Send {
+    /// for error messages
+    fn name(&self) -> &'static str;
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError>;
+
+    /// for sinks that hold on to anything between writes, see [carry_over_metric_sinks]
+    fn take_state(&mut self) -> Option<SinkState> {
+        None
+    }
+
+    /// picks up from the sink it replaces
+    fn restore(&mut self, _state: SinkState) {}
+}
+
+/// Somewhere the modem's event log goes after every scrape
+#[async_trait]
+pub trait LogSink: Send {
+    /// for error messages
+    fn name(&self) -> &'static str;
+    async fn write_logs(
+        &mut self,
+        logs: &[LogEntry],
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError>;
+
+    /// how many entries it remembers sending, for sinks that skip ones they've already sent
+    fn dedup_entries(&self) -> Option<usize> {
+        None
+    }
+
+    /// for sinks that hold on to anything between writes, see [carry_over_log_sinks]
+    fn take_state(&mut self) -> Option<SinkState> {
+        None
+    }
+
+    /// picks up from the sink it replaces
+    fn restore(&mut self, _state: SinkState) {}
+}
+
+/// Channel metrics as points, with `tags` added. Points are stamped with the modem's clock if it
+/// reports one, otherwise with the time of the scrape.
+pub fn metrics_to_points(scraped: &ScrapedMetrics, tags: &HashMap<String, String>) -> Vec<Point> {
+    let mut points: Vec<Point> = scraped
+        .metrics
+        .channels
+        .iter()
+        .map(|channel| {
+            let mut point = channel.to_point();
+            point.fields.push(Field {
+                name: "health".to_owned(),
+                value: FieldData::Float(scraped.channel_health(channel)),
+            });
+            for (reading, in_range) in channel.in_range() {
+                point.fields.push(Field {
+                    name: format!("{}_in_range", reading),
+                    value: FieldData::Boolean(in_range),
+                });
+            }
+            if let Some(delta) = channel.codeword_delta(&scraped.codeword_deltas) {
+                point.fields.extend(codeword_fields(delta));
+            }
+            point
+        })
+        .collect();
+    points.extend(scraped.metrics.status_point(&scraped.codeword_deltas));
+    points.extend(scraped.metrics.startup_points());
+    points.extend(scraped.metrics.partial_service_points());
+    points.extend(scraped.reboot_point());
+    points.extend(scraped.outage_point());
+    points.extend(scraped.bonding_points());
+    points.extend(scraped.speedtest_point());
+    points.extend(scraped.probe_points());
+    points.extend(scraped.firmware_change_point());
+    points.extend(scraped.log_events_point());
+    points.extend(scraped.event_code_points());
+    points.extend(scraped.scraper_points());
+    add_tags(&mut points, tags);
+    let timestamp = scraped
+        .metrics
+        .time
+        .unwrap_or_else(Utc::now)
+        .timestamp_nanos_opt()
+        .and_then(|nanos| u64::try_from(nanos).ok());
+    for point in points.iter_mut() {
+        point.timestamp = timestamp.map(|value| Timestamp { value });
+    }
+    points
+}
+
+/// Per-interval counts and rates to go alongside the running totals
+fn codeword_fields(delta: &CodewordDelta) -> Vec<Field> {
+    let mut fields = vec![
+        Field {
+            name: "corrected_delta".to_owned(),
+            value: FieldData::UNumber(delta.corrected),
+        },
+        Field {
+            name: "uncorrectables_delta".to_owned(),
+            value: FieldData::UNumber(delta.uncorrectables),
+        },
+    ];
+    if delta.seconds > 0.0 {
+        fields.push(Field {
+            name: "corrected_rate".to_owned(),
+            value: FieldData::Float(delta.corrected as f64 / delta.seconds),
+        });
+        fields.push(Field {
+            name: "uncorrectables_rate".to_owned(),
+            value: FieldData::Float(delta.uncorrectables as f64 / delta.seconds),
+        });
+    }
+    fields
+}
+
+/// How each sink fared, by name
+pub type SinkResults = Vec<(&'static str, Result<(), String>)>;
+
+/// Summed across the sinks that dedup, or `None` if none of them do
+pub fn dedup_entries(sinks: &[Box<dyn LogSink>]) -> Option<u64> {
+    sinks
+        .iter()
+        .filter_map(|sink| sink.dedup_entries())
+        .reduce(|a, b| a + b)
+        .map(|entries| entries as u64)
+}
+
+/// Hands what each of the `previous` sinks was holding on to (queued points, what's been sent) to
+/// the new sink of the same kind, so a reload doesn't drop or resend anything
+pub fn carry_over_metric_sinks(
+    previous: &mut [Box<dyn MetricSink>],
+    sinks: &mut [Box<dyn MetricSink>],
+) {
+    for previous in previous.iter_mut() {
+        let Some(sink) = sinks.iter_mut().find(|sink| sink.name() == previous.name()) else {
+            continue;
+        };
+        if let Some(state) = previous.take_state() {
+            sink.restore(state);
+        }
+    }
+}
+
+/// [carry_over_metric_sinks] for log sinks
+pub fn carry_over_log_sinks(previous: &mut [Box<dyn LogSink>], sinks: &mut [Box<dyn LogSink>]) {
+    for previous in previous.iter_mut() {
+        let Some(sink) = sinks.iter_mut().find(|sink| sink.name() == previous.name()) else {
+            continue;
+        };
+        if let Some(state) = previous.take_state() {
+            sink.restore(state);
+        }
+    }
+}
+
+/// Writes to every sink. A failing sink is logged and skipped, so it can't take the others down.
+#[instrument(skip(sinks))]
+pub async fn write_metrics(
+    sinks: &mut [Box<dyn MetricSink>],
+    scraped: &ScrapedMetrics,
+    tags: &HashMap<String, String>,
+) -> SinkResults {
+    let mut results = Vec::new();
+    for sink in sinks.iter_mut() {
+        let result = sink.write_metrics(scraped, tags).await.map_err(|e| {
+            error!("Unable to write metrics to {}: {}", sink.name(), e);
+            e.to_string()
+        });
+        results.push((sink.name(), result));
+    }
+    results
+}
+
+/// Writes to every sink. A failing sink is logged and skipped, so it can't take the others down.
+#[instrument(skip(sinks))]
+pub async fn write_logs(
+    sinks: &mut [Box<dyn LogSink>],
+    logs: &[LogEntry],
+    tags: &HashMap<String, String>,
+) -> SinkResults {
+    let mut results = Vec::new();
+    for sink in sinks.iter_mut() {
+        let result = sink.write_logs(logs, tags).await.map_err(|e| {
+            error!("Unable to write logs to {}: {}", sink.name(), e);
+            e.to_string()
+        });
+        results.push((sink.name(), result));
+    }
+    results
+}
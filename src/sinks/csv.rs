@@ -0,0 +1,104 @@
+use super::{MetricSink, SinkError};
+use crate::scraped::ScrapedMetrics;
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use telegraf::protocol::FieldData;
+use tokio::io::AsyncWriteExt;
+
+/// Appends channel samples to `<directory>/<measurement>-<date>.csv`, so there's history to graph
+/// later without running a metrics stack. A new set of files is started every day (UTC).
+#[derive(Debug, Clone)]
+pub struct CsvSink {
+    pub directory: PathBuf,
+}
+
+/// Quotes `s` if it needs it, per RFC 4180
+fn escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn field_value(value: &FieldData) -> String {
+    match value {
+        FieldData::Boolean(b) => b.to_string(),
+        FieldData::UNumber(n) => n.to_string(),
+        FieldData::Number(n) => n.to_string(),
+        FieldData::Float(f) => f.to_string(),
+        FieldData::Str(s) => escape(s),
+    }
+}
+
+#[async_trait]
+impl MetricSink for CsvSink {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let now = Utc::now();
+        let timestamp = now.to_rfc3339();
+        let hostname = escape(tags.get("hostname").map(String::as_str).unwrap_or_default());
+
+        // group rows by file so each one is only opened once per scrape. Only the channel's own
+        // tags become columns, since the modem's tags vary between modems sharing a file.
+        let mut files: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+        for point in scraped
+            .metrics
+            .channels
+            .iter()
+            .map(|channel| channel.to_point())
+        {
+            let header = ["timestamp", "hostname"]
+                .into_iter()
+                .chain(point.tags.iter().map(|tag| tag.name.as_str()))
+                .chain(point.fields.iter().map(|field| field.name.as_str()))
+                .collect::<Vec<_>>()
+                .join(",");
+            let row = [timestamp.clone(), hostname.clone()]
+                .into_iter()
+                .chain(point.tags.iter().map(|tag| escape(&tag.value)))
+                .chain(point.fields.iter().map(|field| field_value(&field.value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            files
+                .entry(format!(
+                    "{}-{}.csv",
+                    point.measurement,
+                    now.format("%Y-%m-%d")
+                ))
+                .or_insert_with(|| (header, Vec::new()))
+                .1
+                .push(row);
+        }
+
+        tokio::fs::create_dir_all(&self.directory).await?;
+        for (file_name, (header, rows)) in files {
+            let path = self.directory.join(file_name);
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await?;
+            let mut contents = String::new();
+            if file.metadata().await?.len() == 0 {
+                contents.push_str(&header);
+                contents.push('\n');
+            }
+            for row in rows {
+                contents.push_str(&row);
+                contents.push('\n');
+            }
+            file.write_all(contents.as_bytes()).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use super::{LogSink, SinkError};
+use async_trait::async_trait;
+use modem_scraper_lib::payloads::LogEntry;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+use tracing::warn;
+
+/// `elasticsearch:` in the config. Works with OpenSearch too.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ElasticsearchSettings {
+    /// e.g. `https://elasticsearch:9200`
+    pub url: String,
+    /// strftime of each entry's timestamp, so indices roll over daily by default
+    #[serde(default = "default_index")]
+    pub index: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// base64 `id:key`, as Elasticsearch hands it out. Takes priority over basic auth.
+    pub api_key: Option<String>,
+    /// how many times to back off and retry when the cluster answers 429
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+}
+
+fn default_index() -> String {
+    "modem-logs-%Y.%m.%d".to_owned()
+}
+
+fn default_max_retries() -> u32 {
+    5
+}
+
+/// Posts the event log through the `_bulk` API
+#[derive(Debug, Clone)]
+pub struct ElasticsearchSink {
+    http_client: reqwest::Client,
+    settings: ElasticsearchSettings,
+}
+
+impl ElasticsearchSink {
+    pub fn new(http_client: reqwest::Client, settings: ElasticsearchSettings) -> ElasticsearchSink {
+        ElasticsearchSink {
+            http_client,
+            settings,
+        }
+    }
+
+    fn request(&self, body: String) -> reqwest::RequestBuilder {
+        let request = self
+            .http_client
+            .post(format!("{}/_bulk", self.settings.url.trim_end_matches('/')))
+            .header("Content-Type", "application/x-ndjson")
+            .body(body);
+        match (&self.settings.api_key, &self.settings.username) {
+            (Some(api_key), _) => request.header("Authorization", format!("ApiKey {}", api_key)),
+            (None, Some(username)) => request.basic_auth(username, self.settings.password.as_ref()),
+            (None, None) => request,
+        }
+    }
+}
+
+/// The modem hands back its whole log every scrape, so documents are keyed on their contents and
+/// re-sending one just overwrites it
+fn document_id(hostname: &str, log_entry: &LogEntry) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(hostname);
+    hasher.update(log_entry.timestamp.to_rfc3339());
+    hasher.update(&log_entry.message);
+    format!("{:x}", hasher.finalize())
+}
+
+#[async_trait]
+impl LogSink for ElasticsearchSink {
+    fn name(&self) -> &'static str {
+        "elasticsearch"
+    }
+
+    async fn write_logs(
+        &mut self,
+        logs: &[LogEntry],
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        if logs.is_empty() {
+            return Ok(());
+        }
+        let hostname = tags.get("hostname").map(String::as_str).unwrap_or_default();
+
+        let mut body = String::new();
+        for log_entry in logs {
+            // `to_string` would panic on a bad template
+            let mut index = String::new();
+            write!(
+                index,
+                "{}",
+                log_entry.timestamp.format(&self.settings.index)
+            )
+            .map_err(|_| format!("invalid index template {}", self.settings.index))?;
+            let action = json!({
+                "index": {
+                    "_index": index,
+                    "_id": document_id(hostname, log_entry),
+                }
+            });
+            let document = json!({
+                "@timestamp": log_entry.timestamp.to_rfc3339(),
+                "log": { "level": log_entry.level.as_str() },
+                "message": log_entry.message,
+                "fields": log_entry.fields(),
+                "labels": tags,
+            });
+            body.push_str(&format!("{}\n{}\n", action, document));
+        }
+
+        let mut backoff = Duration::from_secs(1);
+        let mut attempt = 0;
+        let res = loop {
+            let res = self.request(body.clone()).send().await?;
+            if res.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= self.settings.max_retries
+            {
+                break res;
+            }
+            attempt += 1;
+            warn!("Elasticsearch is overloaded, retrying in {:?}", backoff);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(Duration::from_secs(60));
+        };
+
+        // a 200 can still have failed items in it
+        let reply: Value = res.error_for_status()?.json().await?;
+        if reply["errors"].as_bool().unwrap_or(false) {
+            let first_error = reply["items"]
+                .as_array()
+                .and_then(|items| items.iter().find_map(|item| item["index"].get("error")))
+                .cloned()
+                .unwrap_or_default();
+            return Err(format!("some log entries were rejected: {}", first_error).into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use super::{MetricSink, SinkError};
+use crate::scraped::ScrapedMetrics;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use telegraf::protocol::FieldData;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+/// `graphite:` in the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphiteSettings {
+    /// `host:port` of the plaintext listener, usually 2003
+    pub address: String,
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+}
+
+fn default_prefix() -> String {
+    "modem".to_owned()
+}
+
+/// Writes `metric.path value timestamp` lines over TCP. Paths follow Telegraf's default graphite
+/// template, `host.tags.measurement.field`, with tag values sorted by tag name.
+#[derive(Debug)]
+pub struct GraphiteSink {
+    settings: GraphiteSettings,
+    /// dropped whenever a write fails, and reconnected on the next scrape
+    stream: Option<TcpStream>,
+}
+
+impl GraphiteSink {
+    pub fn new(settings: GraphiteSettings) -> GraphiteSink {
+        GraphiteSink {
+            settings,
+            stream: None,
+        }
+    }
+}
+
+/// Dots separate path nodes and whitespace separates the line, so neither can be in a node
+fn node(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '.' | ' ' | '\t' | '/' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Graphite only stores numbers
+fn field_value(value: &FieldData) -> Option<String> {
+    match value {
+        FieldData::Boolean(b) => Some(u8::from(*b).to_string()),
+        FieldData::UNumber(n) => Some(n.to_string()),
+        FieldData::Number(n) => Some(n.to_string()),
+        FieldData::Float(f) => Some(f.to_string()),
+        FieldData::Str(_) => None,
+    }
+}
+
+/// Every numeric field in `scraped` as a plaintext protocol line
+fn lines(prefix: &str, scraped: &ScrapedMetrics, tags: &HashMap<String, String>) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let hostname = node(
+        tags.get("hostname")
+            .map(String::as_str)
+            .unwrap_or("unknown"),
+    );
+
+    let mut lines = String::new();
+    // only the channel's own tags, the modem's would make the paths unreadable
+    for point in scraped
+        .metrics
+        .channels
+        .iter()
+        .map(|channel| channel.to_point())
+    {
+        let mut point_tags: Vec<_> = point.tags.iter().collect();
+        point_tags.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut path = vec![node(prefix), hostname.clone()];
+        path.extend(point_tags.iter().map(|tag| node(&tag.value)));
+        path.push(node(&point.measurement));
+
+        for field in &point.fields {
+            if let Some(value) = field_value(&field.value) {
+                lines.push_str(&format!(
+                    "{}.{} {} {}\n",
+                    path.join("."),
+                    node(&field.name),
+                    value,
+                    timestamp
+                ));
+            }
+        }
+    }
+    lines
+}
+
+#[async_trait]
+impl MetricSink for GraphiteSink {
+    fn name(&self) -> &'static str {
+        "graphite"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let lines = lines(&self.settings.prefix, scraped, tags);
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => self
+                .stream
+                .insert(TcpStream::connect(&self.settings.address).await?),
+        };
+        if let Err(e) = stream.write_all(lines.as_bytes()).await {
+            self.stream = None;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use crate::scraped::ScrapedMetrics;
+use modem_scraper_lib::payloads::Channel;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// One entity for Home Assistant's MQTT discovery, see
+/// <https://www.home-assistant.io/integrations/mqtt/#mqtt-discovery>
+pub struct Discovery {
+    /// `<discovery prefix>/<component>/<object id>/config`
+    pub topic: String,
+    pub payload: Value,
+}
+
+/// The `device` block every entity shares, so HA groups them under one modem
+fn device(modem_id: &str, tags: &HashMap<String, String>) -> Value {
+    json!({
+        "identifiers": [format!("modem_scraper_{}", modem_id)],
+        "name": format!("Modem {}", modem_id),
+        "model": tags.get("model"),
+        "sw_version": tags.get("docsis_version"),
+        "manufacturer": tags.get("manufacturer"),
+    })
+}
+
+/// HA only likes `[a-zA-Z0-9_-]` in object ids
+fn object_id(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Discovery payloads for everything in `scraped`: uptime, plus SNR, power and lock status of each
+/// channel. `modem_topic` is where the values themselves get published.
+pub fn discoveries(
+    discovery_prefix: &str,
+    modem_id: &str,
+    modem_topic: &str,
+    scraped: &ScrapedMetrics,
+    tags: &HashMap<String, String>,
+) -> Vec<Discovery> {
+    let device = device(modem_id, tags);
+    let entity = |component: &str, key: &str, name: String, state_topic: String, extra: Value| {
+        let unique_id = object_id(&format!("modem_scraper_{}_{}", modem_id, key));
+        let mut payload = json!({
+            "name": name,
+            "unique_id": unique_id,
+            "object_id": unique_id,
+            "state_topic": state_topic,
+            "device": device,
+        });
+        if let (Some(payload), Value::Object(extra)) = (payload.as_object_mut(), extra) {
+            payload.extend(extra);
+        }
+        Discovery {
+            topic: format!("{}/{}/{}/config", discovery_prefix, component, unique_id),
+            payload,
+        }
+    };
+
+    let mut discoveries = Vec::new();
+    if scraped.metrics.uptime.is_some() {
+        discoveries.push(entity(
+            "sensor",
+            "uptime",
+            "Uptime".to_owned(),
+            format!("{}/uptime", modem_topic),
+            json!({
+                "device_class": "duration",
+                "unit_of_measurement": "s",
+                "state_class": "measurement",
+                "entity_category": "diagnostic",
+            }),
+        ));
+    }
+
+    for channel in &scraped.metrics.channels {
+        let (direction, label, channel_id, has_snr) = match channel {
+            Channel::Downstream(c) => ("downstream", "Downstream", c.channel_id, true),
+            Channel::Upstream(c) => ("upstream", "Upstream", c.channel_id, false),
+            Channel::OfdmaUpstream(c) => ("ofdma_upstream", "OFDMA upstream", c.channel_id, false),
+        };
+        let channel_topic = format!("{}/{}/{}", modem_topic, direction, channel_id);
+        let key = format!("{}_{}", direction, channel_id);
+
+        if has_snr {
+            discoveries.push(entity(
+                "sensor",
+                &format!("{}_snr", key),
+                format!("{} {} SNR", label, channel_id),
+                format!("{}/snr", channel_topic),
+                json!({
+                    "device_class": "signal_strength",
+                    "unit_of_measurement": "dB",
+                    "state_class": "measurement",
+                }),
+            ));
+        }
+        discoveries.push(entity(
+            "sensor",
+            &format!("{}_power", key),
+            format!("{} {} power", label, channel_id),
+            format!("{}/power", channel_topic),
+            json!({
+                "unit_of_measurement": "dBmV",
+                "state_class": "measurement",
+            }),
+        ));
+        discoveries.push(entity(
+            "binary_sensor",
+            &format!("{}_lock_status", key),
+            format!("{} {} locked", label, channel_id),
+            format!("{}/lock_status", channel_topic),
+            json!({
+                "device_class": "connectivity",
+                "payload_on": "true",
+                "payload_off": "false",
+            }),
+        ));
+    }
+
+    discoveries
+}
@@ -0,0 +1,123 @@
+use super::{metrics_to_points, MetricSink, SinkError};
+use crate::scraped::ScrapedMetrics;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+use telegraf::protocol::FieldData;
+use telegraf::Point;
+
+/// `influx:` in the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct InfluxSettings {
+    /// e.g. `http://influxdb:8086`
+    pub url: String,
+    pub org: String,
+    pub bucket: String,
+    pub token: String,
+}
+
+/// Writes straight to InfluxDB v2's `/api/v2/write`, for people who don't run Telegraf
+#[derive(Debug, Clone)]
+pub struct InfluxClient {
+    http_client: reqwest::Client,
+    settings: InfluxSettings,
+}
+
+impl InfluxClient {
+    pub fn new(http_client: reqwest::Client, settings: InfluxSettings) -> InfluxClient {
+        InfluxClient {
+            http_client,
+            settings,
+        }
+    }
+
+    pub async fn write_points(
+        &self,
+        points: &[Point],
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let body: Vec<String> = points.iter().map(to_line_protocol).collect();
+        self.http_client
+            .post(format!(
+                "{}/api/v2/write",
+                self.settings.url.trim_end_matches('/')
+            ))
+            .query(&[
+                ("org", self.settings.org.as_str()),
+                ("bucket", self.settings.bucket.as_str()),
+                ("precision", "ns"),
+            ])
+            .header("Authorization", format!("Token {}", self.settings.token))
+            .body(body.join("\n"))
+            .send()
+            .await?
+            .error_for_status()
+    }
+}
+
+#[async_trait]
+impl MetricSink for InfluxClient {
+    fn name(&self) -> &'static str {
+        "influxdb"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        self.write_points(&metrics_to_points(scraped, tags)).await?;
+        Ok(())
+    }
+}
+
+/// Backslash-escapes every char in `special`
+fn escape(s: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn field_value(value: &FieldData) -> String {
+    match value {
+        FieldData::Boolean(b) => b.to_string(),
+        FieldData::UNumber(n) => format!("{}u", n),
+        FieldData::Number(n) => format!("{}i", n),
+        FieldData::Float(f) => f.to_string(),
+        FieldData::Str(s) => format!("\"{}\"", escape(s, &['"', '\\'])),
+    }
+}
+
+/// https://docs.influxdata.com/influxdb/v2/reference/syntax/line-protocol/
+pub fn to_line_protocol(point: &Point) -> String {
+    let mut line = escape(&point.measurement, &[',', ' ']);
+    for tag in &point.tags {
+        line.push_str(&format!(
+            ",{}={}",
+            escape(&tag.name, &[',', '=', ' ']),
+            escape(&tag.value, &[',', '=', ' '])
+        ));
+    }
+    let fields: Vec<String> = point
+        .fields
+        .iter()
+        .map(|field| {
+            format!(
+                "{}={}",
+                escape(&field.name, &[',', '=', ' ']),
+                field_value(&field.value)
+            )
+        })
+        .collect();
+    line.push(' ');
+    line.push_str(&fields.join(","));
+    // without a timestamp influx uses the time it received the point
+    if let Some(timestamp) = &point.timestamp {
+        line.push_str(&format!(" {}", timestamp.value));
+    }
+    line
+}
@@ -0,0 +1,694 @@
+use super::{LogSink, SinkError, SinkState};
+use crate::dedup::{Eviction, Seen, MIN_CAPACITY};
+use crate::{construct_loki_streams, LokiEntry, LokiStreams, LokiValue};
+use async_trait::async_trait;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use minijinja::Environment;
+use modem_scraper_lib::payloads::LogEntry;
+use prost::Message;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// How many entries to hold on to while Loki is unreachable
+const MAX_PENDING: usize = 4096;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// How pushes are encoded, `loki_encoding` in the config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LokiEncoding {
+    #[default]
+    Json,
+    /// gzipped JSON
+    Gzip,
+    /// snappy compressed protobuf, the same as promtail sends
+    Protobuf,
+}
+
+/// Where each entry's [LogEntry::fields] go, `loki_log_fields` in the config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LokiLogFields {
+    /// nowhere, they can still be pulled out of the line with LogQL
+    #[default]
+    None,
+    /// all of them, as structured metadata
+    Metadata,
+    /// `event_id` and `event_description` as labels, for events in the catalog. There are only so
+    /// many of those, so the streams don't get out of hand.
+    Labels,
+}
+
+/// Extra stream labels, `loki_split_streams` in the config. Streams are always split by level, and
+/// by modem since its tags are labels too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LokiSplit {
+    /// a `category` label, the entry's [LogEntry::category]
+    Category,
+}
+
+/// The `logs_address` and `loki_*` keys in the config. No Debug, so the credentials can't end up
+/// in a log line.
+#[derive(Clone)]
+pub struct LokiSettings {
+    /// the push endpoint, `.../loki/api/v1/push`
+    pub url: String,
+    pub encoding: LokiEncoding,
+    pub log_fields: LokiLogFields,
+    pub split: Vec<LokiSplit>,
+    /// the pushed line, the modem's message as is if unset
+    pub line_template: Option<LineTemplate>,
+    /// basic auth, e.g. Grafana Cloud's user id and API key
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// sent instead of basic auth when both are set
+    pub bearer_token: Option<String>,
+    /// `X-Scope-OrgID`, for multi-tenant Loki
+    pub tenant_id: Option<String>,
+    /// added to every stream, along with the modem's tags (hostname, serial number, firmware...)
+    pub labels: HashMap<String, String>,
+    /// where to remember what's been pushed, so a restart doesn't push the modem's whole log again
+    pub state_directory: Option<PathBuf>,
+    /// how many pushed entries to remember. By default it's twice the most the modem has sent at
+    /// once, and at least 64.
+    pub dedup_capacity: Option<usize>,
+    /// remember what's been pushed for this long instead, however many entries that is. Entries
+    /// older than this aren't pushed at all.
+    pub dedup_ttl: Option<Duration>,
+    /// entries with the same level and message this close together are the same entry
+    pub dedup_tolerance: Duration,
+    /// hold entries back until there are this many, rather than pushing every time the log's read
+    pub batch_size: Option<usize>,
+    /// but no longer than this
+    pub batch_timeout: Duration,
+}
+
+/// `loki_line_template`, a minijinja template of the modem's tags and anything about the entry
+#[derive(Clone)]
+pub struct LineTemplate(Environment<'static>);
+
+impl LineTemplate {
+    /// Fails on a syntax error, or on anything that fails to render a sample entry (an unknown
+    /// filter, say), so `validate-config` catches it rather than the first push
+    pub fn new(template: &str) -> Result<LineTemplate, minijinja::Error> {
+        let mut environment = Environment::new();
+        environment.add_template_owned("line", template.to_owned())?;
+        let line_template = LineTemplate(environment);
+        let sample = LogEntry {
+            timestamp: chrono::DateTime::UNIX_EPOCH,
+            level: log::Level::Warn,
+            message: "No Ranging Response received - T3 time-out;CM-MAC=00:00:00:00:00:00;"
+                .to_owned(),
+            priority: Some(3),
+        };
+        let tags = HashMap::from([
+            ("hostname".to_owned(), "192.168.100.1".to_owned()),
+            ("model".to_owned(), "s33".to_owned()),
+        ]);
+        line_template.render(&sample, &tags)?;
+        Ok(line_template)
+    }
+
+    /// Fields the entry doesn't have are undefined, which renders as nothing and is false in an
+    /// `{% if %}`
+    fn render(
+        &self,
+        log_entry: &LogEntry,
+        tags: &HashMap<String, String>,
+    ) -> Result<String, minijinja::Error> {
+        self.0
+            .get_template("line")?
+            .render(line_fields(log_entry, tags))
+    }
+}
+
+/// Pushes the event log to Loki. Entries are only sent once, and anything that fails to send is
+/// queued and retried with backoff instead of being dropped.
+#[derive(Clone)]
+pub struct LokiSink {
+    http_client: reqwest::Client,
+    settings: LokiSettings,
+    seen: Seen,
+    /// `seen` is loaded from here on the first write, once the modem's hostname is known
+    state_path: Option<PathBuf>,
+    /// waiting to be pushed, oldest first
+    pending: VecDeque<LogEntry>,
+    /// when Loki is down, don't try again before this
+    retry_at: Option<Instant>,
+    backoff: Duration,
+    /// the modem's timestamp of the newest entry pushed, in ns, and the offset it was pushed with
+    last_pushed: Option<(u128, u128)>,
+    /// when the oldest entry in `pending` was queued, for `batch_timeout`
+    held_since: Option<Instant>,
+}
+
+/// What a [LokiSink] hands over on a reload
+struct LokiState {
+    seen: Seen,
+    state_directory: Option<PathBuf>,
+    state_path: Option<PathBuf>,
+    pending: VecDeque<LogEntry>,
+    retry_at: Option<Instant>,
+    backoff: Duration,
+    last_pushed: Option<(u128, u128)>,
+    held_since: Option<Instant>,
+}
+
+impl LokiSink {
+    pub fn new(http_client: reqwest::Client, settings: LokiSettings) -> LokiSink {
+        let eviction = match settings.dedup_ttl {
+            Some(ttl) => Eviction::Age(ttl),
+            None => Eviction::Count(settings.dedup_capacity.unwrap_or(MIN_CAPACITY)),
+        };
+        let seen = Seen::new(eviction).with_tolerance(settings.dedup_tolerance);
+        LokiSink {
+            http_client,
+            settings,
+            seen,
+            state_path: None,
+            pending: VecDeque::new(),
+            retry_at: None,
+            backoff: INITIAL_BACKOFF,
+            last_pushed: None,
+            held_since: None,
+        }
+    }
+
+    /// Picks up where the last run left off, if there's a `state_directory`. Anything wrong with
+    /// the state file is logged and the log is pushed again from scratch.
+    fn load_state(&mut self, tags: &HashMap<String, String>) {
+        let Some(state_directory) = &self.settings.state_directory else {
+            return;
+        };
+        let hostname = tags.get("hostname").map(String::as_str).unwrap_or("modem");
+        let path = state_directory.join(format!(
+            "loki-{}.json",
+            hostname.replace(
+                |c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '-',
+                "_"
+            )
+        ));
+        if let Err(e) = self.seen.load(&path) {
+            warn!("Unable to read {}: {}", path.display(), e);
+        }
+        self.state_path = Some(path);
+    }
+
+    /// Queues whatever hasn't been pushed or queued already, within the tolerance either way
+    fn enqueue(&mut self, logs: &[LogEntry]) {
+        self.seen.forget_expired();
+        let mut queued =
+            Seen::new(Eviction::Count(usize::MAX)).with_tolerance(self.settings.dedup_tolerance);
+        queued.insert_keyed(&self.pending);
+        for log_entry in logs {
+            let key = self.seen.key(log_entry);
+            if !self.seen.contains_entry(log_entry)
+                && !queued.contains_entry(log_entry)
+                && !self.seen.is_expired(&key)
+            {
+                self.pending.push_back(log_entry.clone());
+            }
+        }
+        if self.pending.len() > MAX_PENDING {
+            let dropped = self.pending.len() - MAX_PENDING;
+            warn!(
+                "Loki has been down a while, dropping {} log entries",
+                dropped
+            );
+            self.pending.drain(..dropped);
+        }
+    }
+
+    /// Returns the last timestamp it pushed, for [LokiSink::last_pushed]
+    async fn push(
+        &self,
+        logs: &VecDeque<LogEntry>,
+        tags: &HashMap<String, String>,
+    ) -> Result<Option<(u128, u128)>, SinkError> {
+        let mut labels = self.settings.labels.clone();
+        labels.extend(tags.clone());
+        let mut last_pushed = self.last_pushed;
+        let mut values: Vec<LokiEntry> = Vec::new();
+        for (log_entry, timestamp) in timestamps(logs, &mut last_pushed)? {
+            let (mut labels, metadata) = match self.settings.log_fields {
+                LokiLogFields::None => Default::default(),
+                LokiLogFields::Metadata => (BTreeMap::new(), log_entry.fields()),
+                LokiLogFields::Labels => (event_labels(log_entry), BTreeMap::new()),
+            };
+            for split in &self.settings.split {
+                match split {
+                    LokiSplit::Category => {
+                        labels.insert(
+                            "category".to_owned(),
+                            log_entry.category().as_str().to_owned(),
+                        );
+                    }
+                }
+            }
+            values.push(LokiEntry {
+                level: log_entry.level,
+                timestamp,
+                line: match &self.settings.line_template {
+                    Some(line_template) => {
+                        line_template.render(log_entry, tags).unwrap_or_else(|e| {
+                            warn!(
+                                "Pushing the message as is, loki_line_template failed: {}",
+                                e
+                            );
+                            log_entry.message.to_owned()
+                        })
+                    }
+                    None => log_entry.message.to_owned(),
+                },
+                labels,
+                metadata,
+            });
+        }
+        let streams = construct_loki_streams(labels, values);
+
+        self.request(&streams)?.send().await?.error_for_status()?;
+        Ok(last_pushed)
+    }
+
+    fn request(&self, streams: &LokiStreams) -> Result<reqwest::RequestBuilder, SinkError> {
+        let mut request = self.http_client.post(&self.settings.url);
+        if let Some(bearer_token) = &self.settings.bearer_token {
+            request = request.bearer_auth(bearer_token);
+        } else if let Some(username) = &self.settings.username {
+            request = request.basic_auth(username, self.settings.password.as_ref());
+        }
+        if let Some(tenant_id) = &self.settings.tenant_id {
+            request = request.header("X-Scope-OrgID", tenant_id);
+        }
+        Ok(match self.settings.encoding {
+            LokiEncoding::Json => request.json(streams),
+            LokiEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&serde_json::to_vec(streams)?)?;
+                request
+                    .header("Content-Type", "application/json")
+                    .header("Content-Encoding", "gzip")
+                    .body(encoder.finish()?)
+            }
+            LokiEncoding::Protobuf => {
+                let push_request = push_request(streams)?;
+                request
+                    .header("Content-Type", "application/x-protobuf")
+                    .body(snap::raw::Encoder::new().compress_vec(&push_request.encode_to_vec())?)
+            }
+        })
+    }
+}
+
+/// Each entry with the timestamp to push it with, oldest first. The modems only log to the second,
+/// so entries that share a timestamp are pushed a nanosecond apart, in the order the modem logged
+/// them, instead of leaving Loki to reorder or reject them. `last_pushed` carries that on from the
+/// previous push, for an entry logged in the same second as the last one pushed, and is moved on
+/// to the newest entry pushed.
+fn timestamps<'a>(
+    logs: &'a VecDeque<LogEntry>,
+    last_pushed: &mut Option<(u128, u128)>,
+) -> Result<Vec<(&'a LogEntry, u128)>, SinkError> {
+    let mut logs: Vec<&LogEntry> = logs.iter().collect();
+    // some firmware lists its log newest first
+    let descending = logs
+        .windows(2)
+        .filter(|pair| pair[0].timestamp > pair[1].timestamp)
+        .count();
+    let ascending = logs
+        .windows(2)
+        .filter(|pair| pair[0].timestamp < pair[1].timestamp)
+        .count();
+    if descending > ascending {
+        logs.reverse();
+    }
+    // stable, so entries from the same second stay in the modem's order
+    logs.sort_by_key(|log_entry| log_entry.timestamp);
+
+    let mut timestamps = Vec::with_capacity(logs.len());
+    let mut previous = *last_pushed;
+    for log_entry in logs {
+        let timestamp = log_entry
+            .timestamp
+            .timestamp_nanos_opt()
+            .ok_or("log timestamp out of range")?;
+        let timestamp = u128::try_from(timestamp)?;
+        let offset = match previous {
+            Some((previous, offset)) if previous == timestamp => offset + 1,
+            _ => 0,
+        };
+        previous = Some((timestamp, offset));
+        // an entry older than the last push doesn't take it back
+        if last_pushed.is_none_or(|(newest, _)| timestamp >= newest) {
+            *last_pushed = previous;
+        }
+        timestamps.push((log_entry, timestamp + offset));
+    }
+    Ok(timestamps)
+}
+
+/// Everything about the entry as text, for [LineTemplate]: the modem's tags, the entry's
+/// [LogEntry::fields], then `message`, `level`, `time` and `category`
+fn line_fields(log_entry: &LogEntry, tags: &HashMap<String, String>) -> BTreeMap<String, String> {
+    let mut fields: BTreeMap<String, String> = tags.clone().into_iter().collect();
+    fields.extend(log_entry.fields());
+    fields.extend([
+        ("message".to_owned(), log_entry.message.clone()),
+        ("level".to_owned(), log_entry.level.as_str().to_lowercase()),
+        ("time".to_owned(), log_entry.timestamp.to_rfc3339()),
+        (
+            "category".to_owned(),
+            log_entry.category().as_str().to_owned(),
+        ),
+    ]);
+    fields
+}
+
+/// The catalog's `event_id` and `event_description`, if the entry is in it
+fn event_labels(log_entry: &LogEntry) -> BTreeMap<String, String> {
+    let Some(event_code) = log_entry.event_code() else {
+        return BTreeMap::new();
+    };
+    BTreeMap::from([
+        ("event_id".to_owned(), event_code.id.to_string()),
+        (
+            "event_description".to_owned(),
+            event_code.description.to_owned(),
+        ),
+    ])
+}
+
+// `logproto.PushRequest` from Loki's push.proto, written out by hand to avoid a build script
+
+#[derive(Clone, PartialEq, Message)]
+struct PushRequest {
+    #[prost(message, repeated, tag = "1")]
+    streams: Vec<StreamAdapter>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StreamAdapter {
+    /// `{key="value", ...}`
+    #[prost(string, tag = "1")]
+    labels: String,
+    #[prost(message, repeated, tag = "2")]
+    entries: Vec<EntryAdapter>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct EntryAdapter {
+    #[prost(message, optional, tag = "1")]
+    timestamp: Option<prost_types::Timestamp>,
+    #[prost(string, tag = "2")]
+    line: String,
+    #[prost(message, repeated, tag = "3")]
+    structured_metadata: Vec<LabelPairAdapter>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct LabelPairAdapter {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+/// Labels in the selector syntax protobuf pushes want
+fn format_labels(labels: &HashMap<String, String>) -> String {
+    let labels: BTreeMap<&String, &String> = labels.iter().collect();
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| {
+            let v = v
+                .replace('\\', r"\\")
+                .replace('"', "\\\"")
+                .replace('\n', r"\n");
+            format!("{}=\"{}\"", k, v)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(", "))
+}
+
+fn push_request(streams: &LokiStreams) -> Result<PushRequest, SinkError> {
+    let mut push_request = PushRequest::default();
+    for stream in &streams.streams {
+        let mut entries = Vec::new();
+        for value in &stream.values {
+            let (timestamp, line, metadata) = match value {
+                LokiValue::Line(timestamp, line) => (timestamp, line, None),
+                LokiValue::WithMetadata(timestamp, line, metadata) => {
+                    (timestamp, line, Some(metadata))
+                }
+            };
+            let timestamp: i128 = timestamp.parse()?;
+            entries.push(EntryAdapter {
+                timestamp: Some(prost_types::Timestamp {
+                    seconds: i64::try_from(timestamp / 1_000_000_000)?,
+                    nanos: i32::try_from(timestamp % 1_000_000_000)?,
+                }),
+                line: line.clone(),
+                structured_metadata: metadata
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, value)| LabelPairAdapter {
+                        name: name.clone(),
+                        value: value.clone(),
+                    })
+                    .collect(),
+            });
+        }
+        push_request.streams.push(StreamAdapter {
+            labels: format_labels(&stream.stream),
+            entries,
+        });
+    }
+    Ok(push_request)
+}
+
+#[async_trait]
+impl LogSink for LokiSink {
+    fn name(&self) -> &'static str {
+        "loki"
+    }
+
+    async fn write_logs(
+        &mut self,
+        logs: &[LogEntry],
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        if self.settings.dedup_capacity.is_none() {
+            // the modem's log can be the same length every time, with some of it new
+            self.seen.grow(logs.len() * 2);
+        }
+        if self.state_path.is_none() {
+            self.load_state(tags);
+        }
+        self.enqueue(logs);
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        if let Some(batch_size) = self.settings.batch_size {
+            let held_since = *self.held_since.get_or_insert_with(Instant::now);
+            if self.pending.len() < batch_size && held_since.elapsed() < self.settings.batch_timeout
+            {
+                debug!("Holding {} entries for the next push", self.pending.len());
+                return Ok(());
+            }
+        }
+        if let Some(retry_at) = self.retry_at {
+            if Instant::now() < retry_at {
+                return Ok(());
+            }
+        }
+
+        match self.push(&self.pending, tags).await {
+            Ok(last_pushed) => {
+                self.last_pushed = last_pushed;
+                let evicted = self.seen.insert_keyed(&self.pending);
+                self.pending.clear();
+                self.held_since = None;
+                if !evicted.is_empty() {
+                    debug!("Forgot {} pushed log entries to make room", evicted.len());
+                }
+                if let Some(state_path) = &self.state_path {
+                    // the entries made it, so this isn't the sink failing
+                    if let Err(e) = self.seen.save(state_path) {
+                        warn!("Unable to write {}: {}", state_path.display(), e);
+                    }
+                }
+                self.retry_at = None;
+                self.backoff = INITIAL_BACKOFF;
+                Ok(())
+            }
+            Err(e) => {
+                self.retry_at = Some(Instant::now() + self.backoff);
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                Err(e)
+            }
+        }
+    }
+
+    fn dedup_entries(&self) -> Option<usize> {
+        Some(self.seen.len())
+    }
+
+    fn take_state(&mut self) -> Option<SinkState> {
+        Some(Box::new(LokiState {
+            seen: std::mem::take(&mut self.seen),
+            state_directory: self.settings.state_directory.clone(),
+            state_path: self.state_path.take(),
+            pending: std::mem::take(&mut self.pending),
+            retry_at: self.retry_at.take(),
+            backoff: self.backoff,
+            last_pushed: self.last_pushed.take(),
+            held_since: self.held_since.take(),
+        }))
+    }
+
+    /// The dedup settings are the new ones, with everything the old sink pushed already in them
+    fn restore(&mut self, state: SinkState) {
+        let Ok(state) = state.downcast::<LokiState>() else {
+            return;
+        };
+        self.seen.insert_many(state.seen.iter().copied());
+        // otherwise it's loaded from the new directory on the next write
+        if state.state_directory == self.settings.state_directory {
+            self.state_path = state.state_path;
+        }
+        self.pending = state.pending;
+        self.retry_at = state.retry_at;
+        self.backoff = state.backoff;
+        self.last_pushed = state.last_pushed;
+        self.held_since = state.held_since;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use log::Level;
+
+    fn settings() -> LokiSettings {
+        LokiSettings {
+            url: "http://localhost:3100/loki/api/v1/push".to_owned(),
+            encoding: LokiEncoding::Json,
+            log_fields: LokiLogFields::None,
+            split: Vec::new(),
+            line_template: None,
+            username: None,
+            password: None,
+            bearer_token: None,
+            tenant_id: None,
+            labels: HashMap::new(),
+            state_directory: None,
+            dedup_capacity: None,
+            dedup_ttl: None,
+            dedup_tolerance: Duration::ZERO,
+            batch_size: None,
+            batch_timeout: Duration::ZERO,
+        }
+    }
+
+    fn entry(seconds: i64, message: &str) -> LogEntry {
+        LogEntry {
+            timestamp: Utc.timestamp_opt(seconds, 0).unwrap(),
+            level: Level::Warn,
+            message: message.to_owned(),
+            priority: None,
+        }
+    }
+
+    #[test]
+    fn line_template_leaves_missing_fields_out() {
+        let line_template = LineTemplate::new(
+            "{{ message }}{% if event_id %} event={{ event_id }}{% endif %} ch={{ channel_id }}",
+        )
+        .unwrap();
+        let tags = HashMap::new();
+        let line = line_template.render(&entry(10, "Cable Modem Reboot"), &tags);
+        assert_eq!(line.unwrap(), "Cable Modem Reboot ch=");
+        let line = line_template.render(&entry(10, "T3 time-out <82000200>"), &tags);
+        assert_eq!(line.unwrap(), "T3 time-out <82000200> event=82000200 ch=");
+    }
+
+    #[test]
+    fn broken_line_templates_fail_up_front() {
+        assert!(LineTemplate::new("{{ message }").is_err());
+        assert!(LineTemplate::new("{{ message | no_such_filter }}").is_err());
+        assert!(LineTemplate::new("{{ message | upper }}").is_ok());
+    }
+
+    #[test]
+    fn queued_entries_match_across_a_bucket_edge() {
+        let mut sink = LokiSink::new(
+            reqwest::Client::new(),
+            LokiSettings {
+                dedup_tolerance: Duration::from_secs(2),
+                ..settings()
+            },
+        );
+        // 11 rounds down to 10 and 12 to 12, a bucket apart
+        sink.enqueue(&[entry(11, "T3 time-out")]);
+        sink.enqueue(&[
+            entry(12, "T3 time-out"),
+            entry(12, "SYNC Timing Synchronization"),
+        ]);
+        let messages: Vec<&str> = sink.pending.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["T3 time-out", "SYNC Timing Synchronization"]);
+    }
+
+    const SECOND: u128 = 1_000_000_000;
+
+    #[test]
+    fn same_second_entries_are_a_nanosecond_apart() {
+        let logs = VecDeque::from([
+            entry(10, "a"),
+            entry(10, "b"),
+            entry(11, "c"),
+            entry(10, "d"),
+        ]);
+        let mut last_pushed = None;
+        let pushed: Vec<(&str, u128)> = timestamps(&logs, &mut last_pushed)
+            .unwrap()
+            .into_iter()
+            .map(|(log_entry, timestamp)| (log_entry.message.as_str(), timestamp))
+            .collect();
+        assert_eq!(
+            pushed,
+            vec![
+                ("a", 10 * SECOND),
+                ("b", 10 * SECOND + 1),
+                ("d", 10 * SECOND + 2),
+                ("c", 11 * SECOND),
+            ]
+        );
+        assert_eq!(last_pushed, Some((11 * SECOND, 0)));
+
+        // carries on from the previous push
+        let logs = VecDeque::from([entry(11, "e")]);
+        let pushed = timestamps(&logs, &mut last_pushed).unwrap();
+        assert_eq!(pushed[0].1, 11 * SECOND + 1);
+    }
+
+    #[test]
+    fn entries_older_than_the_last_push_keep_their_timestamp() {
+        let mut last_pushed = Some((20 * SECOND, 3));
+        let logs = VecDeque::from([entry(10, "a"), entry(10, "b")]);
+        let pushed: Vec<u128> = timestamps(&logs, &mut last_pushed)
+            .unwrap()
+            .into_iter()
+            .map(|(_, timestamp)| timestamp)
+            .collect();
+        assert_eq!(pushed, vec![10 * SECOND, 10 * SECOND + 1]);
+        assert_eq!(last_pushed, Some((20 * SECOND, 3)));
+    }
+}
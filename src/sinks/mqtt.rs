@@ -0,0 +1,210 @@
+use super::homeassistant::discoveries;
+use super::{metrics_to_points, MetricSink, SinkError};
+use crate::scraped::ScrapedMetrics;
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use telegraf::protocol::FieldData;
+use tracing::error;
+
+/// `mqtt:` in the config
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MqttSettings {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    /// topics are `<prefix>/<modem>/<direction>/<channel id>/<field>`
+    #[serde(default = "default_topic_prefix")]
+    pub topic_prefix: String,
+    /// 0, 1 or 2
+    #[serde(default)]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
+    /// also publish Home Assistant discovery configs, so the modem shows up as a device
+    #[serde(default)]
+    pub homeassistant: bool,
+    #[serde(default = "default_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_client_id() -> String {
+    env!("CARGO_PKG_NAME").to_owned()
+}
+
+fn default_topic_prefix() -> String {
+    "modem".to_owned()
+}
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_owned()
+}
+
+/// Publishes every channel field to its own topic, for home automation setups without a TSDB
+#[derive(Clone)]
+pub struct MqttSink {
+    client: AsyncClient,
+    topic_prefix: String,
+    qos: QoS,
+    retain: bool,
+    /// None when discovery is off
+    discovery_prefix: Option<String>,
+    /// discovery topics we've already published, so each entity is only announced once
+    discovered: HashSet<String>,
+}
+
+impl MqttSink {
+    /// Connects to the broker. The connection is driven by a background task, so this needs to
+    /// be called from inside the runtime.
+    pub fn connect(settings: &MqttSettings) -> Result<MqttSink, SinkError> {
+        let qos = rumqttc::qos(settings.qos).map_err(|e| format!("invalid qos: {:?}", e))?;
+        let mut options = MqttOptions::new(&settings.client_id, &settings.host, settings.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &settings.username {
+            options.set_credentials(username, settings.password.clone().unwrap_or_default());
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, 100);
+        tokio::task::spawn(async move {
+            loop {
+                // rumqttc reconnects on the next poll, so just don't spin while the broker is down
+                if let Err(e) = event_loop.poll().await {
+                    error!("MQTT connection error: {}", e);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        });
+
+        Ok(MqttSink {
+            client,
+            topic_prefix: settings.topic_prefix.clone(),
+            qos,
+            retain: settings.retain,
+            discovery_prefix: settings
+                .homeassistant
+                .then(|| settings.discovery_prefix.clone()),
+            discovered: HashSet::new(),
+        })
+    }
+
+    async fn publish(&self, topic: String, payload: String) -> Result<(), SinkError> {
+        self.client
+            .publish(topic, self.qos, self.retain, payload)
+            .await?;
+        Ok(())
+    }
+
+    /// Announces any entities HA hasn't heard about yet. Configs are always retained so HA still
+    /// finds them after it restarts.
+    async fn discover(
+        &mut self,
+        modem_id: &str,
+        modem_topic: &str,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let discovery_prefix = match &self.discovery_prefix {
+            Some(discovery_prefix) => discovery_prefix,
+            None => return Ok(()),
+        };
+        for discovery in discoveries(discovery_prefix, modem_id, modem_topic, scraped, tags) {
+            if self.discovered.contains(&discovery.topic) {
+                continue;
+            }
+            self.client
+                .publish(
+                    discovery.topic.clone(),
+                    self.qos,
+                    true,
+                    discovery.payload.to_string(),
+                )
+                .await?;
+            self.discovered.insert(discovery.topic);
+        }
+        Ok(())
+    }
+}
+
+/// The modem's serial number if we know it, otherwise whatever we connected to
+fn modem_id(tags: &HashMap<String, String>) -> String {
+    let modem = tags
+        .get("serial_number")
+        .or_else(|| tags.get("hostname"))
+        .map(String::as_str)
+        .unwrap_or("unknown");
+    topic_segment(modem)
+}
+
+/// `/`, `+` and `#` mean things in topics
+fn topic_segment(s: &str) -> String {
+    s.replace(['/', '+', '#'], "_")
+}
+
+fn payload(value: &FieldData) -> String {
+    match value {
+        FieldData::Boolean(b) => b.to_string(),
+        FieldData::UNumber(n) => n.to_string(),
+        FieldData::Number(n) => n.to_string(),
+        FieldData::Float(f) => f.to_string(),
+        FieldData::Str(s) => s.to_owned(),
+    }
+}
+
+#[async_trait]
+impl MetricSink for MqttSink {
+    fn name(&self) -> &'static str {
+        "mqtt"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let modem_id = modem_id(tags);
+        let modem_topic = format!("{}/{}", self.topic_prefix, modem_id);
+        self.discover(&modem_id, &modem_topic, scraped, tags)
+            .await?;
+
+        if let Some(uptime) = scraped.metrics.uptime {
+            self.publish(
+                format!("{}/uptime", modem_topic),
+                uptime.as_secs().to_string(),
+            )
+            .await?;
+        }
+        for point in metrics_to_points(scraped, tags) {
+            // modem_downstream_channel -> downstream
+            let direction = point
+                .measurement
+                .trim_start_matches("modem_")
+                .trim_end_matches("_channel");
+            // channels by id and `modem_startup` by stage. `modem_status` is neither.
+            let channel_id = point
+                .tags
+                .iter()
+                .find(|tag| tag.name == "channel_id" || tag.name == "stage")
+                .map(|tag| tag.value.as_str())
+                .unwrap_or_default();
+            let point_topic = match channel_id {
+                "" => format!("{}/{}", modem_topic, direction),
+                channel_id => format!("{}/{}/{}", modem_topic, direction, channel_id),
+            };
+            for field in &point.fields {
+                let topic = format!("{}/{}", point_topic, field.name);
+                self.publish(topic, payload(&field.value)).await?;
+            }
+        }
+        Ok(())
+    }
+}
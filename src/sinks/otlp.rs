@@ -0,0 +1,154 @@
+use super::prometheus::{metrics_samples, replace_scrape, Scrape, METRIC_FAMILIES};
+use super::{MetricSink, SinkError};
+use crate::scraped::ScrapedMetrics;
+use async_trait::async_trait;
+use opentelemetry::metrics::{MeterProvider, MetricsError, ObservableCounter, ObservableGauge};
+use opentelemetry::sdk::export::metrics::aggregation::cumulative_temporality_selector;
+use opentelemetry::sdk::metrics::controllers::BasicController;
+use opentelemetry::sdk::metrics::selectors;
+use opentelemetry::sdk::Resource;
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::warn;
+
+enum Instrument {
+    Gauge(ObservableGauge<f64>),
+    Counter(ObservableCounter<f64>),
+}
+
+impl Instrument {
+    fn observe(&self, cx: &Context, value: f64, attributes: &[KeyValue]) {
+        match self {
+            Instrument::Gauge(gauge) => gauge.observe(cx, value, attributes),
+            Instrument::Counter(counter) => counter.observe(cx, value, attributes),
+        }
+    }
+}
+
+/// Exports the latest scrape of every modem over OTLP, using the same families as `/metrics`.
+/// Like [super::prometheus::PrometheusState], scrape tasks write and the exporter reads whenever
+/// its period comes around.
+#[derive(Clone, Default)]
+pub struct OtlpMetrics {
+    latest: Arc<RwLock<BTreeMap<String, Scrape>>>,
+    /// exports every period until [OtlpMetrics::stop]
+    controller: Option<BasicController>,
+}
+
+impl OtlpMetrics {
+    /// Starts exporting every `period`. Metrics only have a gRPC exporter in this version of
+    /// opentelemetry-otlp, so `endpoint` is usually the collector's 4317. Falls back to the
+    /// `OTEL_EXPORTER_OTLP_*` variables when unset.
+    pub fn install(
+        endpoint: Option<String>,
+        period: Duration,
+    ) -> Result<OtlpMetrics, MetricsError> {
+        let mut exporter = opentelemetry_otlp::new_exporter().tonic().with_env();
+        if let Some(endpoint) = endpoint {
+            exporter = exporter.with_endpoint(endpoint);
+        }
+        let controller = opentelemetry_otlp::new_pipeline()
+            .metrics(
+                selectors::simple::inexpensive(),
+                cumulative_temporality_selector(),
+                opentelemetry::runtime::Tokio,
+            )
+            .with_exporter(exporter)
+            .with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                env!("CARGO_PKG_NAME").replace('-', "_"),
+            )]))
+            .with_period(period)
+            .build()?;
+
+        let meter = controller.versioned_meter(
+            env!("CARGO_PKG_NAME"),
+            Some(env!("CARGO_PKG_VERSION")),
+            None,
+        );
+        let mut instruments = HashMap::new();
+        for (name, help, kind) in METRIC_FAMILIES {
+            let instrument = match *kind {
+                // OTel counters are cumulative already, the exporter on the other end adds `_total`
+                "counter" => Instrument::Counter(
+                    meter
+                        .f64_observable_counter(name.trim_end_matches("_total"))
+                        .with_description(*help)
+                        .try_init()?,
+                ),
+                _ => Instrument::Gauge(
+                    meter
+                        .f64_observable_gauge(*name)
+                        .with_description(*help)
+                        .try_init()?,
+                ),
+            };
+            instruments.insert(*name, instrument);
+        }
+
+        let otlp_metrics = OtlpMetrics {
+            latest: Arc::default(),
+            controller: Some(controller),
+        };
+        let latest = otlp_metrics.latest.clone();
+        meter.register_callback(move |cx| {
+            for (tags, scraped) in latest.read().unwrap().values() {
+                for (name, labels, value) in metrics_samples(scraped) {
+                    let instrument = match instruments.get(name) {
+                        Some(instrument) => instrument,
+                        None => continue,
+                    };
+                    let attributes: Vec<KeyValue> = labels
+                        .into_iter()
+                        .map(|(k, v)| KeyValue::new(k, v))
+                        .chain(
+                            tags.iter()
+                                .map(|(k, v)| KeyValue::new(k.clone(), v.clone())),
+                        )
+                        .collect();
+                    instrument.observe(cx, value, &attributes);
+                }
+            }
+        })?;
+
+        Ok(otlp_metrics)
+    }
+
+    /// Whether `other` is a clone of this one, rather than another exporter
+    pub fn is(&self, other: &OtlpMetrics) -> bool {
+        Arc::ptr_eq(&self.latest, &other.latest)
+    }
+
+    /// Exports one last time and stops, for when a reload replaces it. Otherwise it'd keep
+    /// exporting alongside the new one, with whatever it last saw.
+    pub fn stop(&self) {
+        let Some(controller) = self.controller.clone() else {
+            return;
+        };
+        // blocks until the last export's done
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = controller.stop(&Context::current()) {
+                warn!("Unable to stop exporting OTLP metrics: {}", e);
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl MetricSink for OtlpMetrics {
+    fn name(&self) -> &'static str {
+        "otlp"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        replace_scrape(&self.latest, tags, scraped.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,636 @@
+use super::{MetricSink, SinkError};
+use crate::scraped::ScrapedMetrics;
+use async_trait::async_trait;
+use axum::extract::State;
+use axum::routing::get;
+use axum::Router;
+use modem_scraper_lib::events;
+use modem_scraper_lib::payloads::Channel;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+/// name, help, type
+pub(crate) const METRIC_FAMILIES: &[(&str, &str, &str)] = &[
+    (
+        "modem_downstream_lock_status",
+        "1 if the downstream channel is locked",
+        "gauge",
+    ),
+    (
+        "modem_downstream_frequency_hz",
+        "Downstream channel frequency",
+        "gauge",
+    ),
+    (
+        "modem_downstream_power_dbmv",
+        "Downstream channel power",
+        "gauge",
+    ),
+    (
+        "modem_downstream_snr_db",
+        "Downstream channel signal to noise ratio",
+        "gauge",
+    ),
+    (
+        "modem_downstream_corrected_total",
+        "Codewords corrected by FEC",
+        "counter",
+    ),
+    (
+        "modem_downstream_uncorrectables_total",
+        "Codewords FEC couldn't correct",
+        "counter",
+    ),
+    (
+        "modem_upstream_lock_status",
+        "1 if the upstream channel is locked",
+        "gauge",
+    ),
+    (
+        "modem_upstream_frequency_hz",
+        "Upstream channel frequency",
+        "gauge",
+    ),
+    ("modem_upstream_width_hz", "Upstream channel width", "gauge"),
+    (
+        "modem_upstream_power_dbmv",
+        "Upstream channel transmit power",
+        "gauge",
+    ),
+    (
+        "modem_ofdma_upstream_fft_size",
+        "Subcarriers in the OFDMA channel",
+        "gauge",
+    ),
+    (
+        "modem_downstream_health",
+        "0 to 100, from the channel's power, SNR and uncorrectables against DOCSIS ranges",
+        "gauge",
+    ),
+    (
+        "modem_upstream_health",
+        "0 to 100, from the channel's power against DOCSIS ranges",
+        "gauge",
+    ),
+    (
+        "modem_downstream_power_in_range",
+        "1 if the downstream channel is locked with power inside -7 to +7 dBmV",
+        "gauge",
+    ),
+    (
+        "modem_downstream_snr_in_range",
+        "1 if the downstream channel is locked with SNR of at least 33 dB",
+        "gauge",
+    ),
+    (
+        "modem_upstream_power_in_range",
+        "1 if the upstream channel is locked with power inside 38 to 48 dBmV",
+        "gauge",
+    ),
+    (
+        "modem_health",
+        "Average health across every channel",
+        "gauge",
+    ),
+    (
+        "modem_partial_service",
+        "1 while the modem lists channels in the direction that it couldn't bond or lock",
+        "gauge",
+    ),
+    (
+        "modem_startup_status",
+        "Startup sequence step: 0 not started, 1 in progress, 2 complete, 3 error",
+        "gauge",
+    ),
+    (
+        "modem_internet_down",
+        "1 while the modem says it has no internet connection or network access",
+        "gauge",
+    ),
+    (
+        "modem_outages_total",
+        "Internet outages since the scraper started",
+        "counter",
+    ),
+    (
+        "modem_downtime_seconds_total",
+        "Time without internet since the scraper started",
+        "counter",
+    ),
+    (
+        "modem_channels_added_total",
+        "Channels that joined the bonding group since the scraper started",
+        "counter",
+    ),
+    (
+        "modem_channels_removed_total",
+        "Channels that left the bonding group since the scraper started",
+        "counter",
+    ),
+    (
+        "modem_log_event_code_total",
+        "Log lines with the DOCSIS event ID since the scraper started",
+        "counter",
+    ),
+    (
+        "modem_speedtest_download_bits_per_second",
+        "Download speed in the latest speedtest",
+        "gauge",
+    ),
+    (
+        "modem_speedtest_upload_bits_per_second",
+        "Upload speed in the latest speedtest",
+        "gauge",
+    ),
+    (
+        "modem_speedtest_latency_seconds",
+        "Latency to the server in the latest speedtest",
+        "gauge",
+    ),
+    (
+        "modem_speedtest_jitter_seconds",
+        "Jitter to the server in the latest speedtest",
+        "gauge",
+    ),
+    (
+        "modem_speedtest_packet_loss_percent",
+        "Packet loss in the latest speedtest",
+        "gauge",
+    ),
+    (
+        "modem_probe_rtt_seconds",
+        "Average round trip time to the target in the latest round of pings",
+        "gauge",
+    ),
+    (
+        "modem_probe_rtt_max_seconds",
+        "Longest round trip time to the target in the latest round of pings",
+        "gauge",
+    ),
+    (
+        "modem_probe_loss_percent",
+        "Pings to the target that went unanswered in the latest round",
+        "gauge",
+    ),
+    (
+        "modem_scraper_scrape_duration_seconds",
+        "How long the latest metrics request took",
+        "gauge",
+    ),
+    (
+        "modem_scraper_request_duration_seconds",
+        "How long the latest request for the action took",
+        "gauge",
+    ),
+    (
+        "modem_scraper_response_bytes",
+        "Size of the latest reply to the action",
+        "gauge",
+    ),
+    (
+        "modem_scraper_requests_total",
+        "Replies to the action, by result code or HTTP status",
+        "counter",
+    ),
+    (
+        "modem_scraper_logins_total",
+        "Logins, including after the session expired",
+        "counter",
+    ),
+    (
+        "modem_scraper_parse_failures_total",
+        "Replies that couldn't be parsed",
+        "counter",
+    ),
+    (
+        "modem_scraper_sink_errors_total",
+        "Failed writes to outputs",
+        "counter",
+    ),
+    (
+        "modem_scraper_dedup_entries",
+        "Log entries remembered by outputs that skip ones they've sent",
+        "gauge",
+    ),
+    (
+        "modem_scraper_circuit_open",
+        "1 while the modem is left alone after failing too many times in a row",
+        "gauge",
+    ),
+    (
+        "modem_scraper_circuit_trips_total",
+        "Times the modem was left alone after failing too many times in a row",
+        "counter",
+    ),
+];
+
+/// tags, plus the last scrape
+pub(crate) type Scrape = (HashMap<String, String>, ScrapedMetrics);
+
+/// Replaces whatever was last scraped from the modem with these `tags`. A speedtest only comes with
+/// one scrape, so the previous one's kept until there's a new one.
+pub(crate) fn replace_scrape(
+    latest: &RwLock<BTreeMap<String, Scrape>>,
+    tags: &HashMap<String, String>,
+    mut scraped: ScrapedMetrics,
+) {
+    let key = tags.get("hostname").cloned().unwrap_or_default();
+    let mut latest = latest.write().unwrap();
+    if scraped.speedtest.is_none() {
+        scraped.speedtest = latest
+            .get(&key)
+            .and_then(|(_, previous)| previous.speedtest.clone());
+    }
+    latest.insert(key, (tags.clone(), scraped));
+}
+
+/// Latest scrape of every modem, keyed by hostname. Scrape tasks write, `/metrics` reads.
+#[derive(Debug, Clone, Default)]
+pub struct PrometheusState {
+    latest: Arc<RwLock<BTreeMap<String, Scrape>>>,
+}
+
+impl PrometheusState {
+    /// See [replace_scrape]
+    pub fn update(&self, tags: &HashMap<String, String>, scraped: ScrapedMetrics) {
+        replace_scrape(&self.latest, tags, scraped);
+    }
+
+    /// Prometheus text exposition format
+    pub fn render(&self) -> String {
+        exposition(
+            self.latest
+                .read()
+                .unwrap()
+                .values()
+                .map(|(tags, scraped)| (tags, scraped)),
+        )
+    }
+}
+
+/// `scrapes` in the Prometheus text exposition format
+pub(crate) fn exposition<'a>(
+    scrapes: impl Iterator<Item = (&'a HashMap<String, String>, &'a ScrapedMetrics)>,
+) -> String {
+    // samples have to be grouped by family, so bucket them first
+    let mut samples: HashMap<&str, Vec<String>> = HashMap::new();
+    for (tags, scraped) in scrapes {
+        for (name, labels, value) in metrics_samples(scraped) {
+            let mut labels: BTreeMap<&str, String> = labels;
+            labels.extend(tags.iter().map(|(k, v)| (k.as_str(), v.clone())));
+            samples.entry(name).or_default().push(format!(
+                "{}{} {}",
+                name,
+                format_labels(&labels),
+                value
+            ));
+        }
+    }
+
+    let mut body = String::new();
+    for (name, help, kind) in METRIC_FAMILIES {
+        let family = match samples.get(name) {
+            Some(family) => family,
+            None => continue,
+        };
+        writeln!(body, "# HELP {} {}", name, help).unwrap();
+        writeln!(body, "# TYPE {} {}", name, kind).unwrap();
+        for sample in family {
+            writeln!(body, "{}", sample).unwrap();
+        }
+    }
+    body
+}
+
+pub(crate) type Sample = (&'static str, BTreeMap<&'static str, String>, f64);
+
+/// Every channel's samples, plus health, which needs the whole scrape to work out
+pub(crate) fn metrics_samples(scraped: &ScrapedMetrics) -> Vec<Sample> {
+    let mut samples = Vec::new();
+    for channel in &scraped.metrics.channels {
+        samples.extend(channel_samples(channel));
+        let (name, id) = match channel {
+            Channel::Downstream(c) => ("modem_downstream_health", c.channel_id),
+            Channel::Upstream(c) => ("modem_upstream_health", c.channel_id),
+            Channel::OfdmaUpstream(c) => ("modem_upstream_health", c.channel_id),
+        };
+        samples.push((
+            name,
+            BTreeMap::from([("channel_id", id.to_string())]),
+            scraped.channel_health(channel),
+        ));
+        for (reading, in_range) in channel.in_range() {
+            let name = match (channel, reading) {
+                (Channel::Downstream(_), "snr") => "modem_downstream_snr_in_range",
+                (Channel::Downstream(_), _) => "modem_downstream_power_in_range",
+                _ => "modem_upstream_power_in_range",
+            };
+            samples.push((
+                name,
+                BTreeMap::from([("channel_id", id.to_string())]),
+                f64::from(u8::from(in_range)),
+            ));
+        }
+    }
+    if let Some(health) = scraped.health() {
+        samples.push(("modem_health", BTreeMap::new(), health));
+    }
+    for (direction, partial_service) in scraped.metrics.partial_service() {
+        samples.push((
+            "modem_partial_service",
+            BTreeMap::from([("direction", direction.to_owned())]),
+            f64::from(u8::from(partial_service)),
+        ));
+    }
+    for (stage, status) in &scraped.metrics.status.startup {
+        samples.push((
+            "modem_startup_status",
+            BTreeMap::from([("stage", stage.clone())]),
+            f64::from(status.as_number()),
+        ));
+    }
+    for (direction, changes) in &scraped.bonding {
+        let labels = BTreeMap::from([("direction", direction.clone())]);
+        samples.push((
+            "modem_channels_added_total",
+            labels.clone(),
+            changes.added_total as f64,
+        ));
+        samples.push((
+            "modem_channels_removed_total",
+            labels,
+            changes.removed_total as f64,
+        ));
+    }
+    if let Some(outages) = &scraped.outages {
+        samples.push((
+            "modem_internet_down",
+            BTreeMap::new(),
+            f64::from(u8::from(outages.down)),
+        ));
+        samples.push((
+            "modem_outages_total",
+            BTreeMap::new(),
+            outages.outages as f64,
+        ));
+        samples.push((
+            "modem_downtime_seconds_total",
+            BTreeMap::new(),
+            outages.downtime.as_secs_f64(),
+        ));
+    }
+    for (event_id, count) in &scraped.event_codes {
+        let mut labels = BTreeMap::from([("event_id", event_id.to_string())]);
+        if let Some(event_code) = events::by_id(*event_id) {
+            labels.insert("severity", event_code.severity.to_owned());
+            labels.insert("description", event_code.description.to_owned());
+        }
+        samples.push(("modem_log_event_code_total", labels, *count as f64));
+    }
+    if let Some(speedtest) = &scraped.speedtest {
+        samples.push((
+            "modem_speedtest_download_bits_per_second",
+            BTreeMap::new(),
+            speedtest.download,
+        ));
+        samples.push((
+            "modem_speedtest_upload_bits_per_second",
+            BTreeMap::new(),
+            speedtest.upload,
+        ));
+        samples.push((
+            "modem_speedtest_latency_seconds",
+            BTreeMap::new(),
+            speedtest.latency.as_secs_f64(),
+        ));
+        if let Some(jitter) = speedtest.jitter {
+            samples.push((
+                "modem_speedtest_jitter_seconds",
+                BTreeMap::new(),
+                jitter.as_secs_f64(),
+            ));
+        }
+        if let Some(packet_loss) = speedtest.packet_loss {
+            samples.push((
+                "modem_speedtest_packet_loss_percent",
+                BTreeMap::new(),
+                packet_loss,
+            ));
+        }
+    }
+    for probe in &scraped.probes {
+        let labels = BTreeMap::from([
+            ("target", probe.target.clone()),
+            ("method", probe.method.clone()),
+        ]);
+        if let (Some(rtt_avg), Some(rtt_max)) = (probe.rtt_avg, probe.rtt_max) {
+            samples.push((
+                "modem_probe_rtt_seconds",
+                labels.clone(),
+                rtt_avg.as_secs_f64(),
+            ));
+            samples.push((
+                "modem_probe_rtt_max_seconds",
+                labels.clone(),
+                rtt_max.as_secs_f64(),
+            ));
+        }
+        samples.push(("modem_probe_loss_percent", labels, probe.loss()));
+    }
+    if let Some(stats) = &scraped.scraper {
+        samples.push((
+            "modem_scraper_scrape_duration_seconds",
+            BTreeMap::new(),
+            stats.scrape_duration.as_secs_f64(),
+        ));
+        for (action, request) in &stats.requests {
+            let labels = BTreeMap::from([("action", action.clone())]);
+            samples.push((
+                "modem_scraper_request_duration_seconds",
+                labels.clone(),
+                request.latency.as_secs_f64(),
+            ));
+            samples.push((
+                "modem_scraper_response_bytes",
+                labels,
+                request.response_bytes as f64,
+            ));
+            for (result, count) in &request.results {
+                samples.push((
+                    "modem_scraper_requests_total",
+                    BTreeMap::from([("action", action.clone()), ("result", result.clone())]),
+                    *count as f64,
+                ));
+            }
+        }
+        samples.push((
+            "modem_scraper_logins_total",
+            BTreeMap::new(),
+            stats.logins as f64,
+        ));
+        samples.push((
+            "modem_scraper_parse_failures_total",
+            BTreeMap::new(),
+            stats.parse_failures as f64,
+        ));
+        samples.push((
+            "modem_scraper_sink_errors_total",
+            BTreeMap::new(),
+            stats.sink_errors as f64,
+        ));
+        samples.push((
+            "modem_scraper_circuit_open",
+            BTreeMap::new(),
+            f64::from(u8::from(stats.circuit_open)),
+        ));
+        samples.push((
+            "modem_scraper_circuit_trips_total",
+            BTreeMap::new(),
+            stats.circuit_trips as f64,
+        ));
+        if let Some(dedup_entries) = stats.dedup_entries {
+            samples.push((
+                "modem_scraper_dedup_entries",
+                BTreeMap::new(),
+                dedup_entries as f64,
+            ));
+        }
+    }
+    samples
+}
+
+fn channel_samples(channel: &Channel) -> Vec<Sample> {
+    match channel {
+        Channel::Downstream(c) => {
+            let labels = BTreeMap::from([
+                ("channel_id", c.channel_id.to_string()),
+                ("modulation", c.modulation.to_string()),
+            ]);
+            vec![
+                (
+                    "modem_downstream_lock_status",
+                    labels.clone(),
+                    f64::from(u8::from(c.lock_status)),
+                ),
+                (
+                    "modem_downstream_frequency_hz",
+                    labels.clone(),
+                    f64::from(c.frequency),
+                ),
+                ("modem_downstream_power_dbmv", labels.clone(), c.power),
+                ("modem_downstream_snr_db", labels.clone(), c.snr),
+                (
+                    "modem_downstream_corrected_total",
+                    labels.clone(),
+                    c.corrected as f64,
+                ),
+                (
+                    "modem_downstream_uncorrectables_total",
+                    labels,
+                    c.uncorrectables as f64,
+                ),
+            ]
+        }
+        Channel::Upstream(c) => {
+            let labels = BTreeMap::from([
+                ("channel_id", c.channel_id.to_string()),
+                ("modulation", c.modulation.to_string()),
+            ]);
+            vec![
+                (
+                    "modem_upstream_lock_status",
+                    labels.clone(),
+                    f64::from(u8::from(c.lock_status)),
+                ),
+                (
+                    "modem_upstream_frequency_hz",
+                    labels.clone(),
+                    f64::from(c.frequency),
+                ),
+                (
+                    "modem_upstream_width_hz",
+                    labels.clone(),
+                    f64::from(c.width),
+                ),
+                ("modem_upstream_power_dbmv", labels, c.power),
+            ]
+        }
+        // same families as the SC-QAM channels, so dashboards don't need to care
+        Channel::OfdmaUpstream(c) => {
+            let labels = BTreeMap::from([
+                ("channel_id", c.channel_id.to_string()),
+                ("modulation", "OFDMA".to_owned()),
+            ]);
+            vec![
+                (
+                    "modem_upstream_lock_status",
+                    labels.clone(),
+                    f64::from(u8::from(c.lock_status)),
+                ),
+                (
+                    "modem_upstream_frequency_hz",
+                    labels.clone(),
+                    f64::from(c.frequency),
+                ),
+                (
+                    "modem_upstream_width_hz",
+                    labels.clone(),
+                    f64::from(c.width),
+                ),
+                ("modem_upstream_power_dbmv", labels.clone(), c.power),
+                (
+                    "modem_ofdma_upstream_fft_size",
+                    labels,
+                    f64::from(c.fft_size),
+                ),
+            ]
+        }
+    }
+}
+
+/// `{k="v",...}`, escaped per the exposition format
+fn format_labels(labels: &BTreeMap<&str, String>) -> String {
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| {
+            let v = v
+                .replace('\\', r"\\")
+                .replace('"', "\\\"")
+                .replace('\n', r"\n");
+            format!("{}=\"{}\"", k, v)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+#[async_trait]
+impl MetricSink for PrometheusState {
+    fn name(&self) -> &'static str {
+        "prometheus"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        self.update(tags, scraped.clone());
+        Ok(())
+    }
+}
+
+async fn metrics_handler(State(state): State<PrometheusState>) -> String {
+    state.render()
+}
+
+/// Serves `/metrics` on `address` until the process exits
+pub async fn serve(address: SocketAddr, state: PrometheusState) -> Result<(), hyper::Error> {
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(state);
+    axum::Server::bind(&address)
+        .serve(app.into_make_service())
+        .await
+}
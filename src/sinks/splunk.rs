@@ -0,0 +1,117 @@
+use super::{LogSink, SinkError};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use modem_scraper_lib::payloads::LogEntry;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+/// `splunk:` in the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct SplunkSettings {
+    /// e.g. `https://splunk:8088`
+    pub url: String,
+    pub token: String,
+    #[serde(default = "default_sourcetype")]
+    pub sourcetype: String,
+    /// the token's default index when unset
+    pub index: Option<String>,
+    /// events per request
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+}
+
+fn default_sourcetype() -> String {
+    "modem:log".to_owned()
+}
+
+fn default_batch_size() -> usize {
+    100
+}
+
+/// Sends the event log to a Splunk HTTP Event Collector
+#[derive(Debug, Clone)]
+pub struct SplunkSink {
+    http_client: reqwest::Client,
+    settings: SplunkSettings,
+    /// the modem returns its whole log every time and HEC doesn't dedupe, so only send what's
+    /// newer than this
+    latest: Option<DateTime<Utc>>,
+}
+
+impl SplunkSink {
+    pub fn new(http_client: reqwest::Client, settings: SplunkSettings) -> SplunkSink {
+        SplunkSink {
+            http_client,
+            settings,
+            latest: None,
+        }
+    }
+}
+
+#[async_trait]
+impl LogSink for SplunkSink {
+    fn name(&self) -> &'static str {
+        "splunk"
+    }
+
+    async fn write_logs(
+        &mut self,
+        logs: &[LogEntry],
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let mut new_logs: Vec<&LogEntry> = logs
+            .iter()
+            .filter(|log_entry| {
+                self.latest
+                    .map_or(true, |latest| log_entry.timestamp > latest)
+            })
+            .collect();
+        // oldest first, so a failed batch never leaves older entries behind a newer `latest`
+        new_logs.sort_by_key(|log_entry| log_entry.timestamp);
+        let hostname = tags.get("hostname").cloned().unwrap_or_default();
+        let url = format!(
+            "{}/services/collector/event",
+            self.settings.url.trim_end_matches('/')
+        );
+
+        for batch in new_logs.chunks(self.settings.batch_size.max(1)) {
+            // HEC takes events back to back rather than as an array
+            let mut body = String::new();
+            for log_entry in batch {
+                let mut event = json!({
+                    "time": log_entry.timestamp.timestamp_millis() as f64 / 1000.0,
+                    "host": hostname,
+                    "source": env!("CARGO_PKG_NAME"),
+                    "sourcetype": self.settings.sourcetype,
+                    "event": {
+                        "level": log_entry.level.as_str(),
+                        "message": log_entry.message,
+                        "fields": log_entry.fields(),
+                    },
+                    "fields": tags,
+                });
+                if let Some(index) = &self.settings.index {
+                    event["index"] = json!(index);
+                }
+                body.push_str(&event.to_string());
+            }
+
+            self.http_client
+                .post(&url)
+                .header("Authorization", format!("Splunk {}", self.settings.token))
+                .body(body)
+                .send()
+                .await?
+                .error_for_status()?;
+
+            // only after the batch made it, so a failed one is retried next scrape
+            self.latest = batch
+                .iter()
+                .map(|log_entry| log_entry.timestamp)
+                .chain(self.latest)
+                .max();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+use super::{LogSink, MetricSink, SinkError};
+use crate::scraped::ScrapedMetrics;
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use modem_scraper_lib::payloads::{Channel, LogEntry};
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// `sqlite:` in the config
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SqliteSettings {
+    pub path: PathBuf,
+    /// scrapes and logs older than this are deleted
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u32,
+}
+
+fn default_retention_days() -> u32 {
+    30
+}
+
+const SCHEMA: &str = "
+PRAGMA foreign_keys = ON;
+PRAGMA journal_mode = WAL;
+CREATE TABLE IF NOT EXISTS scrapes (
+    id INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    hostname TEXT NOT NULL,
+    -- every tag, including device info like the provisioning status
+    tags TEXT NOT NULL,
+    uptime_seconds INTEGER
+);
+CREATE INDEX IF NOT EXISTS scrapes_hostname_timestamp ON scrapes (hostname, timestamp);
+CREATE TABLE IF NOT EXISTS channels (
+    scrape_id INTEGER NOT NULL REFERENCES scrapes (id) ON DELETE CASCADE,
+    direction TEXT NOT NULL,
+    channel_id INTEGER NOT NULL,
+    modulation TEXT,
+    lock_status INTEGER NOT NULL,
+    frequency INTEGER NOT NULL,
+    width INTEGER,
+    power REAL NOT NULL,
+    snr REAL,
+    corrected INTEGER,
+    uncorrectables INTEGER,
+    fft_size INTEGER
+);
+CREATE INDEX IF NOT EXISTS channels_scrape_id ON channels (scrape_id);
+CREATE TABLE IF NOT EXISTS logs (
+    timestamp INTEGER NOT NULL,
+    hostname TEXT NOT NULL,
+    level TEXT NOT NULL,
+    message TEXT NOT NULL,
+    -- the modem hands back its whole log every scrape
+    UNIQUE (hostname, timestamp, message)
+);
+";
+
+/// Keeps every scrape and log entry in a local SQLite database, for digging into ISP issues
+/// without a metrics stack. See `modem-scraper query`.
+#[derive(Debug, Clone)]
+pub struct SqliteSink {
+    connection: Arc<Mutex<Connection>>,
+    retention_seconds: i64,
+}
+
+impl SqliteSink {
+    /// Opens (or creates) the database and makes sure the tables exist
+    pub fn open(settings: &SqliteSettings) -> Result<SqliteSink, SinkError> {
+        let connection = Connection::open(&settings.path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteSink {
+            connection: Arc::new(Mutex::new(connection)),
+            retention_seconds: i64::from(settings.retention_days) * 24 * 60 * 60,
+        })
+    }
+
+    /// rusqlite blocks, so every query runs off the async threads
+    async fn with_connection<T, F>(&self, f: F) -> Result<T, SinkError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, rusqlite::Error> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        let result =
+            tokio::task::spawn_blocking(move || f(&mut connection.lock().unwrap())).await?;
+        Ok(result?)
+    }
+
+    /// Deletes everything past the retention period. Channels go with their scrape.
+    fn prune(connection: &Connection, cutoff: i64) -> Result<(), rusqlite::Error> {
+        connection.execute("DELETE FROM scrapes WHERE timestamp < ?1", params![cutoff])?;
+        connection.execute("DELETE FROM logs WHERE timestamp < ?1", params![cutoff])?;
+        Ok(())
+    }
+}
+
+/// direction, channel id, modulation, lock status, frequency, width, power, snr, corrected,
+/// uncorrectables, fft size
+type ChannelRow = (
+    &'static str,
+    u8,
+    Option<String>,
+    bool,
+    u32,
+    Option<u32>,
+    f64,
+    Option<f64>,
+    Option<i64>,
+    Option<i64>,
+    Option<u32>,
+);
+
+fn channel_row(channel: &Channel) -> ChannelRow {
+    match channel {
+        Channel::Downstream(c) => (
+            "downstream",
+            c.channel_id,
+            Some(c.modulation.to_string()),
+            c.lock_status,
+            c.frequency,
+            None,
+            c.power,
+            Some(c.snr),
+            // SQLite integers are signed
+            Some(i64::try_from(c.corrected).unwrap_or(i64::MAX)),
+            Some(i64::try_from(c.uncorrectables).unwrap_or(i64::MAX)),
+            None,
+        ),
+        Channel::Upstream(c) => (
+            "upstream",
+            c.channel_id,
+            Some(c.modulation.to_string()),
+            c.lock_status,
+            c.frequency,
+            Some(c.width),
+            c.power,
+            None,
+            None,
+            None,
+            None,
+        ),
+        Channel::OfdmaUpstream(c) => (
+            "ofdma_upstream",
+            c.channel_id,
+            Some("OFDMA".to_owned()),
+            c.lock_status,
+            c.frequency,
+            Some(c.width),
+            c.power,
+            None,
+            None,
+            None,
+            Some(c.fft_size),
+        ),
+    }
+}
+
+#[async_trait]
+impl MetricSink for SqliteSink {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let now = Utc::now().timestamp();
+        let cutoff = now - self.retention_seconds;
+        let hostname = tags.get("hostname").cloned().unwrap_or_default();
+        let tags = serde_json::to_string(tags)?;
+        let uptime = scraped
+            .metrics
+            .uptime
+            .map(|uptime| i64::try_from(uptime.as_secs()).unwrap_or(i64::MAX));
+        let rows: Vec<ChannelRow> = scraped.metrics.channels.iter().map(channel_row).collect();
+
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute(
+                "INSERT INTO scrapes (timestamp, hostname, tags, uptime_seconds) VALUES (?1, ?2, ?3, ?4)",
+                params![now, hostname, tags, uptime],
+            )?;
+            let scrape_id = transaction.last_insert_rowid();
+            for row in rows {
+                transaction.execute(
+                    "INSERT INTO channels (scrape_id, direction, channel_id, modulation, lock_status, frequency, width, power, snr, corrected, uncorrectables, fft_size)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                    params![
+                        scrape_id, row.0, row.1, row.2, row.3, row.4, row.5, row.6, row.7, row.8,
+                        row.9, row.10
+                    ],
+                )?;
+            }
+            SqliteSink::prune(&transaction, cutoff)?;
+            transaction.commit()
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl LogSink for SqliteSink {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn write_logs(
+        &mut self,
+        logs: &[LogEntry],
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let hostname = tags.get("hostname").cloned().unwrap_or_default();
+        let logs: Vec<(i64, String, String)> = logs
+            .iter()
+            .map(|log_entry| {
+                (
+                    log_entry.timestamp.timestamp(),
+                    log_entry.level.to_string(),
+                    log_entry.message.clone(),
+                )
+            })
+            .collect();
+
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            for (timestamp, level, message) in logs {
+                transaction.execute(
+                    "INSERT OR IGNORE INTO logs (timestamp, hostname, level, message) VALUES (?1, ?2, ?3, ?4)",
+                    params![timestamp, hostname, level, message],
+                )?;
+            }
+            transaction.commit()
+        })
+        .await
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .map(|timestamp| timestamp.to_rfc3339())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// What `modem-scraper query` prints: the latest scrape of each modem, then the logs from the
+/// last `hours`
+pub fn dump(settings: &SqliteSettings, hours: i64) -> Result<(), SinkError> {
+    let connection = Connection::open(&settings.path)?;
+
+    let mut scrapes = connection.prepare(
+        "SELECT id, timestamp, hostname, uptime_seconds FROM scrapes
+         WHERE id IN (SELECT MAX(id) FROM scrapes GROUP BY hostname)
+         ORDER BY hostname",
+    )?;
+    let scrapes = scrapes
+        .query_map(params![], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut channels = connection.prepare(
+        "SELECT direction, channel_id, COALESCE(modulation, ''), lock_status, frequency, power,
+                snr, corrected, uncorrectables
+         FROM channels WHERE scrape_id = ?1 ORDER BY direction, channel_id",
+    )?;
+    for (scrape_id, timestamp, hostname, uptime) in scrapes {
+        println!("{} at {}", hostname, format_timestamp(timestamp));
+        if let Some(uptime) = uptime {
+            println!("uptime: {}s", uptime);
+        }
+        println!(
+            "{:<15} {:>3} {:<7} {:<6} {:>10} {:>6} {:>4} {:>10} {:>10}",
+            "direction", "id", "mod", "locked", "frequency", "power", "snr", "corrected", "uncorr"
+        );
+        let rows = channels.query_map(params![scrape_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u8>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, bool>(3)?,
+                row.get::<_, u32>(4)?,
+                row.get::<_, f64>(5)?,
+                row.get::<_, Option<f64>>(6)?,
+                row.get::<_, Option<i64>>(7)?,
+                row.get::<_, Option<i64>>(8)?,
+            ))
+        })?;
+        for row in rows {
+            let (direction, id, modulation, locked, frequency, power, snr, corrected, uncorr) =
+                row?;
+            let or_dash = |n: Option<i64>| n.map_or("-".to_owned(), |n| n.to_string());
+            println!(
+                "{:<15} {:>3} {:<7} {:<6} {:>10} {:>6.1} {:>4} {:>10} {:>10}",
+                direction,
+                id,
+                modulation,
+                locked,
+                frequency,
+                power,
+                snr.map_or("-".to_owned(), |snr| format!("{:.1}", snr)),
+                or_dash(corrected),
+                or_dash(uncorr)
+            );
+        }
+        println!();
+    }
+
+    let cutoff = Utc::now().timestamp() - hours * 60 * 60;
+    let mut logs = connection.prepare(
+        "SELECT timestamp, hostname, level, message FROM logs WHERE timestamp >= ?1
+         ORDER BY timestamp",
+    )?;
+    let logs = logs.query_map(params![cutoff], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+        ))
+    })?;
+    for log_entry in logs {
+        let (timestamp, hostname, level, message) = log_entry?;
+        println!(
+            "{} {} {} {}",
+            format_timestamp(timestamp),
+            hostname,
+            level,
+            message
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,234 @@
+use super::{LogSink, MetricSink, SinkError};
+use crate::scraped::ScrapedMetrics;
+use crate::speedtest::SpeedtestResult;
+use async_trait::async_trait;
+use axum::extract::State;
+use axum::response::Html;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use modem_scraper_lib::payloads::{Channel, LogEntry};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+/// How many scrapes of history the dashboard gets. Two hours at the usual minute interval.
+const HISTORY: usize = 120;
+
+#[derive(Debug, Clone, Serialize)]
+struct Sample {
+    time: DateTime<Utc>,
+    power: f64,
+    /// downstream only
+    snr: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ModemState {
+    tags: HashMap<String, String>,
+    metrics: Option<(DateTime<Utc>, ScrapedMetrics)>,
+    logs: Option<(DateTime<Utc>, Vec<LogEntry>)>,
+    /// by `<type>-<channel id>`. Only channels in the latest scrape are kept.
+    history: BTreeMap<String, VecDeque<Sample>>,
+    /// kept until there's a new one, since only one scrape has it
+    speedtest: Option<SpeedtestResult>,
+}
+
+impl ModemState {
+    fn record(&mut self, time: DateTime<Utc>, scraped: &ScrapedMetrics) {
+        let samples: BTreeMap<String, Sample> = scraped
+            .metrics
+            .channels
+            .iter()
+            .map(|channel| {
+                let (key, power, snr) = match channel {
+                    Channel::Downstream(c) => {
+                        (format!("downstream-{}", c.channel_id), c.power, Some(c.snr))
+                    }
+                    Channel::Upstream(c) => (format!("upstream-{}", c.channel_id), c.power, None),
+                    Channel::OfdmaUpstream(c) => {
+                        (format!("ofdma_upstream-{}", c.channel_id), c.power, None)
+                    }
+                };
+                (key, Sample { time, power, snr })
+            })
+            .collect();
+        self.history.retain(|key, _| samples.contains_key(key));
+        for (key, sample) in samples {
+            let history = self.history.entry(key).or_default();
+            if history.len() == HISTORY {
+                history.pop_front();
+            }
+            history.push_back(sample);
+        }
+    }
+}
+
+/// Latest scrape of every modem, keyed by hostname, for `/api/v1/`. Scrape tasks write, the API
+/// reads.
+#[derive(Debug, Clone, Default)]
+pub struct StatusState {
+    latest: Arc<RwLock<BTreeMap<String, ModemState>>>,
+}
+
+impl StatusState {
+    fn update(&self, tags: &HashMap<String, String>, update: impl FnOnce(&mut ModemState)) {
+        let key = tags.get("hostname").cloned().unwrap_or_default();
+        let mut latest = self.latest.write().unwrap();
+        let modem = latest.entry(key).or_default();
+        modem.tags = tags.clone();
+        update(modem);
+    }
+
+    /// `render` for every modem, by hostname
+    fn render(&self, render: impl Fn(&ModemState) -> Value) -> Json<Value> {
+        let latest = self.latest.read().unwrap();
+        Json(Value::Object(
+            latest
+                .iter()
+                .map(|(hostname, modem)| (hostname.clone(), render(modem)))
+                .collect(),
+        ))
+    }
+}
+
+#[async_trait]
+impl MetricSink for StatusState {
+    fn name(&self) -> &'static str {
+        "status"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        self.update(tags, |modem| {
+            let now = Utc::now();
+            modem.record(now, scraped);
+            if scraped.speedtest.is_some() {
+                modem.speedtest = scraped.speedtest.clone();
+            }
+            modem.metrics = Some((now, scraped.clone()));
+        });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LogSink for StatusState {
+    fn name(&self) -> &'static str {
+        "status"
+    }
+
+    async fn write_logs(
+        &mut self,
+        logs: &[LogEntry],
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        self.update(tags, |modem| modem.logs = Some((Utc::now(), logs.to_vec())));
+        Ok(())
+    }
+}
+
+/// Flat, with a `type`, the modulation as text and the channel's health
+fn channel_json(scraped: &ScrapedMetrics, channel: &Channel) -> Value {
+    let mut value = match channel {
+        Channel::Downstream(c) => {
+            let mut value = json!(c);
+            value["type"] = json!("downstream");
+            value["modulation"] = json!(c.modulation.to_string());
+            if let Some(delta) = scraped.codeword_deltas.get(&c.channel_id) {
+                value["codewords"] = json!(delta);
+            }
+            value
+        }
+        Channel::Upstream(c) => {
+            let mut value = json!(c);
+            value["type"] = json!("upstream");
+            value["modulation"] = json!(c.modulation.to_string());
+            value
+        }
+        Channel::OfdmaUpstream(c) => {
+            let mut value = json!(c);
+            value["type"] = json!("ofdma_upstream");
+            value
+        }
+    };
+    value["health"] = json!(scraped.channel_health(channel));
+    value
+}
+
+/// The modem's own state and tags, without the channels
+async fn status_handler(State(state): State<StatusState>) -> Json<Value> {
+    state.render(|modem| {
+        let mut value = json!({ "tags": modem.tags });
+        if let Some((updated, scraped)) = &modem.metrics {
+            value["updated"] = json!(updated);
+            value["uptime_seconds"] = json!(scraped.metrics.uptime.map(|uptime| uptime.as_secs()));
+            value["time"] = json!(scraped.metrics.time);
+            value["network_access"] = json!(scraped.metrics.status.network_access);
+            value["internet_connected"] = json!(scraped.metrics.status.internet_connected);
+            value["startup"] = json!(scraped
+                .metrics
+                .status
+                .startup
+                .iter()
+                .map(|(stage, status)| (stage.clone(), status.as_str()))
+                .collect::<BTreeMap<_, _>>());
+            value["health"] = json!(scraped.health());
+            value["outages"] = json!(scraped.outages);
+            value["speedtest"] = json!(modem.speedtest);
+        }
+        value
+    })
+}
+
+async fn channels_handler(State(state): State<StatusState>) -> Json<Value> {
+    state.render(|modem| match &modem.metrics {
+        Some((updated, scraped)) => json!({
+            "updated": updated,
+            "channels": scraped
+                .metrics
+                .channels
+                .iter()
+                .map(|channel| channel_json(scraped, channel))
+                .collect::<Vec<_>>(),
+        }),
+        None => json!({ "updated": null, "channels": [] }),
+    })
+}
+
+async fn logs_handler(State(state): State<StatusState>) -> Json<Value> {
+    state.render(|modem| match &modem.logs {
+        Some((updated, logs)) => json!({ "updated": updated, "logs": logs }),
+        None => json!({ "updated": null, "logs": [] }),
+    })
+}
+
+/// Power and SNR of each channel over the last [HISTORY] scrapes, oldest first
+async fn history_handler(State(state): State<StatusState>) -> Json<Value> {
+    state.render(|modem| json!(modem.history))
+}
+
+/// Everything's fetched from the API by the page itself, so there's nothing to template
+async fn dashboard_handler() -> Html<&'static str> {
+    Html(include_str!("dashboard.html"))
+}
+
+/// Serves the dashboard on `/`, and `/api/v1/status`, `/api/v1/channels`, `/api/v1/logs` and
+/// `/api/v1/history` for it and anything else, on `address` until the process exits
+pub async fn serve(address: SocketAddr, state: StatusState) -> Result<(), hyper::Error> {
+    let app = Router::new()
+        .route("/", get(dashboard_handler))
+        .route("/api/v1/status", get(status_handler))
+        .route("/api/v1/channels", get(channels_handler))
+        .route("/api/v1/logs", get(logs_handler))
+        .route("/api/v1/history", get(history_handler))
+        .with_state(state);
+    axum::Server::bind(&address)
+        .serve(app.into_make_service())
+        .await
+}
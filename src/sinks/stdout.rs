@@ -0,0 +1,118 @@
+use super::influx::to_line_protocol;
+use super::{metrics_to_points, LogSink, MetricSink, SinkError};
+use crate::scraped::ScrapedMetrics;
+use async_trait::async_trait;
+use chrono::Utc;
+use modem_scraper_lib::payloads::LogEntry;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use telegraf::protocol::FieldData;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StdoutFormat {
+    /// line protocol for metrics, `timestamp hostname level message` for logs
+    #[default]
+    Text,
+    /// one object per point or log entry, for piping into vector/fluent-bit/jq
+    Json,
+}
+
+/// Prints metrics and logs to stdout. Handy for checking a new modem works.
+#[derive(Debug, Clone, Default)]
+pub struct StdoutSink {
+    pub format: StdoutFormat,
+}
+
+fn field_value(value: &FieldData) -> Value {
+    match value {
+        FieldData::Boolean(b) => json!(b),
+        FieldData::UNumber(n) => json!(n),
+        FieldData::Number(n) => json!(n),
+        FieldData::Float(f) => json!(f),
+        FieldData::Str(s) => json!(s),
+    }
+}
+
+#[async_trait]
+impl MetricSink for StdoutSink {
+    fn name(&self) -> &'static str {
+        "stdout"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        // every point in a scrape shares a timestamp, same as the other sinks
+        let timestamp = Utc::now().to_rfc3339();
+        for point in metrics_to_points(scraped, tags) {
+            match self.format {
+                StdoutFormat::Text => println!("{}", to_line_protocol(&point)),
+                StdoutFormat::Json => {
+                    let tags: Map<String, Value> = point
+                        .tags
+                        .iter()
+                        .map(|tag| (tag.name.clone(), json!(tag.value)))
+                        .collect();
+                    let fields: Map<String, Value> = point
+                        .fields
+                        .iter()
+                        .map(|field| (field.name.clone(), field_value(&field.value)))
+                        .collect();
+                    println!(
+                        "{}",
+                        json!({
+                            "type": "metric",
+                            "timestamp": timestamp,
+                            "measurement": point.measurement,
+                            "tags": tags,
+                            "fields": fields,
+                        })
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LogSink for StdoutSink {
+    fn name(&self) -> &'static str {
+        "stdout"
+    }
+
+    async fn write_logs(
+        &mut self,
+        logs: &[LogEntry],
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let hostname = tags.get("hostname").map(String::as_str).unwrap_or_default();
+        for log_entry in logs {
+            match self.format {
+                StdoutFormat::Text => println!(
+                    "{} {} {} {}",
+                    log_entry.timestamp.to_rfc3339(),
+                    hostname,
+                    log_entry.level,
+                    log_entry.message
+                ),
+                StdoutFormat::Json => println!(
+                    "{}",
+                    json!({
+                        "type": "log",
+                        "timestamp": log_entry.timestamp.to_rfc3339(),
+                        "level": log_entry.level.as_str(),
+                        "message": log_entry.message,
+                        "fields": log_entry.fields(),
+                        "tags": tags,
+                    })
+                ),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use super::influx::to_line_protocol;
+use super::{metrics_to_points, MetricSink, SinkError, SinkState};
+use crate::scraped::ScrapedMetrics;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::time::Duration;
+use telegraf::Point;
+use tokio::time::Instant;
+use tracing::debug;
+
+/// The `telegraf_*` keys besides the address
+#[derive(Debug, Clone, Default)]
+pub struct TelegrafOptions {
+    /// don't send a point again while it's the same as the last one sent for its series
+    pub skip_unchanged: bool,
+    /// but send it anyway once it's been this long, so the series doesn't look like it stopped
+    pub resend_unchanged: Duration,
+    /// hold points back until there are this many, rather than writing every scrape
+    pub batch_size: Option<usize>,
+    /// but no longer than this
+    pub flush_interval: Duration,
+}
+
+/// What a [TelegrafSink] hands over on a reload
+struct TelegrafState {
+    sent: HashMap<String, (String, Instant)>,
+    pending: Vec<Point>,
+    held_since: Option<Instant>,
+}
+
+/// Sends points to telegraf's socket listener. The connection is made on the first write, and
+/// made again after a write fails, so telegraf restarting doesn't need a scraper restart too.
+pub struct TelegrafSink {
+    /// `tcp://`, `udp://` or `unix://`
+    address: String,
+    options: TelegrafOptions,
+    client: Option<telegraf::Client>,
+    /// line protocol of the last point sent for each series, minus the timestamp, and when
+    sent: HashMap<String, (String, Instant)>,
+    /// waiting for the batch to fill up
+    pending: Vec<Point>,
+    /// when the oldest point in `pending` was held back
+    held_since: Option<Instant>,
+}
+
+impl TelegrafSink {
+    /// `telegraf_address`, e.g. `tcp://localhost:8094`, `udp://localhost:8094` or
+    /// `unix:///tmp/telegraf.sock`. A bare `host:port` is TCP.
+    pub fn new(
+        telegraf_address: &str,
+        options: TelegrafOptions,
+    ) -> Result<TelegrafSink, SinkError> {
+        let address = if telegraf_address.contains("://") {
+            telegraf_address.to_owned()
+        } else {
+            format!("tcp://{}", telegraf_address)
+        };
+        match address.split("://").next() {
+            Some("tcp" | "udp" | "unix") => Ok(TelegrafSink {
+                address,
+                options,
+                client: None,
+                sent: HashMap::new(),
+                pending: Vec::new(),
+                held_since: None,
+            }),
+            _ => Err(format!("unsupported telegraf address {}", telegraf_address).into()),
+        }
+    }
+
+    /// Whether the point is different from the last one sent for its series, or that was a while
+    /// ago. Remembers it as sent if so.
+    fn changed(&mut self, point: &Point) -> bool {
+        let mut series = point.measurement.clone();
+        let mut tags: Vec<String> = point
+            .tags
+            .iter()
+            .map(|tag| format!("{}={}", tag.name, tag.value))
+            .collect();
+        tags.sort();
+        for tag in tags {
+            series.push(',');
+            series.push_str(&tag);
+        }
+        let mut untimed = point.clone();
+        untimed.timestamp = None;
+        let line = to_line_protocol(&untimed);
+
+        let now = Instant::now();
+        match self.sent.get(&series) {
+            Some((previous, sent_at))
+                if *previous == line && now - *sent_at < self.options.resend_unchanged =>
+            {
+                false
+            }
+            _ => {
+                self.sent.insert(series, (line, now));
+                true
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl MetricSink for TelegrafSink {
+    fn name(&self) -> &'static str {
+        "telegraf"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let mut points = metrics_to_points(scraped, tags);
+        if self.options.skip_unchanged {
+            let before = points.len();
+            points.retain(|point| self.changed(point));
+            debug!("Skipping {} unchanged points", before - points.len());
+        }
+        self.pending.append(&mut points);
+        if let Some(batch_size) = self.options.batch_size {
+            let held_since = *self.held_since.get_or_insert_with(Instant::now);
+            if self.pending.len() < batch_size && held_since.elapsed() < self.options.flush_interval
+            {
+                return Ok(());
+            }
+        }
+        let points = std::mem::take(&mut self.pending);
+        self.held_since = None;
+        if points.is_empty() {
+            return Ok(());
+        }
+
+        let client = match &mut self.client {
+            Some(client) => client,
+            None => self.client.insert(telegraf::Client::new(&self.address)?),
+        };
+        if let Err(e) = client.write_points(&points) {
+            self.client = None;
+            // they weren't sent after all
+            self.sent.clear();
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    fn take_state(&mut self) -> Option<SinkState> {
+        Some(Box::new(TelegrafState {
+            sent: std::mem::take(&mut self.sent),
+            pending: std::mem::take(&mut self.pending),
+            held_since: self.held_since.take(),
+        }))
+    }
+
+    fn restore(&mut self, state: SinkState) {
+        if let Ok(state) = state.downcast::<TelegrafState>() {
+            self.sent = state.sent;
+            self.pending = state.pending;
+            self.held_since = state.held_since;
+        }
+    }
+}
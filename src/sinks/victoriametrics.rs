@@ -0,0 +1,70 @@
+use super::prometheus::exposition;
+use super::{MetricSink, SinkError};
+use crate::scraped::ScrapedMetrics;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// `victoriametrics:` in the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct VictoriaMetricsSettings {
+    /// e.g. `http://victoriametrics:8428`
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// added to every sample via `extra_label`
+    #[serde(default)]
+    pub extra_labels: HashMap<String, String>,
+}
+
+/// Pushes each scrape to `/api/v1/import/prometheus`, in the same format `/metrics` serves
+#[derive(Debug, Clone)]
+pub struct VictoriaMetricsSink {
+    http_client: reqwest::Client,
+    settings: VictoriaMetricsSettings,
+}
+
+impl VictoriaMetricsSink {
+    pub fn new(
+        http_client: reqwest::Client,
+        settings: VictoriaMetricsSettings,
+    ) -> VictoriaMetricsSink {
+        VictoriaMetricsSink {
+            http_client,
+            settings,
+        }
+    }
+}
+
+#[async_trait]
+impl MetricSink for VictoriaMetricsSink {
+    fn name(&self) -> &'static str {
+        "victoriametrics"
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let extra_labels: Vec<(&str, String)> = self
+            .settings
+            .extra_labels
+            .iter()
+            .map(|(k, v)| ("extra_label", format!("{}={}", k, v)))
+            .collect();
+        let mut request = self
+            .http_client
+            .post(format!(
+                "{}/api/v1/import/prometheus",
+                self.settings.url.trim_end_matches('/')
+            ))
+            .query(&extra_labels)
+            .body(exposition(std::iter::once((tags, scraped))));
+        if let Some(username) = &self.settings.username {
+            request = request.basic_auth(username, self.settings.password.as_ref());
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{error, info, instrument};
+
+/// One speedtest, sent with the next scrape so it can be lined up with the channels
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpeedtestResult {
+    pub time: DateTime<Utc>,
+    /// bits per second
+    pub download: f64,
+    pub upload: f64,
+    pub latency: Duration,
+    pub jitter: Option<Duration>,
+    /// percent
+    pub packet_loss: Option<f64>,
+    /// whatever the speedtest calls the server it used
+    pub server: Option<String>,
+}
+
+pub type SpeedtestError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedtestBackend {
+    /// Ookla's `speedtest` CLI, which has to be installed
+    #[default]
+    Ookla,
+    /// a LibreSpeed server, over HTTP
+    Librespeed,
+}
+
+/// `speedtest:` under a modem
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SpeedtestSettings {
+    #[serde(default)]
+    pub backend: SpeedtestBackend,
+    /// a speedtest uses a fair bit of data, so not every scrape
+    #[serde(default = "default_interval_seconds")]
+    pub interval_seconds: u64,
+    /// the Ookla CLI
+    #[serde(default = "default_command")]
+    pub command: String,
+    /// Ookla server id, the closest one if unset
+    pub server_id: Option<u32>,
+    /// the LibreSpeed backend, e.g. `https://speed.example.com/backend`
+    pub url: Option<String>,
+    /// how much LibreSpeed downloads and uploads
+    #[serde(default = "default_download_megabytes")]
+    pub download_megabytes: u64,
+    #[serde(default = "default_upload_megabytes")]
+    pub upload_megabytes: u64,
+}
+
+fn default_interval_seconds() -> u64 {
+    3600
+}
+
+fn default_command() -> String {
+    "speedtest".to_owned()
+}
+
+fn default_download_megabytes() -> u64 {
+    100
+}
+
+fn default_upload_megabytes() -> u64 {
+    25
+}
+
+/// How many times LibreSpeed's server is pinged
+const PINGS: usize = 10;
+
+/// What `speedtest --format=json` prints, the parts that matter
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OoklaResult {
+    ping: OoklaPing,
+    download: OoklaTransfer,
+    upload: OoklaTransfer,
+    packet_loss: Option<f64>,
+    server: Option<OoklaServer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OoklaPing {
+    /// ms
+    latency: f64,
+    jitter: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OoklaTransfer {
+    /// bytes per second
+    bandwidth: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OoklaServer {
+    name: String,
+    location: Option<String>,
+}
+
+fn millis(ms: f64) -> Duration {
+    Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+}
+
+async fn ookla(settings: &SpeedtestSettings) -> Result<SpeedtestResult, SpeedtestError> {
+    let mut command = tokio::process::Command::new(&settings.command);
+    command.args(["--format=json", "--accept-license", "--accept-gdpr"]);
+    if let Some(server_id) = settings.server_id {
+        command.arg(format!("--server-id={}", server_id));
+    }
+    let output = command.kill_on_drop(true).output().await?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} exited with {}: {}",
+            settings.command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    let result: OoklaResult = serde_json::from_slice(&output.stdout)?;
+    Ok(SpeedtestResult {
+        time: Utc::now(),
+        download: result.download.bandwidth * 8.0,
+        upload: result.upload.bandwidth * 8.0,
+        latency: millis(result.ping.latency),
+        jitter: Some(millis(result.ping.jitter)),
+        packet_loss: result.packet_loss,
+        server: result.server.map(|server| match server.location {
+            Some(location) => format!("{} ({})", server.name, location),
+            None => server.name,
+        }),
+    })
+}
+
+/// The same way LibreSpeed's own page does it: pings to `empty.php`, a download from
+/// `garbage.php`, and an upload to `empty.php`. One connection each, so it's a little slower than
+/// the page.
+async fn librespeed(settings: &SpeedtestSettings) -> Result<SpeedtestResult, SpeedtestError> {
+    let url = settings
+        .url
+        .as_deref()
+        .ok_or("the librespeed backend needs a url")?
+        .trim_end_matches('/');
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(300))
+        .build()?;
+
+    let mut pings = Vec::with_capacity(PINGS);
+    for _ in 0..PINGS {
+        let started = Instant::now();
+        http_client
+            .get(format!("{}/empty.php", url))
+            .send()
+            .await?
+            .error_for_status()?;
+        pings.push(started.elapsed());
+    }
+    let latency = pings.iter().min().copied().unwrap_or_default();
+    let jitter = pings
+        .windows(2)
+        .map(|pair| pair[0].max(pair[1]) - pair[0].min(pair[1]))
+        .sum::<Duration>()
+        / (PINGS as u32 - 1);
+
+    let started = Instant::now();
+    let mut response = http_client
+        .get(format!(
+            "{}/garbage.php?ckSize={}",
+            url, settings.download_megabytes
+        ))
+        .send()
+        .await?
+        .error_for_status()?;
+    let mut downloaded = 0;
+    while let Some(chunk) = response.chunk().await? {
+        downloaded += chunk.len();
+    }
+    let download = downloaded as f64 * 8.0 / started.elapsed().as_secs_f64();
+
+    let body = vec![0u8; settings.upload_megabytes as usize * 1024 * 1024];
+    let uploaded = body.len();
+    let started = Instant::now();
+    http_client
+        .post(format!("{}/empty.php", url))
+        .body(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    let upload = uploaded as f64 * 8.0 / started.elapsed().as_secs_f64();
+
+    Ok(SpeedtestResult {
+        time: Utc::now(),
+        download,
+        upload,
+        latency,
+        jitter: Some(jitter),
+        packet_loss: None,
+        server: Some(url.to_owned()),
+    })
+}
+
+#[instrument(skip(settings), fields(backend = ?settings.backend))]
+pub async fn run(settings: &SpeedtestSettings) -> Result<SpeedtestResult, SpeedtestError> {
+    match settings.backend {
+        SpeedtestBackend::Ookla => ookla(settings).await,
+        SpeedtestBackend::Librespeed => librespeed(settings).await,
+    }
+}
+
+/// Runs a speedtest every `interval_seconds` in the background, starting straight away, and keeps
+/// the latest result for the next scrape. One per modem that has `speedtest:`.
+#[derive(Debug)]
+pub struct Speedtester {
+    latest: Arc<Mutex<Option<SpeedtestResult>>>,
+    task: JoinHandle<()>,
+}
+
+impl Speedtester {
+    pub fn spawn(settings: SpeedtestSettings, device_address: String) -> Speedtester {
+        let latest = Arc::new(Mutex::new(None));
+        let task = tokio::spawn({
+            let latest = latest.clone();
+            async move {
+                let mut interval =
+                    tokio::time::interval(Duration::from_secs(settings.interval_seconds.max(1)));
+                // a speedtest that ran long shouldn't be followed right away by another
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    interval.tick().await;
+                    match run(&settings).await {
+                        Ok(result) => {
+                            info!(
+                                "Speedtest for {}: {:.1} Mbps down, {:.1} Mbps up, {}ms",
+                                device_address,
+                                result.download / 1e6,
+                                result.upload / 1e6,
+                                result.latency.as_millis()
+                            );
+                            *latest.lock().unwrap() = Some(result);
+                        }
+                        Err(e) => error!("Speedtest for {} failed: {}", device_address, e),
+                    }
+                }
+            }
+        });
+        Speedtester { latest, task }
+    }
+
+    /// The result that finished since the previous call, if there is one
+    pub fn take(&self) -> Option<SpeedtestResult> {
+        self.latest.lock().unwrap().take()
+    }
+}
+
+impl Drop for Speedtester {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,334 @@
+use crate::scraped::ScrapedMetrics;
+use crate::sinks::{LogSink, MetricSink, SinkError, SinkState};
+use async_trait::async_trait;
+use modem_scraper_lib::payloads::LogEntry;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// `spool:` in the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpoolSettings {
+    pub directory: PathBuf,
+    /// per output and modem. The oldest samples are dropped to stay under it.
+    #[serde(default = "default_max_megabytes")]
+    pub max_megabytes: u64,
+}
+
+fn default_max_megabytes() -> u64 {
+    64
+}
+
+/// Each file is at most this fraction of the total, so dropping the oldest one doesn't lose much
+const SEGMENTS: u64 = 8;
+
+/// One write that didn't make it out
+#[derive(Serialize, Deserialize)]
+struct Record<T> {
+    /// counts up from 1, across every segment
+    id: u64,
+    tags: HashMap<String, String>,
+    payload: T,
+}
+
+/// Writes that failed, in order, as append-only `<first id>.jsonl` files in one directory, along
+/// with a `sent` file holding the id of the last one sent since. That's written after every
+/// record, so a record sent just before a crash isn't sent again afterwards.
+pub struct Spool {
+    directory: PathBuf,
+    max_bytes: u64,
+    /// oldest first
+    segments: Vec<PathBuf>,
+    sent: u64,
+    next_id: u64,
+}
+
+impl Spool {
+    /// Picks up whatever's in `directory` from before. It's created once there's something to keep.
+    pub fn open(directory: PathBuf, max_bytes: u64) -> std::io::Result<Spool> {
+        let sent = match std::fs::read_to_string(directory.join("sent")) {
+            Ok(sent) => sent.trim().parse().unwrap_or_default(),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Ok(Spool {
+                    directory,
+                    max_bytes,
+                    segments: Vec::new(),
+                    sent,
+                    next_id: sent + 1,
+                })
+            }
+            Err(e) => return Err(e),
+        };
+        let mut segments: Vec<PathBuf> = entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .filter(|path| {
+                path.as_ref()
+                    .map_or(true, |path| path.extension().is_some_and(|e| e == "jsonl"))
+            })
+            .collect::<std::io::Result<_>>()?;
+        // names are zero padded, so this is also oldest first
+        segments.sort();
+
+        let mut spool = Spool {
+            directory,
+            max_bytes,
+            segments,
+            sent,
+            next_id: sent + 1,
+        };
+        if let Some(newest) = spool.segments.last() {
+            let records: Vec<Record<serde_json::Value>> = spool.read(newest)?;
+            if let Some(last) = records.last() {
+                spool.next_id = spool.next_id.max(last.id + 1);
+            }
+        }
+        Ok(spool)
+    }
+
+    /// Appends to the newest file, starting a new one once it's full. The oldest files are dropped
+    /// if that takes it over `max_bytes`.
+    pub fn push<T: Serialize + ?Sized>(
+        &mut self,
+        payload: &T,
+        tags: &HashMap<String, String>,
+    ) -> std::io::Result<()> {
+        let record = Record {
+            id: self.next_id,
+            tags: tags.clone(),
+            payload,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        std::fs::create_dir_all(&self.directory)?;
+
+        let segment_bytes = (self.max_bytes / SEGMENTS).max(1);
+        let newest = match self.segments.last() {
+            Some(newest) if file_size(newest)? < segment_bytes => newest.clone(),
+            _ => {
+                let segment = self.directory.join(format!("{:020}.jsonl", self.next_id));
+                self.segments.push(segment.clone());
+                segment
+            }
+        };
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&newest)?
+            .write_all(&line)?;
+        self.next_id += 1;
+
+        let mut total = 0;
+        for segment in &self.segments {
+            total += file_size(segment)?;
+        }
+        while total > self.max_bytes && self.segments.len() > 1 {
+            let oldest = self.segments.remove(0);
+            total -= file_size(&oldest)?;
+            warn!(
+                "{} is full, dropping {}",
+                self.directory.display(),
+                oldest.display()
+            );
+            std::fs::remove_file(&oldest)?;
+        }
+        Ok(())
+    }
+
+    /// What's in the oldest file that hasn't been sent yet, or `None` if there's nothing left
+    fn oldest<T: DeserializeOwned>(&self) -> std::io::Result<Option<Vec<Record<T>>>> {
+        let Some(oldest) = self.segments.first() else {
+            return Ok(None);
+        };
+        let mut records: Vec<Record<T>> = self.read(oldest)?;
+        records.retain(|record| record.id > self.sent);
+        Ok(Some(records))
+    }
+
+    fn sent(&mut self, id: u64) -> std::io::Result<()> {
+        self.sent = id;
+        std::fs::write(self.directory.join("sent"), id.to_string())
+    }
+
+    /// Once everything in it has been sent
+    fn remove_oldest(&mut self) -> std::io::Result<()> {
+        if !self.segments.is_empty() {
+            std::fs::remove_file(self.segments.remove(0))?;
+        }
+        Ok(())
+    }
+
+    /// A line that doesn't parse, most likely one cut short by a crash, is logged and skipped
+    fn read<T: DeserializeOwned>(&self, segment: &Path) -> std::io::Result<Vec<Record<T>>> {
+        let mut records = Vec::new();
+        for line in BufReader::new(std::fs::File::open(segment)?).lines() {
+            match serde_json::from_str(&line?) {
+                Ok(record) => records.push(record),
+                Err(e) => warn!("Skipping a record in {}: {}", segment.display(), e),
+            }
+        }
+        Ok(records)
+    }
+}
+
+fn file_size(path: &Path) -> std::io::Result<u64> {
+    match std::fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// Keeps what `sink` couldn't take in a [Spool], and sends it before anything new once it's back.
+/// The spool is opened on the first write, once the modem's hostname is known.
+pub struct Spooled<S: ?Sized> {
+    settings: SpoolSettings,
+    spool: Option<Spool>,
+    sink: Box<S>,
+}
+
+impl<S: ?Sized> Spooled<S> {
+    pub fn new(sink: Box<S>, settings: SpoolSettings) -> Spooled<S> {
+        Spooled {
+            settings,
+            spool: None,
+            sink,
+        }
+    }
+}
+
+/// `<directory>/<output>-<hostname>`
+fn open_spool(
+    settings: &SpoolSettings,
+    name: &str,
+    tags: &HashMap<String, String>,
+) -> std::io::Result<Spool> {
+    let hostname = tags.get("hostname").map(String::as_str).unwrap_or("modem");
+    let directory = settings.directory.join(format!(
+        "{}-{}",
+        name,
+        hostname.replace(
+            |c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '-',
+            "_"
+        )
+    ));
+    Spool::open(
+        directory,
+        settings.max_megabytes.saturating_mul(1024 * 1024),
+    )
+}
+
+/// Sends everything in `spool`, oldest first, stopping at the first failure
+async fn catch_up_metrics(spool: &mut Spool, sink: &mut dyn MetricSink) -> Result<(), SinkError> {
+    while let Some(records) = spool.oldest::<ScrapedMetrics>()? {
+        for record in records {
+            sink.write_metrics(&record.payload, &record.tags).await?;
+            spool.sent(record.id)?;
+        }
+        spool.remove_oldest()?;
+    }
+    Ok(())
+}
+
+/// Sends everything in `spool`, oldest first, stopping at the first failure
+async fn catch_up_logs(spool: &mut Spool, sink: &mut dyn LogSink) -> Result<(), SinkError> {
+    while let Some(records) = spool.oldest::<Vec<LogEntry>>()? {
+        for record in records {
+            sink.write_logs(&record.payload, &record.tags).await?;
+            spool.sent(record.id)?;
+        }
+        spool.remove_oldest()?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl MetricSink for Spooled<dyn MetricSink> {
+    fn name(&self) -> &'static str {
+        self.sink.name()
+    }
+
+    async fn write_metrics(
+        &mut self,
+        scraped: &ScrapedMetrics,
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let spool = match &mut self.spool {
+            Some(spool) => spool,
+            None => match open_spool(&self.settings, self.sink.name(), tags) {
+                Ok(spool) => self.spool.insert(spool),
+                Err(e) => {
+                    warn!("Unable to open the {} spool: {}", self.sink.name(), e);
+                    return self.sink.write_metrics(scraped, tags).await;
+                }
+            },
+        };
+        let mut result = catch_up_metrics(spool, self.sink.as_mut()).await;
+        if result.is_ok() {
+            result = self.sink.write_metrics(scraped, tags).await;
+        }
+        if result.is_err() {
+            spool.push(scraped, tags)?;
+        }
+        result
+    }
+
+    fn take_state(&mut self) -> Option<SinkState> {
+        self.sink.take_state()
+    }
+
+    fn restore(&mut self, state: SinkState) {
+        self.sink.restore(state)
+    }
+}
+
+#[async_trait]
+impl LogSink for Spooled<dyn LogSink> {
+    fn name(&self) -> &'static str {
+        self.sink.name()
+    }
+
+    async fn write_logs(
+        &mut self,
+        logs: &[LogEntry],
+        tags: &HashMap<String, String>,
+    ) -> Result<(), SinkError> {
+        let spool = match &mut self.spool {
+            Some(spool) => spool,
+            None => match open_spool(&self.settings, self.sink.name(), tags) {
+                Ok(spool) => self.spool.insert(spool),
+                Err(e) => {
+                    warn!("Unable to open the {} spool: {}", self.sink.name(), e);
+                    return self.sink.write_logs(logs, tags).await;
+                }
+            },
+        };
+        let mut result = catch_up_logs(spool, self.sink.as_mut()).await;
+        if result.is_ok() {
+            result = self.sink.write_logs(logs, tags).await;
+        }
+        if result.is_err() {
+            spool.push(logs, tags)?;
+        }
+        result
+    }
+
+    fn dedup_entries(&self) -> Option<usize> {
+        self.sink.dedup_entries()
+    }
+
+    fn take_state(&mut self) -> Option<SinkState> {
+        self.sink.take_state()
+    }
+
+    fn restore(&mut self, state: SinkState) {
+        self.sink.restore(state)
+    }
+}
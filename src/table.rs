@@ -0,0 +1,161 @@
+//! Plain text tables of a single scrape, for `scrape-once`
+
+use crate::scraped::ScrapedMetrics;
+use modem_scraper_lib::payloads::{Channel, LogEntry};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// How many log lines to show, newest first
+const RECENT_LOGS: usize = 10;
+
+fn mhz(hz: u32) -> String {
+    format!("{:.1}", f64::from(hz) / 1_000_000.0)
+}
+
+fn locked(lock_status: bool) -> &'static str {
+    if lock_status {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Pads every column to its widest cell
+fn write_table(out: &mut String, header: &[&str], rows: &[Vec<String>]) {
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].len())
+                .chain(std::iter::once(header[i].len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let mut write_row = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+            .collect();
+        let _ = writeln!(out, "  {}", line.join("  "));
+    };
+    write_row(header.to_vec());
+    for row in rows {
+        write_row(row.iter().map(String::as_str).collect());
+    }
+}
+
+/// Identity, status, a table per channel direction and the most recent log lines
+pub fn render(
+    scraped: &ScrapedMetrics,
+    logs: &[LogEntry],
+    tags: &HashMap<String, String>,
+) -> String {
+    let mut out = String::new();
+    let tag = |name: &str| tags.get(name).map(String::as_str).unwrap_or("unknown");
+    let _ = writeln!(out, "{} ({})", tag("hostname"), tag("model"));
+    if let Some(firmware_version) = tags.get("firmware_version") {
+        let _ = writeln!(out, "firmware {}", firmware_version);
+    }
+    if let Some(uptime) = scraped.metrics.uptime {
+        let seconds = uptime.as_secs();
+        let _ = writeln!(
+            out,
+            "up {} days {:02}:{:02}:{:02}",
+            seconds / 86400,
+            seconds % 86400 / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        );
+    }
+    if let Some(health) = scraped.health() {
+        let _ = writeln!(out, "health {:.0}", health);
+    }
+
+    let mut downstream = Vec::new();
+    let mut upstream = Vec::new();
+    for channel in &scraped.metrics.channels {
+        let health = format!("{:.0}", scraped.channel_health(channel));
+        match channel {
+            Channel::Downstream(c) => downstream.push(vec![
+                c.channel_id.to_string(),
+                locked(c.lock_status).to_owned(),
+                c.modulation.to_string(),
+                mhz(c.frequency),
+                format!("{:.1}", c.power),
+                format!("{:.1}", c.snr),
+                c.corrected.to_string(),
+                c.uncorrectables.to_string(),
+                health,
+            ]),
+            Channel::Upstream(c) => upstream.push(vec![
+                c.channel_id.to_string(),
+                locked(c.lock_status).to_owned(),
+                c.modulation.to_string(),
+                mhz(c.frequency),
+                mhz(c.width),
+                format!("{:.1}", c.power),
+                health,
+            ]),
+            Channel::OfdmaUpstream(c) => upstream.push(vec![
+                c.channel_id.to_string(),
+                locked(c.lock_status).to_owned(),
+                "OFDMA".to_owned(),
+                mhz(c.frequency),
+                mhz(c.width),
+                format!("{:.1}", c.power),
+                health,
+            ]),
+        }
+    }
+    if !downstream.is_empty() {
+        let _ = writeln!(out, "\nDownstream");
+        write_table(
+            &mut out,
+            &[
+                "ID",
+                "Locked",
+                "Modulation",
+                "MHz",
+                "dBmV",
+                "SNR dB",
+                "Corrected",
+                "Uncorrectable",
+                "Health",
+            ],
+            &downstream,
+        );
+    }
+    if !upstream.is_empty() {
+        let _ = writeln!(out, "\nUpstream");
+        write_table(
+            &mut out,
+            &[
+                "ID",
+                "Locked",
+                "Modulation",
+                "MHz",
+                "Width MHz",
+                "dBmV",
+                "Health",
+            ],
+            &upstream,
+        );
+    }
+
+    if !logs.is_empty() {
+        let _ = writeln!(out, "\nRecent logs");
+        let mut recent: Vec<&LogEntry> = logs.iter().collect();
+        recent.sort_by_key(|log_entry| std::cmp::Reverse(log_entry.timestamp));
+        for log_entry in recent.into_iter().take(RECENT_LOGS) {
+            let _ = writeln!(
+                out,
+                "  {} {:<5} {}",
+                log_entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                log_entry.level,
+                log_entry.message
+            );
+        }
+    }
+    out
+}
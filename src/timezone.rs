@@ -0,0 +1,23 @@
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use modem_scraper_lib::payloads::{LogEntry, Metrics};
+
+/// The modem clients read the modem's clock as UTC. For a modem that's actually on `timezone`,
+/// this reads it as that instead. Times that don't exist there (skipped over by DST) are left
+/// alone, and ambiguous ones take the earlier reading.
+pub fn localize(timestamp: DateTime<Utc>, timezone: Tz) -> DateTime<Utc> {
+    timezone
+        .from_local_datetime(&timestamp.naive_utc())
+        .earliest()
+        .map_or(timestamp, |local| local.with_timezone(&Utc))
+}
+
+pub fn localize_metrics(metrics: &mut Metrics, timezone: Tz) {
+    metrics.time = metrics.time.map(|time| localize(time, timezone));
+}
+
+pub fn localize_logs(logs: &mut [LogEntry], timezone: Tz) {
+    for log_entry in logs {
+        log_entry.timestamp = localize(log_entry.timestamp, timezone);
+    }
+}